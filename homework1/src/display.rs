@@ -15,8 +15,6 @@ use image::{save_buffer, ColorType};
 use std::path::Path;
 use std::time::{Duration, Instant};
 
-use crate::rst;
-
 pub fn save_image<P: AsRef<Path>>(path: P, data: &[u8], width: u32, height: u32) -> Result<()> {
     save_buffer(path, data, width, height, ColorType::Rgb8)?;
     Ok(())
@@ -193,8 +191,9 @@ impl ImageDisplayer {
         })
     }
 
-    pub fn display_image(&self, r: &rst::Rasterizer, wh: (u32, u32)) -> Result<()> {
-        let image = RawImage2d::from_raw_rgb_reversed(r.data(), wh);
+    // data: rgb rows from the top, as `rst::Rasterizer::data`
+    pub fn display_image(&self, data: &[u8], wh: (u32, u32)) -> Result<()> {
+        let image = RawImage2d::from_raw_rgb_reversed(data, wh);
         let opengl_texture = CompressedSrgbTexture2d::new(&self.display, image)?;
         let uniforms = uniform! {
             matrix: [
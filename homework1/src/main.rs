@@ -15,6 +15,7 @@ mod triangle;
 
 fn main() -> Result<()> {
     let mut angle = 0.0f32;
+    let mut show_depth = false;
     let mut r = rst::Rasterizer::new(700, 700);
     let eye_pos = Vec3::new(0.0, 0.0, 5.0);
     let pos = vec![
@@ -34,6 +35,12 @@ fn main() -> Result<()> {
             Action::Stop => return display::save_image("output.png", r.data(), 700, 700),
             Action::Key(VirtualKeyCode::A) => angle += 10.0,
             Action::Key(VirtualKeyCode::D) => angle -= 10.0,
+            Action::Key(VirtualKeyCode::C) => show_depth = false,
+            Action::Key(VirtualKeyCode::Z) => show_depth = true,
+            Action::Key(VirtualKeyCode::G) => {
+                display::save_image("output-depth.png", &r.depth_data(), 700, 700)?;
+                dbg!("output-depth.png");
+            }
             _ => (),
         }
         r.clear(rst::Buffers::all());
@@ -41,7 +48,10 @@ fn main() -> Result<()> {
         r.set_view(get_view_matrix(eye_pos));
         r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
         r.draw(pos_id, ind_id, rst::Primitive::Triangle);
-        displayer.display_image(&r, (700, 700))
+        match show_depth {
+            true => displayer.display_image(&r.depth_data(), (700, 700)),
+            false => displayer.display_image(r.data(), (700, 700)),
+        }
     });
     Ok(())
 }
@@ -60,7 +60,7 @@ impl Rasterizer {
             next_id: 0,
         };
         res.frame_buf.resize((w * h) as usize, Rgb::default());
-        res.depth_buf.resize((w * h) as usize, 0.0);
+        res.depth_buf.resize((w * h) as usize, f32::INFINITY);
         res
     }
 
@@ -105,7 +105,7 @@ impl Rasterizer {
             self.frame_buf.fill(Rgb::default());
         }
         if buffers.contains(Buffers::DEPTH) {
-            self.depth_buf.fill(f32::default());
+            self.depth_buf.fill(f32::INFINITY);
         }
     }
 
@@ -160,6 +160,46 @@ impl Rasterizer {
         }
     }
 
+    /**
+     * the depth buffer as an rgb image, for debugging, laid out as `data`:
+     * linear view space depth (-z), normalized, the nearer the brighter,
+     * and black where nothing is drawn.
+     */
+    pub fn depth_data(&self) -> Vec<u8> {
+        let f1 = (100.0 - 0.1) / 2.0;
+        let f2 = (100.0 + 0.1) / 2.0;
+        let inv_projection = self.projection.inverse();
+        let depth: Vec<f32> = self
+            .depth_buf
+            .iter()
+            .map(|z| match z.is_finite() {
+                true => {
+                    -inv_projection
+                        .project_point3(Vec3::new(0.0, 0.0, (z - f2) / f1))
+                        .z
+                }
+                false => f32::INFINITY,
+            })
+            .collect();
+
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for d in depth.iter().filter(|d| d.is_finite()) {
+            min = min.min(*d);
+            max = max.max(*d);
+        }
+        let range = (max - min).max(f32::EPSILON);
+        depth
+            .iter()
+            .flat_map(|d| {
+                let gray = match d.is_finite() {
+                    true => ((1.0 - (d - min) / range) * 255.0) as u8,
+                    false => 0,
+                };
+                [gray; 3]
+            })
+            .collect()
+    }
+
     fn draw_line(&mut self, begin: Vec3, end: Vec3, line_color: Rgb) {
         let x1 = begin.x;
         let y1 = begin.y;
@@ -185,8 +225,13 @@ impl Rasterizer {
                 y = y2 as i32;
                 xe = x1 as i32;
             }
-            let mut point = Vec3::new(x as f32, y as f32, 1.0);
-            self.set_pixel(&point, &line_color);
+            // the depth at x, interpolated between the ends
+            let z = |x: i32| match dx {
+                0 => begin.z.min(end.z),
+                _ => begin.z + (end.z - begin.z) * (x as f32 - x1) / (x2 - x1),
+            };
+            let mut point = Vec3::new(x as f32, y as f32, z(x));
+            self.set_line_pixel(&point, &line_color);
 
             while x < xe {
                 x = x + 1;
@@ -200,8 +245,8 @@ impl Rasterizer {
                     }
                     px = px + 2 * (dy1 - dx1);
                 }
-                point = Vec3::new(x as f32, y as f32, 1.0);
-                self.set_pixel(&point, &line_color);
+                point = Vec3::new(x as f32, y as f32, z(x));
+                self.set_line_pixel(&point, &line_color);
             }
         } else {
             if dy >= 0 {
@@ -213,9 +258,14 @@ impl Rasterizer {
                 y = y2 as i32;
                 ye = y1 as i32;
             }
+            // the depth at y, interpolated between the ends
+            let z = |y: i32| match dy {
+                0 => begin.z.min(end.z),
+                _ => begin.z + (end.z - begin.z) * (y as f32 - y1) / (y2 - y1),
+            };
 
-            let mut point = Vec3::new(x as f32, y as f32, 1.0);
-            self.set_pixel(&point, &line_color);
+            let mut point = Vec3::new(x as f32, y as f32, z(y));
+            self.set_line_pixel(&point, &line_color);
 
             while y < ye {
                 y = y + 1;
@@ -231,12 +281,27 @@ impl Rasterizer {
                     py = py + 2 * (dx1 - dy1);
                 }
 
-                point = Vec3::new(x as f32, y as f32, 1.0);
-                self.set_pixel(&point, &line_color);
+                point = Vec3::new(x as f32, y as f32, z(y));
+                self.set_line_pixel(&point, &line_color);
             }
         }
     }
 
+    // set the pixel, and keep the nearest depth drawn there.
+    // lines are drawn without depth test, so the colors are the same as before
+    fn set_line_pixel(&mut self, point: &Vec3, color: &Rgb) {
+        self.set_pixel(point, color);
+        if point.x < 0.0
+            || point.x as u32 >= self.width
+            || point.y < 0.0
+            || point.y as u32 >= self.height
+        {
+            return;
+        }
+        let ind = ((self.height - 1 - point.y as u32) * self.width + point.x as u32) as usize;
+        self.depth_buf[ind] = self.depth_buf[ind].min(point.z);
+    }
+
     fn rasterize_wireframe(&mut self, t: &Triangle) {
         self.draw_line(t.c(), t.a(), t.color[0]);
         self.draw_line(t.c(), t.b(), t.color[1]);
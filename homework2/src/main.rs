@@ -2,12 +2,15 @@ use anyhow::Result;
 use homework2::{get_model_matrix, get_projection_matrix, get_view_matrix};
 use utils::graphic::{save_image, start_loop, Action, Control, Key};
 
-use glam::Vec3;
+use glam::{Vec3, Vec4};
 
 mod rst;
 
 fn main() -> Result<()> {
     let mut angle = 0.0f32;
+    let mut transparency = rst::Transparency::Opaque;
+    let mut reversed = false;
+    let mut show_depth = false;
     let mut r = rst::Rasterizer::new(700, 700);
     // let mut r = rst::Rasterizer::new(20, 20);
     let eye_pos = Vec3::new(0.0, 0.0, 5.0);
@@ -21,16 +24,18 @@ fn main() -> Result<()> {
     ];
     let ind = vec![[0, 1, 2], [3, 4, 5]];
     let cols = vec![
-        Vec3::new(217.0, 238.0, 185.0),
-        Vec3::new(217.0, 238.0, 185.0),
-        Vec3::new(217.0, 238.0, 185.0),
-        Vec3::new(185.0, 217.0, 238.0),
-        Vec3::new(185.0, 217.0, 238.0),
-        Vec3::new(185.0, 217.0, 238.0),
+        Vec4::new(217.0, 238.0, 185.0, 0.6),
+        Vec4::new(217.0, 238.0, 185.0, 0.6),
+        Vec4::new(217.0, 238.0, 185.0, 0.6),
+        Vec4::new(185.0, 217.0, 238.0, 0.8),
+        Vec4::new(185.0, 217.0, 238.0, 0.8),
+        Vec4::new(185.0, 217.0, 238.0, 0.8),
     ];
     let pos_id = r.load_positions(pos);
+    // the same triangles drawn the other way round, R switches between them
+    let reversed_ind_id = r.load_indices(ind.iter().rev().copied().collect());
     let ind_id = r.load_indices(ind);
-    let col_id = r.load_colors(cols);
+    let col_id = r.load_colors_rgba(cols);
 
     start_loop(700, 700, move |actions, display_image| {
         for action in actions {
@@ -41,6 +46,22 @@ fn main() -> Result<()> {
                 }
                 Action::Key(Key::A) => angle += 10.0,
                 Action::Key(Key::D) => angle -= 10.0,
+                Action::Key(Key::T) => {
+                    transparency = match transparency {
+                        rst::Transparency::Opaque => rst::Transparency::SortedTriangles,
+                        rst::Transparency::SortedTriangles => rst::Transparency::ABuffer,
+                        rst::Transparency::ABuffer => rst::Transparency::Opaque,
+                    };
+                    dbg!(transparency);
+                    r.set_transparency(transparency);
+                }
+                Action::Key(Key::R) => reversed = !reversed,
+                Action::Key(Key::C) => show_depth = false,
+                Action::Key(Key::Z) => show_depth = true,
+                Action::Key(Key::G) => {
+                    save_image(&r.depth_view(), "output-depth.png")?;
+                    dbg!("output-depth.png");
+                }
                 _ => (),
             }
         }
@@ -48,8 +69,13 @@ fn main() -> Result<()> {
         r.set_model(get_model_matrix(angle));
         r.set_view(get_view_matrix(eye_pos));
         r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+        let ind_id = if reversed { reversed_ind_id } else { ind_id };
         r.draw(pos_id, ind_id, col_id, rst::Primitive::Triangle);
-        display_image(&r)?;
+        r.resolve();
+        match show_depth {
+            true => display_image(&r.depth_view())?,
+            false => display_image(&r)?,
+        }
         Ok(Control::Continue)
     });
     Ok(())
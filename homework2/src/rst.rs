@@ -3,9 +3,51 @@ use std::collections::HashMap;
 use glam::{Mat4, Vec3, Vec4};
 use homework2::inside_triangle;
 
-pub use utils::rasterizer::{Buffers, ColBufId, IndBufId, PosBufId, Primitive, Rasterizable};
+pub use utils::rasterizer::{
+    BufRasterizer, Buffers, ColBufId, IndBufId, PosBufId, Primitive, Rasterizable,
+};
 pub use utils::triangle::{Rgb, Triangle};
 
+/**
+ * how fragments with alpha less than 1 are drawn
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transparency {
+    // alpha is ignored, every fragment passing the depth test overwrites the pixel
+    Opaque,
+    // triangles with any vertex alpha less than 1, or with any fragment made translucent by the shader,
+    // are kept until `resolve`, then blended from the farthest to the nearest by their centers.
+    // it's wrong for triangles intersecting each other.
+    SortedTriangles,
+    // translucent fragments are kept in a linked list of every pixel (A-buffer),
+    // then sorted and blended per pixel in `resolve`.
+    ABuffer,
+}
+
+/**
+ * a fragment passed to the fragment shader
+ */
+pub struct Fragment {
+    pub screen_pos: Vec3,
+    // 0..255
+    pub color: Vec3,
+    // 0 for fully transparent, 1 for opaque
+    pub alpha: f32,
+}
+
+// return (r, g, b, alpha)
+pub type FragmentShader = fn(&Fragment) -> Vec4;
+
+// a node of the per pixel linked list
+struct ABufferNode {
+    depth: f32,
+    color: Vec3,
+    alpha: f32,
+    next: u32,
+}
+
+const A_BUFFER_END: u32 = u32::MAX;
+
 pub struct Rasterizer {
     model: Mat4,
     view: Mat4,
@@ -14,10 +56,20 @@ pub struct Rasterizer {
     pos_buf: HashMap<u32, Vec<Vec3>>,
     ind_buf: HashMap<u32, Vec<[usize; 3]>>,
     col_buf: HashMap<u32, Vec<Rgb>>,
+    // the alpha of every color buffer, by the same id
+    alpha_buf: HashMap<u32, Vec<f32>>,
 
     frame_buf: Vec<Rgb>,
     depth_buf: Vec<f32>,
 
+    fragment_shader: Option<FragmentShader>,
+    transparency: Transparency,
+    // translucent triangles waiting for `resolve`, in SortedTriangles
+    translucent_triangles: Vec<(Triangle, [f32; 3])>,
+    // the first node of every pixel and all the nodes, in ABuffer
+    a_buffer_heads: Vec<u32>,
+    a_buffer_nodes: Vec<ABufferNode>,
+
     width: u32,
     height: u32,
     next_id: u32,
@@ -42,14 +94,21 @@ impl Rasterizer {
             pos_buf: HashMap::default(),
             ind_buf: HashMap::default(),
             col_buf: HashMap::default(),
+            alpha_buf: HashMap::default(),
             frame_buf: Vec::new(),
             depth_buf: Vec::default(),
+            fragment_shader: None,
+            transparency: Transparency::Opaque,
+            translucent_triangles: Vec::new(),
+            a_buffer_heads: Vec::new(),
+            a_buffer_nodes: Vec::new(),
             width: w,
             height: h,
             next_id: 0,
         };
         res.frame_buf.resize((w * h) as usize, Rgb::default());
         res.depth_buf.resize((w * h) as usize, f32::INFINITY);
+        res.a_buffer_heads.resize((w * h) as usize, A_BUFFER_END);
         res
     }
 
@@ -69,9 +128,30 @@ impl Rasterizer {
         let id = self.get_next_id();
         let rgbs = colors.iter().map(|c| Rgb::from(c)).collect();
         self.col_buf.insert(id, rgbs);
+        self.alpha_buf.insert(id, vec![1.0; colors.len()]);
         ColBufId(id)
     }
 
+    /**
+     * colors with alpha, (r, g, b) in 0..255 and alpha in 0..1
+     */
+    pub fn load_colors_rgba(&mut self, colors: Vec<Vec4>) -> ColBufId {
+        let id = self.get_next_id();
+        let rgbs = colors.iter().map(|c| Rgb::from(&c.truncate())).collect();
+        self.col_buf.insert(id, rgbs);
+        self.alpha_buf
+            .insert(id, colors.iter().map(|c| c.w).collect());
+        ColBufId(id)
+    }
+
+    pub fn set_fragment_shader(&mut self, shader: FragmentShader) {
+        self.fragment_shader = Some(shader);
+    }
+
+    pub fn set_transparency(&mut self, transparency: Transparency) {
+        self.transparency = transparency;
+    }
+
     pub fn set_model(&mut self, model: Mat4) {
         self.model = model;
     }
@@ -103,7 +183,73 @@ impl Rasterizer {
         }
         if buffers.contains(Buffers::DEPTH) {
             self.depth_buf.fill(f32::INFINITY);
+            self.translucent_triangles.clear();
+            self.a_buffer_heads.fill(A_BUFFER_END);
+            self.a_buffer_nodes.clear();
+        }
+    }
+
+    /**
+     * blend the translucent fragments kept by SortedTriangles or ABuffer onto the frame.
+     * it should be called after all draws of a frame.
+     */
+    pub fn resolve(&mut self) {
+        let mut triangles = std::mem::take(&mut self.translucent_triangles);
+        // the depth grows with the distance, so the farthest comes first
+        triangles.sort_by(|(a, _), (b, _)| {
+            let center_z = |t: &Triangle| t.v.iter().map(|v| v.z).sum::<f32>();
+            center_z(b).total_cmp(&center_z(a))
+        });
+        for (t, alpha) in &triangles {
+            self.rasterize_triangle(t, *alpha, true);
+        }
+
+        for ind in 0..self.a_buffer_heads.len() {
+            let mut fragments = vec![];
+            let mut node = self.a_buffer_heads[ind];
+            while node != A_BUFFER_END {
+                let n = &self.a_buffer_nodes[node as usize];
+                // opaque fragments drawn after it may cover it
+                if n.depth < self.depth_buf[ind] {
+                    fragments.push((n.depth, n.color, n.alpha));
+                }
+                node = n.next;
+            }
+            fragments.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            let mut color = Vec3::from(self.frame_buf[ind]);
+            for (_, c, alpha) in fragments {
+                color = color.lerp(c, alpha);
+            }
+            self.frame_buf[ind] = Rgb::from(&color);
         }
+        self.a_buffer_heads.fill(A_BUFFER_END);
+        self.a_buffer_nodes.clear();
+    }
+
+    /**
+     * render the depth buffer into an image, for debugging:
+     * linear view space depth (-z), normalized, the nearer the brighter.
+     * it could be displayed or saved the same as the rasterizer itself.
+     */
+    pub fn depth_view(&self) -> BufRasterizer {
+        let f1 = (50.0 - 0.1) / 2.0;
+        let f2 = (50.0 + 0.1) / 2.0;
+        let inv_projection = self.projection.inverse();
+        // laid out as the frame buffer, so the image is the same way up
+        let depth: Vec<f32> = self
+            .depth_buf
+            .iter()
+            .map(|z| match z.is_finite() {
+                true => {
+                    -inv_projection
+                        .project_point3(Vec3::new(0.0, 0.0, (z - f2) / f1))
+                        .z
+                }
+                false => f32::INFINITY,
+            })
+            .collect();
+        BufRasterizer::from_depth(&depth, self.width, self.height)
     }
 
     pub fn draw(
@@ -120,6 +266,7 @@ impl Rasterizer {
         let buf = self.pos_buf.get(&pos_buf_id.0).unwrap().clone();
         let ind = self.ind_buf.get(&ind_buf_id.0).unwrap().clone();
         let col = self.col_buf.get(&col_buf_id.0).unwrap().clone();
+        let alpha = self.alpha_buf.get(&col_buf_id.0).unwrap().clone();
 
         let f1 = (50.0 - 0.1) / 2.0;
         let f2 = (50.0 + 0.1) / 2.0;
@@ -154,12 +301,25 @@ impl Rasterizer {
                 t.set_color(vert_ind, col[i[vert_ind]]);
             }
 
-            self.rasterize_triangle(&t);
+            let alpha = [alpha[i[0]], alpha[i[1]], alpha[i[2]]];
+            if self.transparency == Transparency::SortedTriangles && alpha.iter().any(|a| *a < 1.0)
+            {
+                self.translucent_triangles.push((t, alpha));
+                continue;
+            }
+            if self.rasterize_triangle(&t, alpha, false) {
+                self.translucent_triangles.push((t, alpha));
+            }
         }
     }
 
     //Screen space rasterization
-    fn rasterize_triangle(&mut self, t: &Triangle) {
+    // blend: blend the fragments onto the frame without writing the depth,
+    // for the translucent triangles of SortedTriangles
+    // return: whether any fragment was made translucent by the shader and left out, in SortedTriangles.
+    // the triangle should be blended in `resolve` then, its opaque fragments are already drawn.
+    fn rasterize_triangle(&mut self, t: &Triangle, vertex_alpha: [f32; 3], blend: bool) -> bool {
+        let mut left_out = false;
         // get the bounding box of the triangle
         let mut max_x = 0.0f32;
         let mut max_y = 0.0f32;
@@ -201,12 +361,43 @@ impl Rasterizer {
                     continue;
                 }
                 let buf_ind = ((self.height - 1 - y) * self.width + x) as usize;
-                if z_interpolated < self.depth_buf[buf_ind] {
-                    self.set_pixel(&Vec3::new(xc, yc, z_interpolated), &t.get_color());
+                if z_interpolated >= self.depth_buf[buf_ind] {
+                    continue;
+                }
+
+                let fragment = Fragment {
+                    screen_pos: Vec3::new(xc, yc, z_interpolated),
+                    color: Vec3::from(t.get_color()),
+                    alpha: vertex_alpha[0] * alpha
+                        + vertex_alpha[1] * beta
+                        + vertex_alpha[2] * gama,
+                };
+                let color = match self.fragment_shader {
+                    Some(shader) => shader(&fragment),
+                    None => to_vec4(fragment.color, fragment.alpha),
+                };
+
+                if blend {
+                    let dst = Vec3::from(self.frame_buf[buf_ind]);
+                    let src = dst.lerp(color.truncate(), color.w);
+                    self.frame_buf[buf_ind] = Rgb::from(&src);
+                } else if self.transparency == Transparency::ABuffer && color.w < 1.0 {
+                    self.a_buffer_nodes.push(ABufferNode {
+                        depth: z_interpolated,
+                        color: color.truncate(),
+                        alpha: color.w,
+                        next: self.a_buffer_heads[buf_ind],
+                    });
+                    self.a_buffer_heads[buf_ind] = (self.a_buffer_nodes.len() - 1) as u32;
+                } else if self.transparency == Transparency::SortedTriangles && color.w < 1.0 {
+                    left_out = true;
+                } else {
+                    self.set_pixel(&fragment.screen_pos, &Rgb::from(&color.truncate()));
                     self.depth_buf[buf_ind] = z_interpolated;
                 }
             }
         }
+        left_out
     }
 
     fn draw_line(&mut self, begin: Vec3, end: Vec3, line_color: Rgb) {
@@ -318,3 +509,161 @@ fn compute_barcentric_2d(x: f32, y: f32, v: [Vec3; 3]) -> [f32; 3] {
             - v[1].x * v[0].y);
     [c1, c2, c3]
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use homework2::{get_model_matrix, get_projection_matrix, get_view_matrix};
+
+    // the two triangles of the demo, the first one is the nearer
+    fn draw(transparency: Transparency, ind: Vec<[usize; 3]>, alpha: f32) -> Rasterizer {
+        draw_shaded(transparency, ind, alpha, None)
+    }
+
+    fn draw_shaded(
+        transparency: Transparency,
+        ind: Vec<[usize; 3]>,
+        alpha: f32,
+        shader: Option<FragmentShader>,
+    ) -> Rasterizer {
+        let mut r = Rasterizer::new(64, 64);
+        r.fragment_shader = shader;
+        let pos = vec![
+            Vec3::new(2.0, 0.0, -2.0),
+            Vec3::new(0.0, 2.0, -2.0),
+            Vec3::new(-2.0, 0.0, -2.0),
+            Vec3::new(3.5, -1.0, -5.0),
+            Vec3::new(2.5, 1.5, -5.0),
+            Vec3::new(-1.0, 0.5, -5.0),
+        ];
+        let cols = [0, 1, 2, 3, 4, 5].map(|i| match i < 3 {
+            true => Vec4::new(200.0, 100.0, 0.0, alpha),
+            false => Vec4::new(0.0, 100.0, 200.0, alpha),
+        });
+        let pos_id = r.load_positions(pos);
+        let ind_id = r.load_indices(ind);
+        let col_id = r.load_colors_rgba(cols.to_vec());
+
+        r.set_transparency(transparency);
+        r.clear(Buffers::all());
+        r.set_model(get_model_matrix(0.0));
+        r.set_view(get_view_matrix(Vec3::new(0.0, 0.0, 5.0)));
+        r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+        r.draw(pos_id, ind_id, col_id, Primitive::Triangle);
+        r.resolve();
+        r
+    }
+
+    #[test]
+    fn test_transparency_order_independent() {
+        let white = Rgb::new(255, 255, 255);
+        let near = draw(Transparency::Opaque, vec![[0, 1, 2]], 1.0);
+        let far = draw(Transparency::Opaque, vec![[3, 4, 5]], 1.0);
+        let overlap = (0..near.frame_buf.len())
+            .find(|i| near.frame_buf[*i] != white && far.frame_buf[*i] != white)
+            .unwrap();
+
+        // far over the white background, then near over it
+        let expected = Vec3::splat(255.0)
+            .lerp(Vec3::new(0.0, 100.0, 200.0), 0.5)
+            .lerp(Vec3::new(200.0, 100.0, 0.0), 0.5);
+
+        for transparency in [Transparency::SortedTriangles, Transparency::ABuffer] {
+            let near_first = draw(transparency, vec![[0, 1, 2], [3, 4, 5]], 0.5);
+            let far_first = draw(transparency, vec![[3, 4, 5], [0, 1, 2]], 0.5);
+            assert_eq!(near_first.frame_buf, far_first.frame_buf);
+
+            let color = Vec3::from(near_first.frame_buf[overlap]);
+            assert!(
+                color.abs_diff_eq(expected, 2.0),
+                "{:?} {:?}",
+                color,
+                expected
+            );
+        }
+
+        // without transparency the near one covers the far one
+        let opaque = draw(Transparency::Opaque, vec![[0, 1, 2], [3, 4, 5]], 0.5);
+        assert_eq!(opaque.frame_buf[overlap], near.frame_buf[overlap]);
+    }
+
+    #[test]
+    fn test_fragment_shader_alpha() {
+        // opaque vertices made translucent by the shader
+        fn shader(fragment: &Fragment) -> Vec4 {
+            to_vec4(fragment.color, 0.0)
+        }
+        let mut r = Rasterizer::new(64, 64);
+        let pos_id = r.load_positions(vec![
+            Vec3::new(2.0, 0.0, -2.0),
+            Vec3::new(0.0, 2.0, -2.0),
+            Vec3::new(-2.0, 0.0, -2.0),
+        ]);
+        let ind_id = r.load_indices(vec![[0, 1, 2]]);
+        let col_id = r.load_colors(vec![Vec3::ZERO; 3]);
+        r.set_fragment_shader(shader);
+        r.set_transparency(Transparency::ABuffer);
+        r.clear(Buffers::all());
+        r.set_view(get_view_matrix(Vec3::new(0.0, 0.0, 5.0)));
+        r.set_projection(get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+        r.draw(pos_id, ind_id, col_id, Primitive::Triangle);
+        assert!(!r.a_buffer_nodes.is_empty());
+        r.resolve();
+
+        assert!(r.frame_buf.iter().all(|c| *c == Rgb::new(255, 255, 255)));
+        assert!(r.depth_buf.iter().all(|d| *d == f32::INFINITY));
+    }
+
+    #[test]
+    fn test_sorted_triangles_shader_alpha() {
+        // opaque vertices made half transparent by the shader
+        fn shader(fragment: &Fragment) -> Vec4 {
+            to_vec4(fragment.color, 0.5)
+        }
+        let white = Rgb::new(255, 255, 255);
+        let near = draw(Transparency::Opaque, vec![[0, 1, 2]], 1.0);
+        let far = draw(Transparency::Opaque, vec![[3, 4, 5]], 1.0);
+        let overlap = (0..near.frame_buf.len())
+            .find(|i| near.frame_buf[*i] != white && far.frame_buf[*i] != white)
+            .unwrap();
+
+        // the same as translucent vertices, whichever is drawn first
+        let by_vertices = draw(
+            Transparency::SortedTriangles,
+            vec![[0, 1, 2], [3, 4, 5]],
+            0.5,
+        );
+        for ind in [vec![[0, 1, 2], [3, 4, 5]], vec![[3, 4, 5], [0, 1, 2]]] {
+            let by_shader = draw_shaded(Transparency::SortedTriangles, ind, 1.0, Some(shader));
+            assert_eq!(by_shader.frame_buf, by_vertices.frame_buf);
+            assert_ne!(by_shader.frame_buf[overlap], near.frame_buf[overlap]);
+            // translucent fragments don't hide what's behind them
+            assert!(by_shader.depth_buf.iter().all(|d| *d == f32::INFINITY));
+        }
+    }
+
+    #[test]
+    fn test_depth_view() {
+        let white = Rgb::new(255, 255, 255);
+        let near = draw(Transparency::Opaque, vec![[0, 1, 2]], 1.0);
+        let far = draw(Transparency::Opaque, vec![[3, 4, 5]], 1.0);
+        let both = draw(Transparency::Opaque, vec![[0, 1, 2], [3, 4, 5]], 1.0);
+        let view = both.depth_view();
+        assert_eq!(view.size(), both.size());
+
+        // the nearest is white, the farthest is black, and so is the background.
+        // both are flat, up to the precision of the depth buffer
+        let only_near = (0..view.data.len())
+            .find(|i| near.frame_buf[*i] != white && far.frame_buf[*i] == white)
+            .unwrap();
+        let only_far = (0..view.data.len())
+            .find(|i| near.frame_buf[*i] == white && far.frame_buf[*i] != white)
+            .unwrap();
+        let background = (0..view.data.len())
+            .find(|i| both.frame_buf[*i] == white)
+            .unwrap();
+        assert!(view.data[only_near].0 >= 250);
+        assert!(view.data[only_far].0 <= 5);
+        assert_eq!(view.data[background], Rgb::new(0, 0, 0));
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use glam::Vec3;
+use homework3::{
+    get_model_matrix, get_projection_matrix, get_view_matrix, mesh::Mesh, rst, shader,
+};
+use utils::graphic::{save_image, Action, Control, Key};
+
+// a checkerboard floor going away from the eye,
+// press P to switch between perspective correct and affine interpolation.
+// run it from the workspace root:
+// cargo run --release -p homework3 --example checkerboard
+fn main() -> Result<()> {
+    let frame_width = 700;
+    let filename = "output.png";
+    let mut angle = 0.0;
+    let mut interpolation = rst::Interpolation::PerspectiveCorrect;
+
+    let floor = Mesh::floor(8.0);
+
+    let mut r = rst::Rasterizer::new(frame_width, frame_width);
+    let vert_id = r.load_vertices(floor.vertices);
+    let ind_id = r.load_indices(floor.indices);
+    r.set_texture(shader::Texture::checkerboard(512, 16));
+    r.set_vertex_shader(homework3::vertex_shader);
+    r.set_fragment_shader(unlit_texture_fragment_shader);
+
+    let eye_pos = Vec3::new(0.0, 2.0, 12.0);
+
+    utils::graphic::start_loop(frame_width, frame_width, move |actions, display_image| {
+        for action in actions {
+            match action {
+                Action::Stop => {
+                    save_image(&r, filename)?;
+                    return Ok(Control::Stop);
+                }
+                Action::Key(Key::A) => angle -= 10.0,
+                Action::Key(Key::D) => angle += 10.0,
+                Action::Key(Key::P) => {
+                    interpolation = match interpolation {
+                        rst::Interpolation::PerspectiveCorrect => rst::Interpolation::Affine,
+                        rst::Interpolation::Affine => rst::Interpolation::PerspectiveCorrect,
+                    };
+                    dbg!(interpolation);
+                    r.set_interpolation(interpolation);
+                }
+                _ => (),
+            }
+        }
+        r.clear(rst::Buffers::all());
+
+        r.set_model(get_model_matrix(angle, 1.0));
+        r.set_view(get_view_matrix(eye_pos));
+        r.set_projection(get_projection_matrix(45., 1., 0.1, 50.));
+
+        r.draw(vert_id, ind_id, rst::Primitive::Triangle);
+
+        display_image(&r)?;
+        Ok(Control::Continue)
+    });
+
+    Ok(())
+}
+
+// texture color only, without lighting, so the pattern is clear
+fn unlit_texture_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
+    match payload.texture {
+        Some(texture) => texture
+            .get_color(payload.tex_coords.x, payload.tex_coords.y)
+            .into(),
+        None => Vec3::ZERO,
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use glam::Vec3;
+use homework3::{
+    get_model_matrix, get_projection_matrix, get_view_matrix, mesh::Mesh, phong_fragment_shader,
+    rst, vertex_shader,
+};
+
+// compare drawing a model as a triangle list, with the matrices computed per triangle as it used to be,
+// against drawing it indexed, with the vertex cache and the matrices computed per draw,
+// and forward shading against deferred shading,
+// run it from the workspace root:
+// cargo run --release -p homework3 --example draw_bench
+fn main() -> Result<()> {
+    let frame_width = 700;
+    let frames = 100;
+
+    // (model, scale)
+    let models = [
+        ("homework3/models/bunny/bunny.obj", 25.0),
+        ("homework3/models/spot/spot_triangulated_good.obj", 2.5),
+    ];
+
+    for (obj_file, scale) in models {
+        let mesh = Mesh::load_obj(obj_file)?;
+        let triangle_list = mesh.to_triangles();
+        // a draw per triangle, so the matrices are computed for every triangle again
+        let single_triangles: Vec<Vec<_>> = triangle_list.iter().map(|t| vec![t.clone()]).collect();
+        println!(
+            "{}: {} vertices, {} triangles",
+            obj_file,
+            mesh.vertices.len(),
+            mesh.indices.len()
+        );
+
+        let mut r = rst::Rasterizer::new(frame_width, frame_width);
+        r.set_vertex_shader(vertex_shader);
+        r.set_fragment_shader(phong_fragment_shader);
+        r.set_view(get_view_matrix(Vec3::new(0.0, 0.0, 10.0)));
+        r.set_projection(get_projection_matrix(45., 1., 0.1, 50.));
+        let vert_id = r.load_vertices(mesh.vertices);
+        let ind_id = r.load_indices(mesh.indices);
+
+        let start_time = Instant::now();
+        for i in 0..frames {
+            r.clear(rst::Buffers::all());
+            r.set_model(get_model_matrix(i as f32 * 3.6, scale));
+            for triangle in &single_triangles {
+                r.draw_triangle(triangle);
+            }
+        }
+        let per_triangle_cost = start_time.elapsed().as_secs_f32();
+
+        let start_time = Instant::now();
+        for i in 0..frames {
+            r.clear(rst::Buffers::all());
+            r.set_model(get_model_matrix(i as f32 * 3.6, scale));
+            r.draw_triangle(&triangle_list);
+        }
+        let list_cost = start_time.elapsed().as_secs_f32();
+
+        let start_time = Instant::now();
+        for i in 0..frames {
+            r.clear(rst::Buffers::all());
+            r.set_model(get_model_matrix(i as f32 * 3.6, scale));
+            r.draw(vert_id, ind_id, rst::Primitive::Triangle);
+        }
+        let indexed_cost = start_time.elapsed().as_secs_f32();
+
+        r.set_shading(rst::Shading::Deferred);
+        let start_time = Instant::now();
+        for i in 0..frames {
+            r.clear(rst::Buffers::all());
+            r.set_model(get_model_matrix(i as f32 * 3.6, scale));
+            r.draw(vert_id, ind_id, rst::Primitive::Triangle);
+            r.resolve();
+        }
+        let deferred_cost = start_time.elapsed().as_secs_f32();
+        r.set_shading(rst::Shading::Forward);
+
+        println!(
+            "  triangle list, matrices per triangle: {:.3} ms/frame, indexed: {:.3} ms/frame, speedup {:.2}x",
+            per_triangle_cost * 1000.0 / frames as f32,
+            indexed_cost * 1000.0 / frames as f32,
+            per_triangle_cost / indexed_cost
+        );
+        println!(
+            "  triangle list, matrices per draw: {:.3} ms/frame, speedup of the vertex cache alone {:.2}x",
+            list_cost * 1000.0 / frames as f32,
+            list_cost / indexed_cost
+        );
+        println!(
+            "  indexed deferred: {:.3} ms/frame",
+            deferred_cost * 1000.0 / frames as f32
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use glam::{Mat4, Vec3};
+use homework3::{
+    get_model_matrix, get_projection_matrix, get_view_matrix, mesh::Mesh, phong_fragment_shader,
+    rst, shadow::ShadowMap, Light,
+};
+use utils::graphic::{save_image, Action, Control, Key};
+
+// spot casting a shadow onto the floor.
+// A/D rotate spot, W/S move the bias up and down, Q/E change the pcf radius.
+// run it from the workspace root:
+// cargo run --release -p homework3 --example shadow
+fn main() -> Result<()> {
+    let frame_width = 700;
+    let shadow_map_size = 1024;
+    let filename = "output.png";
+    let mut angle = 140.0;
+    let scale = 2.5;
+
+    let spot = Mesh::load_obj("homework3/models/spot/spot_triangulated_good.obj")?;
+    let floor = Mesh::floor(6.0);
+    let floor_model = Mat4::from_translation(Vec3::new(0.0, -1.8, 0.0));
+
+    let light = Light {
+        position: Vec3::new(-4.0, 10.0, 6.0),
+        intensity: Vec3::ONE * 150.0,
+    };
+    let shadow_map = ShadowMap::new(light, Vec3::ZERO, shadow_map_size, 60.0);
+
+    // the shadow pass and the camera pass share the meshes, but not the buffer ids
+    let mut shadow_r = shadow_map.rasterizer();
+    let shadow_spot = (
+        shadow_r.load_vertices(spot.vertices.clone()),
+        shadow_r.load_indices(spot.indices.clone()),
+    );
+    let shadow_floor = (
+        shadow_r.load_vertices(floor.vertices.clone()),
+        shadow_r.load_indices(floor.indices.clone()),
+    );
+
+    let mut r = rst::Rasterizer::new(frame_width, frame_width);
+    let spot_ids = (r.load_vertices(spot.vertices), r.load_indices(spot.indices));
+    let floor_ids = (
+        r.load_vertices(floor.vertices),
+        r.load_indices(floor.indices),
+    );
+    r.set_vertex_shader(homework3::vertex_shader);
+    r.set_fragment_shader(phong_fragment_shader);
+
+    let eye_pos = Vec3::new(0.0, 3.0, 12.0);
+    let view = get_view_matrix(eye_pos);
+    r.set_view(view);
+    // the only light is the one casting the shadow, in the view space as the shaders take it
+    r.set_lights(vec![Light {
+        position: view.transform_point3(light.position),
+        ..light
+    }]);
+    // the camera pass keeps the shadow map, the shadow pass captures into it every frame
+    r.set_shadow_maps(vec![shadow_map]);
+
+    utils::graphic::start_loop(frame_width, frame_width, move |actions, display_image| {
+        for action in actions {
+            let shadow_map = &mut r.shadow_maps_mut()[0];
+            match action {
+                Action::Stop => {
+                    save_image(&r, filename)?;
+                    return Ok(Control::Stop);
+                }
+                Action::Key(Key::A) => angle -= 10.0,
+                Action::Key(Key::D) => angle += 10.0,
+                Action::Key(Key::W) => shadow_map.bias *= 2.0,
+                Action::Key(Key::S) => shadow_map.bias /= 2.0,
+                Action::Key(Key::E) => shadow_map.pcf_radius += 1,
+                Action::Key(Key::Q) => {
+                    shadow_map.pcf_radius = shadow_map.pcf_radius.saturating_sub(1)
+                }
+                _ => continue,
+            }
+            dbg!(shadow_map.bias, shadow_map.pcf_radius);
+        }
+        let spot_model = get_model_matrix(angle, scale);
+
+        // shadow pass, from the light
+        shadow_r.clear(rst::Buffers::all());
+        shadow_r.set_model(spot_model);
+        shadow_r.draw(shadow_spot.0, shadow_spot.1, rst::Primitive::Triangle);
+        shadow_r.set_model(floor_model);
+        shadow_r.draw(shadow_floor.0, shadow_floor.1, rst::Primitive::Triangle);
+        r.shadow_maps_mut()[0].capture(&shadow_r);
+
+        // camera pass
+        r.clear(rst::Buffers::all());
+        r.set_view(view);
+        r.set_projection(get_projection_matrix(45., 1., 0.1, 50.));
+
+        r.set_model(spot_model);
+        r.draw(spot_ids.0, spot_ids.1, rst::Primitive::Triangle);
+        r.set_model(floor_model);
+        r.draw(floor_ids.0, floor_ids.1, rst::Primitive::Triangle);
+
+        display_image(&r)?;
+        Ok(Control::Continue)
+    });
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use anyhow::{anyhow, Result};
+use glam::{Vec2, Vec3};
+
+use crate::mesh::{Mesh, Vertex};
+
+/**
+ * a bicubic bezier surface patch,
+ * control_points[i][j] is the i-th along u and the j-th along v.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BezierPatch {
+    pub control_points: [[Vec3; 4]; 4],
+}
+
+// de Casteljau's algorithm on a cubic curve
+fn de_casteljau(points: [Vec3; 4], t: f32) -> Vec3 {
+    let mut points = points;
+    for n in (1..4).rev() {
+        for i in 0..n {
+            points[i] = points[i].lerp(points[i + 1], t);
+        }
+    }
+    points[0]
+}
+
+// derivative of a cubic curve, from its hodograph
+fn derivative(points: [Vec3; 4], t: f32) -> Vec3 {
+    let [a, b, c, d] = points;
+    let hodograph = [3.0 * (b - a), 3.0 * (c - b), 3.0 * (d - c)];
+    let [a, b, c] = hodograph;
+    let (ab, bc) = (a.lerp(b, t), b.lerp(c, t));
+    ab.lerp(bc, t)
+}
+
+impl BezierPatch {
+    pub fn new(control_points: [[Vec3; 4]; 4]) -> Self {
+        Self { control_points }
+    }
+
+    // the control points of the cubic curve along u at v
+    fn curve_along_u(&self, v: f32) -> [Vec3; 4] {
+        self.control_points.map(|row| de_casteljau(row, v))
+    }
+
+    // the control points of the cubic curve along v at u
+    fn curve_along_v(&self, u: f32) -> [Vec3; 4] {
+        let p = &self.control_points;
+        [0, 1, 2, 3].map(|j| de_casteljau([p[0][j], p[1][j], p[2][j], p[3][j]], u))
+    }
+
+    /**
+     * tensor product de Casteljau:
+     * every row is reduced to a point at v, then those 4 points at u.
+     */
+    pub fn evaluate(&self, u: f32, v: f32) -> Vec3 {
+        de_casteljau(self.curve_along_u(v), u)
+    }
+
+    // partial derivatives on u and v
+    pub fn derivatives(&self, u: f32, v: f32) -> (Vec3, Vec3) {
+        (
+            derivative(self.curve_along_u(v), u),
+            derivative(self.curve_along_v(u), v),
+        )
+    }
+
+    /**
+     * the normal of du x dv.
+     * at a degenerated edge (the pole of a lid) one derivative is zero,
+     * so the normal is taken a little towards the middle of the patch.
+     */
+    pub fn normal(&self, u: f32, v: f32) -> Vec3 {
+        let (du, dv) = self.derivatives(u, v);
+        let n = du.cross(dv);
+        if n.length_squared() > 1e-12 {
+            return n.normalize();
+        }
+        let (u, v) = (u + (0.5 - u) * 1e-3, v + (0.5 - v) * 1e-3);
+        let (du, dv) = self.derivatives(u, v);
+        du.cross(dv).normalize_or_zero()
+    }
+
+    /**
+     * a grid of (divisions + 1)^2 vertices with uv = (u, v),
+     * two counter clockwise triangles (around du x dv) per cell.
+     */
+    pub fn tessellate(&self, divisions: u32) -> Mesh {
+        let n = divisions.max(1);
+        let mut vertices = Vec::with_capacity(((n + 1) * (n + 1)) as usize);
+        for i in 0..=n {
+            let u = i as f32 / n as f32;
+            for j in 0..=n {
+                let v = j as f32 / n as f32;
+                vertices.push(Vertex {
+                    position: self.evaluate(u, v),
+                    normal: self.normal(u, v),
+                    tex_coords: Vec2::new(u, v),
+                });
+            }
+        }
+
+        let index = |i: u32, j: u32| (i * (n + 1) + j) as usize;
+        let mut indices = Vec::with_capacity((2 * n * n) as usize);
+        for i in 0..n {
+            for j in 0..n {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                );
+                // skip the triangles collapsed at a degenerated edge
+                for [a, b, c] in [[a, b, c], [a, c, d]] {
+                    let (pa, pb, pc) = (
+                        vertices[a].position,
+                        vertices[b].position,
+                        vertices[c].position,
+                    );
+                    if (pb - pa).cross(pc - pa).length_squared() > 1e-12 {
+                        indices.push([a, b, c]);
+                    }
+                }
+            }
+        }
+        Mesh { vertices, indices }
+    }
+}
+
+/**
+ * all the patches tessellated into one mesh
+ */
+pub fn tessellate(patches: &[BezierPatch], divisions: u32) -> Mesh {
+    let mut mesh = Mesh::default();
+    for patch in patches {
+        let part = patch.tessellate(divisions);
+        let offset = mesh.vertices.len();
+        mesh.vertices.extend(part.vertices);
+        mesh.indices.extend(
+            part.indices
+                .iter()
+                .map(|[a, b, c]| [a + offset, b + offset, c + offset]),
+        );
+    }
+    mesh
+}
+
+/**
+ * load patches from a .bpt file:
+ * the number of patches, then for every patch its degrees on u and v ("3 3"),
+ * followed by its 16 control points, one `x y z` per line, j changing fastest.
+ */
+pub fn load_bpt(path: &str) -> Result<Vec<BezierPatch>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut values = content.split_whitespace();
+    let mut next = || -> Result<f32> {
+        let value = values
+            .next()
+            .ok_or_else(|| anyhow!("unexpected end of {}", path))?;
+        Ok(value.parse::<f32>()?)
+    };
+
+    let count = next()? as usize;
+    let mut patches = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (degree_u, degree_v) = (next()?, next()?);
+        if degree_u != 3.0 || degree_v != 3.0 {
+            return Err(anyhow!(
+                "only bicubic patches are supported, got degree {} x {}",
+                degree_u,
+                degree_v
+            ));
+        }
+        let mut control_points = [[Vec3::ZERO; 4]; 4];
+        for row in control_points.iter_mut() {
+            for point in row.iter_mut() {
+                *point = Vec3::new(next()?, next()?, next()?);
+            }
+        }
+        patches.push(BezierPatch::new(control_points));
+    }
+    Ok(patches)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::*;
+
+    // a quarter of a cylinder of radius 1 around z, from z = 0 to 1
+    fn quarter_cylinder() -> BezierPatch {
+        let k = 0.552285;
+        let arc = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, k, 0.0),
+            Vec3::new(k, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let mut control_points = [[Vec3::ZERO; 4]; 4];
+        for (i, row) in control_points.iter_mut().enumerate() {
+            for (j, point) in row.iter_mut().enumerate() {
+                // u goes down, v goes around
+                *point = arc[j] + Vec3::Z * (1.0 - i as f32 / 3.0);
+            }
+        }
+        BezierPatch::new(control_points)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let patch = quarter_cylinder();
+        // corners are control points
+        assert_eq!(patch.evaluate(0.0, 0.0), patch.control_points[0][0]);
+        assert_eq!(patch.evaluate(1.0, 1.0), patch.control_points[3][3]);
+
+        for (u, v) in [(0.2, 0.5), (0.5, 0.0), (0.9, 0.7)] {
+            let p = patch.evaluate(u, v);
+            assert!((p.z - (1.0 - u)).abs() < 1e-5);
+            assert!((p.truncate().length() - 1.0).abs() < 1e-3);
+
+            // the normal points away from the axis, since du goes down and dv goes around
+            let n = patch.normal(u, v);
+            assert!(n.abs_diff_eq(p.truncate().normalize().extend(0.0), 1e-3));
+
+            let h = 1e-3;
+            let (du, dv) = patch.derivatives(u, v);
+            let du_expected = (patch.evaluate(u + h, v) - patch.evaluate(u - h, v)) / (2.0 * h);
+            let dv_expected = (patch.evaluate(u, v + h) - patch.evaluate(u, v - h)) / (2.0 * h);
+            assert!(du.abs_diff_eq(du_expected, 1e-2));
+            assert!(dv.abs_diff_eq(dv_expected, 1e-2));
+        }
+    }
+
+    #[test]
+    fn test_tessellate() {
+        let patch = quarter_cylinder();
+        let mesh = patch.tessellate(4);
+        assert_eq!(mesh.vertices.len(), 25);
+        assert_eq!(mesh.indices.len(), 32);
+        assert_eq!(mesh.vertices[24].tex_coords, glam::Vec2::ONE);
+        // every triangle faces the way of its vertex normals
+        for [a, b, c] in mesh.indices.iter().copied() {
+            let (pa, pb, pc) = (
+                mesh.vertices[a].position,
+                mesh.vertices[b].position,
+                mesh.vertices[c].position,
+            );
+            assert!((pb - pa).cross(pc - pa).dot(mesh.vertices[a].normal) > 0.0);
+        }
+
+        // a lid like patch with a whole row at one point loses the collapsed triangles
+        let mut pole = patch;
+        pole.control_points[0] = [Vec3::Z; 4];
+        let mesh = pole.tessellate(4);
+        assert_eq!(mesh.indices.len(), 32 - 4);
+        assert!(mesh.vertices[0].normal.length() > 0.99);
+
+        let both = tessellate(&[patch, pole], 4);
+        assert_eq!(both.vertices.len(), 50);
+        assert_eq!(both.indices.len(), 32 + 28);
+        assert!(both.indices.iter().flatten().all(|i| *i < 50));
+    }
+
+    #[test]
+    fn test_load_bpt() {
+        let patches = load_bpt("models/teapot/teapot.bpt").unwrap();
+        assert_eq!(patches.len(), 32);
+        // the teapot stands on z = 0, and is no taller than its lid
+        let mesh = tessellate(&patches, 4);
+        let (min_z, max_z) = mesh
+            .vertices
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v.position.z), max.max(v.position.z))
+            });
+        assert!(min_z.abs() < 1e-4);
+        assert!((max_z - 3.15).abs() < 1e-4);
+
+        let path = std::env::temp_dir().join("homework3_bad.bpt");
+        std::fs::write(&path, "1\n2 3\n").unwrap();
+        assert!(load_bpt(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
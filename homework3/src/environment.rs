@@ -0,0 +1,526 @@
+use std::f32::consts::PI;
+
+use anyhow::Result;
+use glam::{Mat3, Mat4, Vec2, Vec3};
+
+// the size of the finest level of the specular mips, the source is downsampled to it
+const SPECULAR_WIDTH: u32 = 256;
+// the specular mips, from roughness 0 to roughness 1
+const SPECULAR_LEVELS: usize = 5;
+// ggx samples for every texel of the prefiltered mips
+const PREFILTER_SAMPLES: u32 = 64;
+
+/**
+ * radiance from every direction, stored as an equirectangular image:
+ * u goes around y from -z, v goes from +y at the top to -y at the bottom.
+ */
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    pub width: u32,
+    pub height: u32,
+    // linear rgb, top row first
+    pub data: Vec<Vec3>,
+}
+
+impl EnvironmentMap {
+    /**
+     * the radiance of every direction is f(direction), in world space
+     */
+    pub fn from_fn(width: u32, height: u32, f: impl Fn(Vec3) -> Vec3) -> Self {
+        let data = (0..width * height)
+            .map(|i| {
+                let uv = Vec2::new(
+                    ((i % width) as f32 + 0.5) / width as f32,
+                    ((i / width) as f32 + 0.5) / height as f32,
+                );
+                f(uv_to_direction(uv))
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /**
+     * an equirectangular image, hdr (radiance .hdr) or ldr (png, decoded from srgb).
+     * the format is told by the extension.
+     */
+    pub fn load_equirect(path: &str) -> Result<Self> {
+        let img = load_linear(path)?;
+        let (width, height) = img.dimensions();
+        let data = img.pixels().map(|p| Vec3::from_array(p.0)).collect();
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /**
+     * a cube map of 6 square faces, in the order of +x, -x, +y, -y, +z, -z.
+     * faces are seen from the inside of the cube, as opengl lays them out.
+     * it's resampled to an equirectangular map of `width` x `width / 2`.
+     * the faces are hdr or ldr as `load_equirect`.
+     */
+    pub fn load_cube_map(paths: [&str; 6], width: u32) -> Result<Self> {
+        let faces = paths
+            .iter()
+            .map(|path| load_linear(path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::from_fn(width, width / 2, |d| {
+            let (face, u, v) = cube_face(d);
+            let img = &faces[face];
+            let x = ((u * img.width() as f32) as u32).min(img.width() - 1);
+            let y = ((v * img.height() as f32) as u32).min(img.height() - 1);
+            Vec3::from_array(img.get_pixel(x, y).0)
+        }))
+    }
+
+    /**
+     * bilinear lookup of a world space direction
+     */
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let uv = direction_to_uv(direction.normalize_or_zero());
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = (uv.y * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        // u wraps around, v stops at the poles
+        let texel = |x: i32, y: i32| {
+            let x = x.rem_euclid(self.width as i32) as u32;
+            let y = (y.max(0) as u32).min(self.height - 1);
+            self.data[(y * self.width + x) as usize]
+        };
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = texel(x0, y0).lerp(texel(x0 + 1, y0), tx);
+        let bottom = texel(x0, y0 + 1).lerp(texel(x0 + 1, y0 + 1), tx);
+        top.lerp(bottom, ty)
+    }
+
+    /**
+     * a box filtered copy of half the size
+     */
+    fn downsample(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let data = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) * 2, (i / width) * 2);
+                let texel = |dx: u32, dy: u32| {
+                    let (x, y) = ((x + dx).min(self.width - 1), (y + dy).min(self.height - 1));
+                    self.data[(y * self.width + x) as usize]
+                };
+                (texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1)) / 4.0
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /**
+     * the solid angle covered by a texel in row y
+     */
+    fn texel_solid_angle(&self, y: u32) -> f32 {
+        let theta = (y as f32 + 0.5) / self.height as f32 * PI;
+        (2.0 * PI / self.width as f32) * (PI / self.height as f32) * theta.sin()
+    }
+}
+
+/**
+ * an image in linear rgb: hdr is linear already,
+ * and the others (png) are stored in srgb as images usually are.
+ */
+fn load_linear(path: &str) -> Result<image::Rgb32FImage> {
+    let reader = image::io::Reader::open(path)?;
+    // decoding hdr as a DynamicImage tone maps it to 8 bits, its own decoder keeps the floats
+    if reader.format() == Some(image::ImageFormat::Hdr) {
+        let decoder = image::codecs::hdr::HdrDecoder::new(reader.into_inner())?;
+        let (width, height) = (decoder.metadata().width, decoder.metadata().height);
+        let data = decoder.read_image_hdr()?.iter().flat_map(|p| p.0).collect();
+        return image::Rgb32FImage::from_raw(width, height, data)
+            .ok_or_else(|| anyhow::anyhow!("{}: truncated hdr image", path));
+    }
+
+    let mut img = reader.decode()?.to_rgb32f();
+    for c in img.iter_mut() {
+        *c = crate::shader::srgb_to_linear(*c);
+    }
+    Ok(img)
+}
+
+/**
+ * an environment map prepared for image based lighting:
+ * - the diffuse irradiance, projected onto 9 spherical harmonics
+ * - the specular radiance, prefiltered by the ggx lobe of a few roughness levels
+ *
+ * shaders look it up with view space directions,
+ * it's rotated to the world by the view matrix set by `set_camera_view`.
+ */
+#[derive(Clone)]
+pub struct Environment {
+    // scales the radiance of the whole map
+    pub intensity: f32,
+    // spherical harmonics of the radiance, bands 0..=2
+    sh: [Vec3; 9],
+    // from roughness 0 to roughness 1, each half the size of the previous one
+    specular: Vec<EnvironmentMap>,
+    view_to_world: Mat3,
+}
+
+impl Environment {
+    pub fn new(map: &EnvironmentMap) -> Self {
+        let mut sh = [Vec3::ZERO; 9];
+        for y in 0..map.height {
+            let solid_angle = map.texel_solid_angle(y);
+            for x in 0..map.width {
+                let uv = Vec2::new(
+                    (x as f32 + 0.5) / map.width as f32,
+                    (y as f32 + 0.5) / map.height as f32,
+                );
+                let basis = sh_basis(uv_to_direction(uv));
+                let radiance = map.data[(y * map.width + x) as usize];
+                for (c, b) in sh.iter_mut().zip(basis.iter()) {
+                    *c += radiance * *b * solid_angle;
+                }
+            }
+        }
+
+        let mut base = map.clone();
+        while base.width > SPECULAR_WIDTH {
+            base = base.downsample();
+        }
+        let mut specular = vec![base];
+        for level in 1..SPECULAR_LEVELS {
+            let roughness = level as f32 / (SPECULAR_LEVELS - 1) as f32;
+            // every level is filtered from the previous one, blurred enough not to alias
+            let source = &specular[level - 1];
+            let (width, height) = ((source.width / 2).max(8), (source.height / 2).max(4));
+            let filtered =
+                EnvironmentMap::from_fn(width, height, |r| prefilter(source, r, roughness));
+            specular.push(filtered);
+        }
+
+        Self {
+            intensity: 1.0,
+            sh,
+            specular,
+            view_to_world: Mat3::IDENTITY,
+        }
+    }
+
+    /**
+     * a procedural sky: a blue gradient above the horizon, a warm sun and a dark ground
+     */
+    pub fn sky() -> Self {
+        let sun = Vec3::new(-0.5, 0.6, 0.4).normalize();
+        Self::new(&EnvironmentMap::from_fn(256, 128, |d| {
+            let sky = match d.y >= 0.0 {
+                true => Vec3::new(0.6, 0.75, 1.0).lerp(Vec3::new(0.2, 0.4, 0.9), d.y),
+                false => Vec3::new(0.25, 0.2, 0.15),
+            };
+            let sun_disk = match d.dot(sun) > 0.995 {
+                true => Vec3::new(20.0, 18.0, 14.0),
+                false => Vec3::ZERO,
+            };
+            sky + sun_disk
+        }))
+    }
+
+    /**
+     * the view matrix of the camera,
+     * directions passed to the lookups are in its view space.
+     */
+    pub fn set_camera_view(&mut self, view: Mat4) {
+        self.view_to_world = Mat3::from_mat4(view).transpose();
+    }
+
+    /**
+     * the irradiance arriving at a surface facing the normal (in view space),
+     * a lambertian surface of albedo 1 reflects irradiance / pi.
+     */
+    pub fn irradiance(&self, normal: Vec3) -> Vec3 {
+        // the cosine lobe convolved into every band
+        const BAND: [f32; 9] = [
+            PI,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+        ];
+        let basis = sh_basis((self.view_to_world * normal).normalize_or_zero());
+        let mut res = Vec3::ZERO;
+        for i in 0..9 {
+            res += self.sh[i] * BAND[i] * basis[i];
+        }
+        res.max(Vec3::ZERO) * self.intensity
+    }
+
+    /**
+     * the radiance reflected about the reflection direction (in view space),
+     * prefiltered by the ggx lobe of the roughness.
+     */
+    pub fn specular(&self, reflection: Vec3, roughness: f32) -> Vec3 {
+        let direction = self.view_to_world * reflection;
+        let level = roughness.clamp(0.0, 1.0) * (self.specular.len() - 1) as f32;
+        let (lo, t) = (level.floor() as usize, level.fract());
+        let hi = (lo + 1).min(self.specular.len() - 1);
+        let res = self.specular[lo]
+            .sample(direction)
+            .lerp(self.specular[hi].sample(direction), t);
+        res * self.intensity
+    }
+}
+
+/**
+ * the split sum approximation of the specular brdf integrated over the hemisphere,
+ * reflectance = f0 * scale + bias. (karis' analytic fit of the lut)
+ */
+pub fn env_brdf(n_dot_v: f32, roughness: f32) -> (f32, f32) {
+    let c0 = [-1.0, -0.0275, -0.572, 0.022];
+    let c1 = [1.0, 0.0425, 1.04, -0.04];
+    let r: Vec<f32> = (0..4).map(|i| roughness * c0[i] + c1[i]).collect();
+    let a004 = (r[0] * r[0]).min((-9.28 * n_dot_v).exp2()) * r[0] + r[1];
+    (-1.04 * a004 + r[2], 1.04 * a004 + r[3])
+}
+
+/**
+ * ggx importance sampling around the reflection direction,
+ * taking the normal and the eye direction both to be r.
+ */
+fn prefilter(source: &EnvironmentMap, r: Vec3, roughness: f32) -> Vec3 {
+    let a = roughness * roughness;
+    let tangent = r.any_orthonormal_vector();
+    let tbn = Mat3::from_cols(tangent, r.cross(tangent), r);
+
+    let (mut sum, mut weight) = (Vec3::ZERO, 0.0);
+    for i in 0..PREFILTER_SAMPLES {
+        // hammersley point set
+        let xi = Vec2::new(
+            i as f32 / PREFILTER_SAMPLES as f32,
+            i.reverse_bits() as f32 / (1u64 << 32) as f32,
+        );
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = ((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let h = tbn * Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let l = 2.0 * r.dot(h) * h - r;
+        let n_dot_l = r.dot(l);
+        if n_dot_l > 0.0 {
+            sum += source.sample(l) * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    match weight > 0.0 {
+        true => sum / weight,
+        false => source.sample(r),
+    }
+}
+
+/**
+ * the real spherical harmonics of bands 0..=2 at a unit direction
+ */
+fn sh_basis(d: Vec3) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3.0 * d.z * d.z - 1.0),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+fn uv_to_direction(uv: Vec2) -> Vec3 {
+    let phi = (uv.x - 0.5) * 2.0 * PI;
+    let theta = uv.y * PI;
+    Vec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+fn direction_to_uv(d: Vec3) -> Vec2 {
+    Vec2::new(
+        d.x.atan2(-d.z) / (2.0 * PI) + 0.5,
+        d.y.clamp(-1.0, 1.0).acos() / PI,
+    )
+}
+
+/**
+ * the face of a cube map a direction hits, and the uv on it from the top left
+ */
+fn cube_face(d: Vec3) -> (usize, f32, f32) {
+    let a = d.abs();
+    let (face, sc, tc, ma) = if a.x >= a.y && a.x >= a.z {
+        match d.x > 0.0 {
+            true => (0, -d.z, -d.y, a.x),
+            false => (1, d.z, -d.y, a.x),
+        }
+    } else if a.y >= a.z {
+        match d.y > 0.0 {
+            true => (2, d.x, d.z, a.y),
+            false => (3, d.x, -d.z, a.y),
+        }
+    } else {
+        match d.z > 0.0 {
+            true => (4, d.x, -d.y, a.z),
+            false => (5, -d.x, -d.y, a.z),
+        }
+    };
+    (face, 0.5 * (sc / ma + 1.0), 0.5 * (tc / ma + 1.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uv_direction() {
+        for d in [
+            Vec3::new(0.3, 0.5, -0.8),
+            Vec3::new(-0.9, -0.1, 0.2),
+            Vec3::new(0.0, 0.2, 1.0),
+        ] {
+            let d = d.normalize();
+            assert!(uv_to_direction(direction_to_uv(d)).abs_diff_eq(d, 1e-5));
+        }
+        assert!(direction_to_uv(Vec3::Y).y.abs() < 1e-6);
+        assert!(direction_to_uv(-Vec3::Z).abs_diff_eq(Vec2::new(0.5, 0.5), 1e-6));
+    }
+
+    #[test]
+    fn test_load_equirect() {
+        let dir = std::env::temp_dir();
+        // a png is decoded from srgb, 128 is about 0.216 in linear
+        let png = dir.join("homework3_equirect.png");
+        image::RgbImage::from_fn(4, 2, |x, _| image::Rgb([x as u8 * 64, 128, 255]))
+            .save(&png)
+            .unwrap();
+        let map = EnvironmentMap::load_equirect(png.to_str().unwrap()).unwrap();
+        assert_eq!((map.width, map.height), (4, 2));
+        for (i, texel) in map.data.iter().enumerate() {
+            let srgb = Vec3::new((i % 4) as f32 * 64.0, 128.0, 255.0) / 255.0;
+            let linear = Vec3::from(srgb.to_array().map(crate::shader::srgb_to_linear));
+            assert!(texel.abs_diff_eq(linear, 1e-6), "{} {}", i, texel);
+        }
+        assert!((map.data[0].y - 0.216).abs() < 1e-3);
+
+        // an hdr is linear already, and may go over 1
+        let hdr = dir.join("homework3_equirect.hdr");
+        let pixels = [0.25, 2.0, 0.5, 8.0].map(|v| image::Rgb([v, v / 2.0, v]));
+        let file = std::io::BufWriter::new(std::fs::File::create(&hdr).unwrap());
+        image::codecs::hdr::HdrEncoder::new(file)
+            .encode(&pixels, 2, 2)
+            .unwrap();
+        let map = EnvironmentMap::load_equirect(hdr.to_str().unwrap()).unwrap();
+        for (texel, pixel) in map.data.iter().zip(pixels) {
+            assert!(
+                texel.abs_diff_eq(Vec3::from_array(pixel.0), 1e-6),
+                "{}",
+                texel
+            );
+        }
+
+        std::fs::remove_file(png).unwrap();
+        std::fs::remove_file(hdr).unwrap();
+    }
+
+    #[test]
+    fn test_load_cube_map() {
+        // a face of its own gray for every direction, decoded from srgb
+        let grays = [32u8, 64, 96, 128, 160, 192];
+        let paths: Vec<_> = grays
+            .iter()
+            .map(|gray| {
+                let path = std::env::temp_dir().join(format!("homework3_cube_{}.png", gray));
+                image::RgbImage::from_pixel(2, 2, image::Rgb([*gray; 3]))
+                    .save(&path)
+                    .unwrap();
+                path
+            })
+            .collect();
+        let names: Vec<&str> = paths.iter().map(|p| p.to_str().unwrap()).collect();
+        let map = EnvironmentMap::load_cube_map(names.try_into().unwrap(), 64).unwrap();
+
+        let directions = [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z];
+        for (direction, gray) in directions.iter().zip(grays) {
+            let linear = crate::shader::srgb_to_linear(gray as f32 / 255.0);
+            let texel = map.data[(direction_to_texel(&map, *direction)) as usize];
+            assert!(
+                texel.abs_diff_eq(Vec3::splat(linear), 1e-6),
+                "{} {}",
+                direction,
+                texel
+            );
+        }
+
+        for path in paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    // the index of the texel a direction falls in
+    fn direction_to_texel(map: &EnvironmentMap, direction: Vec3) -> u32 {
+        let uv = direction_to_uv(direction);
+        let x = ((uv.x * map.width as f32) as u32).min(map.width - 1);
+        let y = ((uv.y * map.height as f32) as u32).min(map.height - 1);
+        y * map.width + x
+    }
+
+    #[test]
+    fn test_constant_environment() {
+        // a uniform radiance l gives an irradiance of pi * l from every side,
+        // and the same radiance reflected at any roughness
+        let radiance = Vec3::new(0.5, 1.0, 2.0);
+        let env = Environment::new(&EnvironmentMap::from_fn(64, 32, |_| radiance));
+        for n in [Vec3::X, -Vec3::Y, Vec3::new(0.3, 0.4, -0.5).normalize()] {
+            assert!(env.irradiance(n).abs_diff_eq(radiance * PI, 0.02));
+            for roughness in [0.0, 0.3, 1.0] {
+                assert!(env.specular(n, roughness).abs_diff_eq(radiance, 1e-3));
+            }
+        }
+    }
+
+    #[test]
+    fn test_directional_environment() {
+        // bright above, dark below
+        let mut env = Environment::new(&EnvironmentMap::from_fn(64, 32, |d| {
+            Vec3::ONE * d.y.max(0.0)
+        }));
+        // the irradiance of a cosine sky is 2 pi / 3 facing up, 0 facing down
+        assert!((env.irradiance(Vec3::Y).x - 2.0 * PI / 3.0).abs() < 0.1);
+        assert!(env.irradiance(-Vec3::Y).x < 0.1);
+        assert!(env.irradiance(Vec3::X).x > env.irradiance(-Vec3::Y).x);
+
+        // a rough reflection blurs the sky toward the horizon
+        let sharp = env.specular(Vec3::Y, 0.0).x;
+        let blurred = env.specular(Vec3::Y, 1.0).x;
+        assert!(sharp > 0.95 && blurred < sharp);
+
+        // lookups are in view space, a camera looking down sees the sky behind it
+        env.set_camera_view(Mat4::look_at_rh(Vec3::Y * 5.0, Vec3::ZERO, -Vec3::Z));
+        assert!(env.irradiance(Vec3::Z).x > 1.9);
+    }
+
+    #[test]
+    fn test_env_brdf() {
+        // smooth surfaces seen head on reflect f0
+        let (scale, bias) = env_brdf(1.0, 0.0);
+        assert!((scale - 1.0).abs() < 0.05 && bias.abs() < 0.05);
+        // grazing angles reflect more, whatever f0 is
+        let (_, bias) = env_brdf(0.05, 0.0);
+        assert!(bias > 0.3);
+    }
+}
@@ -0,0 +1,149 @@
+use glam::{Vec2, Vec3};
+use utils::rasterizer::BufRasterizer;
+
+/**
+ * the attributes of the visible fragment at a pixel,
+ * written by the rasterizer when a fragment passes the depth test.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GBufferSample {
+    pub view_pos: Vec3,
+    pub normal: Vec3,
+    pub tex_coords: Vec2,
+    pub color: Vec3,
+    // 0 for the fragment shader and texture set on the rasterizer,
+    // otherwise the id returned by `load_material`
+    pub material: u32,
+    // how much the pixel is covered by a triangle edge, for the edge render modes
+    pub edge: f32,
+    // the weights of the 3 vertices used for interpolating this fragment
+    pub barycentric: Vec3,
+}
+
+/**
+ * which attribute of the G-buffer to visualize
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    Depth,
+    Normal,
+    TexCoords,
+    Barycentric,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 4] = [
+        DebugView::Depth,
+        DebugView::Normal,
+        DebugView::TexCoords,
+        DebugView::Barycentric,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Depth => "depth",
+            DebugView::Normal => "normal",
+            DebugView::TexCoords => "uv",
+            DebugView::Barycentric => "barycentric",
+        }
+    }
+}
+
+/**
+ * per pixel attributes of the visible surfaces,
+ * laid out the same as the frame buffer (top row first).
+ * pixels not covered by any triangle are None.
+ */
+pub struct GBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub samples: Vec<Option<GBufferSample>>,
+}
+
+impl GBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            samples: vec![None; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.fill(None);
+    }
+
+    /**
+     * render one attribute into an image:
+     * - depth: linear view space depth (-z), normalized, the nearer the brighter
+     * - normal: (n + 1) / 2
+     * - uv: (u, v, 0)
+     * - barycentric: (alpha, beta, gamma)
+     */
+    pub fn view(&self, view: DebugView) -> BufRasterizer {
+        if view == DebugView::Depth {
+            let depth: Vec<f32> = self
+                .samples
+                .iter()
+                .map(|s| match s {
+                    Some(s) => -s.view_pos.z,
+                    None => f32::INFINITY,
+                })
+                .collect();
+            return BufRasterizer::from_depth(&depth, self.width, self.height);
+        }
+
+        let vec3s = self
+            .samples
+            .iter()
+            .map(|s| match s {
+                None => Vec3::ZERO,
+                Some(s) => match view {
+                    DebugView::Normal => (s.normal.normalize_or_zero() + Vec3::ONE) / 2.0,
+                    DebugView::TexCoords => s.tex_coords.extend(0.0),
+                    DebugView::Barycentric => s.barycentric,
+                    DebugView::Depth => unreachable!(),
+                },
+            })
+            .map(|c| c.clamp(Vec3::ZERO, Vec3::ONE))
+            .collect();
+        BufRasterizer::from_vec3s(vec3s, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use utils::rasterizer::Rasterizable;
+    use utils::rgb::Rgb;
+
+    #[test]
+    fn test_view() {
+        let mut g_buf = GBuffer::new(2, 1);
+        g_buf.samples[0] = Some(GBufferSample {
+            view_pos: Vec3::new(0.0, 0.0, -2.0),
+            normal: Vec3::Z,
+            tex_coords: Vec2::new(1.0, 0.0),
+            color: Vec3::ONE,
+            material: 0,
+            edge: 0.0,
+            barycentric: Vec3::new(0.0, 1.0, 0.0),
+        });
+
+        let depth = g_buf.view(DebugView::Depth);
+        assert_eq!(depth.data(), &vec![Rgb(255, 255, 255), Rgb(0, 0, 0)]);
+
+        let normal = g_buf.view(DebugView::Normal);
+        assert_eq!(normal.data()[0], Rgb(127, 127, 255));
+
+        let uv = g_buf.view(DebugView::TexCoords);
+        assert_eq!(uv.data()[0], Rgb(255, 0, 0));
+
+        let barycentric = g_buf.view(DebugView::Barycentric);
+        assert_eq!(barycentric.data()[0], Rgb(0, 255, 0));
+        assert_eq!(barycentric.data()[1], Rgb(0, 0, 0));
+
+        g_buf.clear();
+        assert!(g_buf.samples.iter().all(|s| s.is_none()));
+    }
+}
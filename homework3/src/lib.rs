@@ -1,5 +1,11 @@
+pub mod bezier_patch;
+pub mod environment;
+pub mod gbuffer;
+pub mod mesh;
 pub mod rst;
 pub mod shader;
+pub mod shadow;
+pub mod ssao;
 
 use std::f32::consts::PI;
 
@@ -141,9 +147,39 @@ fn reflect(vec: Vec3, axis: Vec3) -> Vec3 {
     return (2. * costheta * axis - vec).normalize();
 }
 
-struct Light {
-    position: Vec3,
-    intensity: Vec3,
+/**
+ * the ambient light of the blinn-phong shaders: a constant 10,
+ * or the irradiance of the environment scaled so that a uniform radiance of 1 gives the same.
+ */
+fn ambient_light_intensity(payload: &shader::FragmentShaderPayload) -> Vec3 {
+    let intensity = match payload.environment {
+        Some(environment) => environment.irradiance(payload.normal.normalize()) / PI * 10.,
+        None => Vec3::ONE * 10.,
+    };
+    intensity * payload.ambient_occlusion
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub position: Vec3,
+    pub intensity: Vec3,
+}
+
+/**
+ * the 2 lights the blinn-phong shaders are lit by, unless the rasterizer is given others.
+ * the positions are in the same space as the view positions of the fragments.
+ */
+pub fn default_lights() -> Vec<Light> {
+    vec![
+        Light {
+            position: Vec3::ONE * 20.0,
+            intensity: Vec3::ONE * 500.,
+        },
+        Light {
+            position: Vec3::new(-20., 20., 0.),
+            intensity: Vec3::ONE * 500.,
+        },
+    ]
 }
 
 pub fn texture_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
@@ -161,17 +197,7 @@ pub fn texture_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3
     let kd = texture_color / 255.0;
     let ks = Vec3::new(0.7937, 0.7937, 0.7937);
 
-    let l1 = Light {
-        position: Vec3::ONE * 20.0,
-        intensity: Vec3::ONE * 500.,
-    };
-    let l2 = Light {
-        position: Vec3::new(-20., 20., 0.),
-        intensity: Vec3::ONE * 500.,
-    };
-
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vec3::ONE * 10.;
+    let amb_light_intensity = ambient_light_intensity(payload);
     let eye_pos = Vec3::new(0.0, 0.0, 10.0);
 
     let p = 150.;
@@ -181,7 +207,7 @@ pub fn texture_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3
     let normal = payload.normal.normalize();
     let mut result_color = Vec3::ZERO;
 
-    for light in lights {
+    for (i, light) in payload.lights.iter().enumerate() {
         // TODO: For each light source in the code, calculate what the *ambient*, *diffuse*, and *specular*
         // components are. Then, accumulate that result on the *result_color* object.
 
@@ -205,7 +231,7 @@ pub fn texture_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3
                 .max(0.0)
                 .powf(p);
 
-        result_color += la + ld + ls;
+        result_color += la + (ld + ls) * payload.light_visibility(i, point);
     }
     result_color * 255.0
 }
@@ -226,17 +252,7 @@ pub fn texture_bilinear_fragment_shader(payload: &shader::FragmentShaderPayload)
     let kd = texture_color / 255.0;
     let ks = Vec3::new(0.7937, 0.7937, 0.7937);
 
-    let l1 = Light {
-        position: Vec3::ONE * 20.0,
-        intensity: Vec3::ONE * 500.,
-    };
-    let l2 = Light {
-        position: Vec3::new(-20., 20., 0.),
-        intensity: Vec3::ONE * 500.,
-    };
-
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vec3::ONE * 10.;
+    let amb_light_intensity = ambient_light_intensity(payload);
     let eye_pos = Vec3::new(0.0, 0.0, 10.0);
 
     let p = 150.;
@@ -246,7 +262,7 @@ pub fn texture_bilinear_fragment_shader(payload: &shader::FragmentShaderPayload)
     let normal = payload.normal.normalize();
     let mut result_color = Vec3::ZERO;
 
-    for light in lights {
+    for (i, light) in payload.lights.iter().enumerate() {
         // TODO: For each light source in the code, calculate what the *ambient*, *diffuse*, and *specular*
         // components are. Then, accumulate that result on the *result_color* object.
 
@@ -270,7 +286,7 @@ pub fn texture_bilinear_fragment_shader(payload: &shader::FragmentShaderPayload)
                 .max(0.0)
                 .powf(p);
 
-        result_color += la + ld + ls;
+        result_color += la + (ld + ls) * payload.light_visibility(i, point);
     }
     result_color * 255.0
 }
@@ -280,17 +296,7 @@ pub fn phong_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
     let kd = payload.color / 255.0;
     let ks = Vec3::ONE * 0.7937;
 
-    let l1 = Light {
-        position: Vec3::ONE * 20.0,
-        intensity: Vec3::ONE * 500.,
-    };
-    let l2 = Light {
-        position: Vec3::new(-20., 20., 0.),
-        intensity: Vec3::ONE * 500.,
-    };
-
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vec3::ONE * 10.;
+    let amb_light_intensity = ambient_light_intensity(payload);
     let eye_pos = Vec3::new(0.0, 0.0, 10.0);
 
     let p = 150.;
@@ -300,7 +306,7 @@ pub fn phong_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
     let normal = payload.normal.normalize();
 
     let mut result_color = Vec3::ZERO;
-    for light in lights {
+    for (i, light) in payload.lights.iter().enumerate() {
         // TODO: For each light source in the code, calculate what the *ambient*, *diffuse*, and *specular*
         // components are. Then, accumulate that result on the *result_color* object.
 
@@ -324,28 +330,122 @@ pub fn phong_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
                 .max(0.0)
                 .powf(p);
 
-        result_color += la + ld + ls;
+        result_color += la + (ld + ls) * payload.light_visibility(i, point);
     }
 
     result_color * 255.
 }
 
+/**
+ * physically based shading with the metallic-roughness model of `payload.pbr`:
+ * lambert diffuse plus a cook-torrance specular,
+ * with the ggx normal distribution, the smith geometry term and the schlick fresnel.
+ *
+ * lit by the lights of the rasterizer, each one shadowed by its shadow map if it has one.
+ * the ambient light and the reflections come from the environment, if there's one.
+ *
+ * the frame buffer holds 8 bit srgb, so the radiance is tone mapped by reinhard
+ * (c / (1 + c), keeping highlights from clipping to white) and encoded to srgb here.
+ */
+pub fn pbr_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
+    let radiance = pbr_radiance(payload);
+    let mapped = radiance / (Vec3::ONE + radiance);
+    Vec3::from(mapped.to_array().map(shader::linear_to_srgb)) * 255.
+}
+
+// the linear and unbounded radiance `pbr_fragment_shader` shows
+fn pbr_radiance(payload: &shader::FragmentShaderPayload) -> Vec3 {
+    let (base_color, metallic, roughness) = payload.pbr.sample(payload.tex_coords);
+    // a perfect mirror has a delta distribution, which a rasterizer can't show
+    let roughness = roughness.max(0.04);
+
+    let point = payload.view_pos;
+    let normal = payload.normal.normalize();
+    // the eye is at the origin of the view space
+    let v = (-point).normalize();
+    let n_dot_v = normal.dot(v).max(1e-4);
+
+    // dielectrics reflect about 4% at normal incidence, metals reflect their base color
+    let f0 = Vec3::splat(0.04).lerp(base_color, metallic);
+
+    let ambient = match payload.environment {
+        Some(environment) => {
+            // image based lighting with the split sum approximation
+            let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+            let kd = (Vec3::ONE - f) * (1.0 - metallic);
+            let diffuse = kd * base_color * environment.irradiance(normal) / PI;
+            let reflection = 2.0 * normal.dot(v) * normal - v;
+            let (scale, bias) = environment::env_brdf(n_dot_v, roughness);
+            diffuse + environment.specular(reflection, roughness) * (f0 * scale + bias)
+        }
+        None => base_color * 0.05,
+    };
+    let mut result_color = ambient * payload.ambient_occlusion;
+    for (i, light) in payload.lights.iter().enumerate() {
+        let light_dir = light.position - point;
+        let radiance = light.intensity / light_dir.dot(light_dir);
+        let l = light_dir.normalize();
+        let h = (l + v).normalize();
+        let n_dot_l = normal.dot(l);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+
+        let d = distribution_ggx(normal.dot(h), roughness);
+        let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+        let f = fresnel_schlick(h.dot(v).max(0.0), f0);
+        let specular = d * g * f / (4.0 * n_dot_v * n_dot_l);
+        // what is not reflected is refracted and scattered, metals absorb it
+        let kd = (Vec3::ONE - f) * (1.0 - metallic);
+        let diffuse = kd * base_color / PI;
+
+        result_color +=
+            (diffuse + specular) * radiance * n_dot_l * payload.light_visibility(i, point);
+    }
+
+    result_color
+}
+
+/**
+ * ggx (trowbridge-reitz) normal distribution,
+ * the density of microfacets facing the half vector.
+ */
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness.powi(4);
+    let n_dot_h = n_dot_h.max(0.0);
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * denom * denom)
+}
+
+/**
+ * smith geometry term with schlick-ggx,
+ * how much of the microfacets are neither shadowed nor masked.
+ */
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    // the k remapping for direct lighting
+    let k = (roughness + 1.0).powi(2) / 8.0;
+    let schlick_ggx = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    schlick_ggx(n_dot_v) * schlick_ggx(n_dot_l)
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: Vec3) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - cos_theta).powi(5)
+}
+
+/**
+ * schlick fresnel averaged over the microfacets of a rough surface,
+ * for the light of the whole environment rather than of a single direction.
+ */
+fn fresnel_schlick_roughness(cos_theta: f32, f0: Vec3, roughness: f32) -> Vec3 {
+    f0 + (Vec3::splat(1.0 - roughness).max(f0) - f0) * (1.0 - cos_theta).powi(5)
+}
+
 pub fn displacement_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
     let ka = Vec3::ONE * 0.005;
     let kd = payload.color / 255.0;
     let ks = Vec3::ONE * 0.7937;
 
-    let l1 = Light {
-        position: Vec3::ONE * 20.0,
-        intensity: Vec3::ONE * 500.,
-    };
-    let l2 = Light {
-        position: Vec3::new(-20., 20., 0.),
-        intensity: Vec3::ONE * 500.,
-    };
-
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vec3::ONE * 10.;
+    let amb_light_intensity = ambient_light_intensity(payload);
     let eye_pos = Vec3::new(0.0, 0.0, 10.0);
 
     let p = 150.;
@@ -413,7 +513,7 @@ pub fn displacement_fragment_shader(payload: &shader::FragmentShaderPayload) ->
 
     let mut result_color = Vec3::ZERO;
 
-    for light in lights {
+    for (i, light) in payload.lights.iter().enumerate() {
         // TODO: For each light source in the code, calculate what the *ambient*, *diffuse*, and *specular*
         // components are. Then, accumulate that result on the *result_color* object.
 
@@ -437,7 +537,7 @@ pub fn displacement_fragment_shader(payload: &shader::FragmentShaderPayload) ->
                 .max(0.0)
                 .powf(p);
 
-        result_color += la + ld + ls;
+        result_color += la + (ld + ls) * payload.light_visibility(i, point);
     }
     result_color * 255.
 }
@@ -447,17 +547,7 @@ pub fn bump_fragment_shader(payload: &shader::FragmentShaderPayload) -> Vec3 {
     let kd = payload.color / 255.0;
     let ks = Vec3::ONE * 0.7937;
 
-    let l1 = Light {
-        position: Vec3::ONE * 20.0,
-        intensity: Vec3::ONE * 500.,
-    };
-    let l2 = Light {
-        position: Vec3::new(-20., -20., 0.),
-        intensity: Vec3::ONE * 500.,
-    };
-
-    let lights = vec![l1, l2];
-    let amb_light_intensity = Vec3::ONE * 10.;
+    let amb_light_intensity = ambient_light_intensity(payload);
     let eye_pos = Vec3::new(0.0, 0.0, 10.0);
 
     let p = 150.;
@@ -833,4 +923,126 @@ mod test {
             assert!(result.abs_diff_eq(point, f32::EPSILON));
         }
     }
+
+    #[test]
+    fn test_pbr_terms() {
+        // the projected microfacet area over the hemisphere is 1
+        for roughness in [0.3, 0.6, 1.0] {
+            let n = 512;
+            let mut sum = 0.0;
+            for i in 0..n {
+                let theta = (i as f32 + 0.5) / n as f32 * PI / 2.0;
+                let d = distribution_ggx(theta.cos(), roughness);
+                sum += d * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / n as f32);
+            }
+            assert!((sum - 1.0).abs() < 0.02, "{} {}", roughness, sum);
+        }
+
+        let f0 = Vec3::new(0.9, 0.6, 0.2);
+        assert!(fresnel_schlick(1.0, f0).abs_diff_eq(f0, 1e-6));
+        assert!(fresnel_schlick(0.0, f0).abs_diff_eq(Vec3::ONE, 1e-6));
+
+        assert!(geometry_smith(1.0, 1.0, 0.5) > geometry_smith(0.1, 0.1, 0.5));
+        assert!(geometry_smith(0.5, 0.5, 0.2) > geometry_smith(0.5, 0.5, 0.9));
+    }
+
+    #[test]
+    fn test_pbr_fragment_shader() {
+        let lights = [Vec3::new(-10., 10., 0.), Vec3::new(10., 10., 0.)].map(|position| Light {
+            position,
+            intensity: Vec3::ONE * 600.,
+        });
+        let shade_lit = |pbr: &shader::PbrParams, normal: Vec3, lights: &[Light]| {
+            pbr_radiance(&shader::FragmentShaderPayload {
+                view_pos: Vec3::new(0.0, 0.0, -10.0),
+                color: Vec3::ZERO,
+                normal,
+                tex_coords: Vec2::ZERO,
+                texture: &None,
+                lights,
+                shadow_maps: &[],
+                ambient_occlusion: 1.0,
+                pbr,
+                environment: None,
+            })
+        };
+        let shade = |pbr: &shader::PbrParams, normal: Vec3| shade_lit(pbr, normal, &lights);
+        // half way between the eye and the light at (10, 10, 0)
+        let mirror = (Vec3::new(10.0, 10.0, 10.0).normalize() + Vec3::Z).normalize();
+
+        let params = |metallic: f32, roughness: f32| shader::PbrParams {
+            base_color: Vec3::new(1.0, 0.2, 0.2),
+            metallic,
+            roughness,
+            ..Default::default()
+        };
+
+        // a smooth surface has a sharper highlight than a rough one
+        let (smooth, rough) = (params(0.0, 0.1), params(0.0, 0.8));
+        assert!(shade(&smooth, mirror).y > shade(&rough, mirror).y);
+        assert!(shade(&smooth, Vec3::Z).y < shade(&rough, Vec3::Z).y);
+
+        // a dielectric highlight is white, a metal one has the color of the metal
+        let dielectric = shade(&smooth, mirror);
+        let metal = shade(&params(1.0, 0.1), mirror);
+        assert!(dielectric.y > 0.5 * dielectric.x);
+        assert!(metal.y < 0.3 * metal.x);
+
+        // facing away from every light, only the ambient is left
+        let ambient = Vec3::new(1.0, 0.2, 0.2) * 0.05;
+        let back = shade(&rough, -Vec3::Y);
+        assert!(back.abs_diff_eq(ambient, 1e-3));
+        // and without any light, even facing the eye
+        assert!(shade_lit(&rough, Vec3::Z, &[]).abs_diff_eq(ambient, 1e-3));
+        assert!(shade(&rough, Vec3::Z).x > 2.0 * ambient.x);
+
+        // a smooth metal facing the eye in a white environment reflects its own color,
+        // on top of the direct light and in place of the constant ambient
+        let white = environment::EnvironmentMap::from_fn(32, 16, |_| Vec3::ONE);
+        let environment = environment::Environment::new(&white);
+        let metal = params(1.0, 0.0);
+        let reflected = pbr_radiance(&shader::FragmentShaderPayload {
+            view_pos: Vec3::new(0.0, 0.0, -10.0),
+            color: Vec3::ZERO,
+            normal: Vec3::Z,
+            tex_coords: Vec2::ZERO,
+            texture: &None,
+            lights: &lights,
+            shadow_maps: &[],
+            ambient_occlusion: 1.0,
+            pbr: &metal,
+            environment: Some(&environment),
+        });
+        let ambient = metal.base_color * 0.05;
+        let reflection = reflected - shade(&metal, Vec3::Z) + ambient;
+        assert!(reflection.abs_diff_eq(metal.base_color, 0.05));
+
+        // the shader tone maps and encodes the radiance, however bright, into 0..255
+        let strong = [Light {
+            position: Vec3::new(10., 10., 0.),
+            intensity: Vec3::ONE * 1e6,
+        }];
+        let color = |lights: &[Light]| {
+            pbr_fragment_shader(&shader::FragmentShaderPayload {
+                view_pos: Vec3::new(0.0, 0.0, -10.0),
+                color: Vec3::ZERO,
+                normal: mirror,
+                tex_coords: Vec2::ZERO,
+                texture: &None,
+                lights,
+                shadow_maps: &[],
+                ambient_occlusion: 1.0,
+                pbr: &smooth,
+                environment: None,
+            })
+        };
+        let highlight = color(&strong);
+        assert!(highlight.max_element() <= 255.0 && highlight.min_element() > 200.0);
+        // the dark ambient is lifted by the srgb encoding
+        let dark = color(&[]);
+        let linear = Vec3::new(1.0, 0.2, 0.2) * 0.05;
+        assert!(dark.x > linear.x * 255.0 && dark.x < 255.0 * 0.3);
+        let mapped = shader::linear_to_srgb(linear.x / (1.0 + linear.x)) * 255.0;
+        assert!((dark.x - mapped).abs() < 1e-3);
+    }
 }
@@ -1,20 +1,56 @@
 use anyhow::Result;
 use glam::Vec3;
 use homework3::{
-    bump_fragment_shader, displacement_fragment_shader, get_model_matrix, get_projection_matrix,
-    get_view_matrix, normal_fragment_shader, phong_fragment_shader, rst, shader,
+    bezier_patch, bump_fragment_shader, displacement_fragment_shader,
+    environment::{Environment, EnvironmentMap},
+    gbuffer::DebugView,
+    get_model_matrix, get_projection_matrix, get_view_matrix,
+    mesh::Mesh,
+    normal_fragment_shader, pbr_fragment_shader, phong_fragment_shader, rst, shader,
+    shadow::ShadowMap,
+    ssao::Ssao,
     texture_bilinear_fragment_shader, texture_fragment_shader,
 };
-use obj::load_obj;
-use utils::{
-    graphic::{save_image, Action, Control, Key},
-    triangle::Triangle,
-};
+use utils::graphic::{save_image, Action, Control, Key};
+use utils::post::{Bloom, ColorLut, Fxaa, PostChain, ToneCurve, Vignette};
+use utils::rasterizer::RasterizableMut;
+use utils::rgb::Rgb;
+use utils::stats::{FrameLog, FrameStats};
 
 fn main() -> Result<()> {
     // let mut angle = 0.0;
     let mut angle = 140.0;
     let mut scale = 2.5;
+    let mut interpolation = rst::Interpolation::PerspectiveCorrect;
+    let mut shading = rst::Shading::Forward;
+    let mut render_mode = rst::RenderMode::Filled;
+    // I turns the frame stats on and off, they're shown in the overlay,
+    // saved to stats_file and averaged on the terminal at last
+    let mut use_stats = false;
+    let mut frame_log = FrameLog::new();
+    // shown in the overlay of the next frame
+    let mut last_stats: Option<FrameStats> = None;
+    let stats_file = "stats.csv";
+    // ssao works only in deferred shading, turning it on switches to deferred,
+    // and switching to forward turns it off
+    let mut use_ssao = false;
+    // K shows a grid of pbr spheres instead of the model,
+    // metallic grows to the right and roughness grows downwards
+    let mut sphere_grid = false;
+    let grid_size = 5;
+    // T shows the teapot tessellated from its bezier patches instead of the model
+    let mut show_teapot = false;
+    let teapot_file = "homework3/models/teapot/teapot.bpt";
+    let teapot_divisions = 8;
+    // E lights the scene by the environment instead of the constant ambient light,
+    // an equirectangular .hdr or .png, or the procedural sky if empty
+    let mut use_environment = false;
+    let environment_file = "";
+    // H casts shadows of the model from the default lights, onto the model itself
+    let mut use_shadows = false;
+    let shadow_map_size = 1024;
+    // None for the final color, otherwise a G-buffer attribute
+    let mut debug_view: Option<DebugView> = None;
     // let mut scale = 10.0;
     let filename = "output.png";
     // spot
@@ -48,40 +84,104 @@ fn main() -> Result<()> {
     // let hmap_file = "";
 
     let frame_width = 700;
-    let mut triangle_list = Vec::new();
 
-    let input = std::io::BufReader::new(std::fs::File::open(format!("{}{}", obj_path, obj_file))?);
-    let loadout: obj::Obj<obj::TexturedVertex> = load_obj(input)?;
+    // F1..F5 turn the post processing filters on and off
+    let mut post = PostChain::new()
+        .with(Fxaa::default())
+        .with(Bloom::default())
+        .with(Vignette::default())
+        .with(ToneCurve::Gamma(1.2))
+        .with(ColorLut::from_fn(16, |c| c * Vec3::new(1.1, 1.0, 0.85)));
+    for i in 0..post.len() {
+        post.set_enabled(i, false);
+    }
+
+    let environment = match environment_file {
+        "" => Environment::sky(),
+        file => Environment::new(&EnvironmentMap::load_equirect(file)?),
+    };
+    dbg!("environment loaded");
+
+    let mesh = Mesh::load_obj(&format!("{}{}", obj_path, obj_file))?;
     dbg!("obj loaded");
 
-    for i in (0..loadout.indices.len()).step_by(3) {
-        let mut t = Triangle::new();
+    let teapot = bezier_patch::tessellate(&bezier_patch::load_bpt(teapot_file)?, teapot_divisions);
 
-        for j in 0..3 {
-            // dbg!(i, j);
-            let vertice = &loadout.vertices[loadout.indices[i + j] as usize];
-            t.set_vertex(j, Vec3::from_array(vertice.position));
-            t.set_normal(j, Vec3::from_array(vertice.normal));
-            t.set_tex_coords(j, vertice.texture[0], vertice.texture[1]);
-        }
-        triangle_list.push(t);
-    }
+    let eye_pos = Vec3::new(0.0, 0.0, 10.0);
+    let view = get_view_matrix(eye_pos);
 
-    dbg!("triangle_list loaded");
+    // a shadow map for every default light, they're in the view space
+    // and the shadow maps take them in the world space.
+    // every light draws the model in its own shadow pass, sharing the meshes but not the buffer ids
+    let shadow_maps: Vec<ShadowMap> = homework3::default_lights()
+        .into_iter()
+        .map(|light| {
+            let light = homework3::Light {
+                position: view.inverse().transform_point3(light.position),
+                ..light
+            };
+            ShadowMap::new(light, Vec3::ZERO, shadow_map_size, 60.0)
+        })
+        .collect();
+    let mut shadow_passes: Vec<_> = shadow_maps
+        .iter()
+        .map(|shadow_map| {
+            let mut shadow_r = shadow_map.rasterizer();
+            let model_ids = (
+                shadow_r.load_vertices(mesh.vertices.clone()),
+                shadow_r.load_indices(mesh.indices.clone()),
+            );
+            let teapot_ids = (
+                shadow_r.load_vertices(teapot.vertices.clone()),
+                shadow_r.load_indices(teapot.indices.clone()),
+            );
+            (shadow_r, model_ids, teapot_ids)
+        })
+        .collect();
 
     let mut r = rst::Rasterizer::new(frame_width, frame_width);
+    let vert_id = r.load_vertices(mesh.vertices);
+    let ind_id = r.load_indices(mesh.indices);
+
+    let teapot_ids = (
+        r.load_vertices(teapot.vertices),
+        r.load_indices(teapot.indices),
+    );
+    // the teapot stands on z = 0 with z up, turn it to y up and center it
+    let teapot_model = glam::Mat4::from_scale(Vec3::splat(0.4))
+        * glam::Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2)
+        * glam::Mat4::from_translation(Vec3::new(0.0, 0.0, -1.575));
+
+    let sphere = Mesh::sphere(0.6, 32, 16);
+    let sphere_ids = (
+        r.load_vertices(sphere.vertices),
+        r.load_indices(sphere.indices),
+    );
+    let sphere_materials: Vec<rst::MaterialId> = (0..grid_size * grid_size)
+        .map(|i| {
+            let (col, row) = (i % grid_size, i / grid_size);
+            r.load_material(shader::Material {
+                fragment_shader: pbr_fragment_shader,
+                texture: None,
+                pbr: shader::PbrParams {
+                    base_color: Vec3::new(0.9, 0.6, 0.2),
+                    metallic: col as f32 / (grid_size - 1) as f32,
+                    roughness: row as f32 / (grid_size - 1) as f32,
+                    ..Default::default()
+                },
+            })
+        })
+        .collect();
 
     // let mut texture_path = format!("{}{}", obj_path, "rock.png");
     // r.set_texture(shader::Texture::new(&texture_path)?);
 
-    let eye_pos = Vec3::new(0.0, 0.0, 10.0);
-
     r.set_vertex_shader(homework3::vertex_shader);
 
     // let use_shader = UseShader::Normal;
     // let use_shader = UseShader::Phong;
     // let use_shader = UseShader::Texture;
-    let use_shader = UseShader::Normal;
+    let mut use_shader = UseShader::Normal;
     set_fragment_shader(&mut r, use_shader, obj_path, texture_file, hmap_file)?;
 
     dbg!("texture loaded");
@@ -90,26 +190,127 @@ fn main() -> Result<()> {
         for action in actions {
             match action {
                 Action::Stop => {
-                    save_image(&r, filename)?;
+                    save_image(&post.apply(&r), filename)?;
+                    if !frame_log.frames().is_empty() {
+                        frame_log.write_csv(stats_file)?;
+                        println!("average: {}", frame_log.average().unwrap());
+                    }
                     return Ok(Control::Stop);
                 }
                 Action::Key(Key::A) => angle -= 10.0,
                 Action::Key(Key::D) => angle += 10.0,
                 Action::Key(Key::W) => scale += 0.1,
                 Action::Key(Key::S) => scale -= 0.1,
+                Action::Key(Key::P) => {
+                    interpolation = match interpolation {
+                        rst::Interpolation::PerspectiveCorrect => rst::Interpolation::Affine,
+                        rst::Interpolation::Affine => rst::Interpolation::PerspectiveCorrect,
+                    };
+                    dbg!(interpolation);
+                    r.set_interpolation(interpolation);
+                }
+                Action::Key(Key::F) => {
+                    shading = match shading {
+                        rst::Shading::Forward => rst::Shading::Deferred,
+                        rst::Shading::Deferred => rst::Shading::Forward,
+                    };
+                    dbg!(shading);
+                    r.set_shading(shading);
+                    if use_ssao && shading == rst::Shading::Forward {
+                        use_ssao = false;
+                        dbg!(use_ssao);
+                        r.set_ssao(None);
+                    }
+                }
+                Action::Key(Key::I) => {
+                    use_stats = !use_stats;
+                    r.set_stats_enabled(use_stats);
+                }
+                Action::Key(Key::M) => {
+                    render_mode = match render_mode {
+                        rst::RenderMode::Filled => rst::RenderMode::Wireframe,
+                        rst::RenderMode::Wireframe => rst::RenderMode::HiddenLine,
+                        rst::RenderMode::HiddenLine => rst::RenderMode::SolidWithEdges,
+                        rst::RenderMode::SolidWithEdges => rst::RenderMode::Filled,
+                    };
+                    dbg!(render_mode);
+                    r.set_render_mode(render_mode);
+                }
+                Action::Key(Key::K) => {
+                    sphere_grid = !sphere_grid;
+                    dbg!(sphere_grid);
+                }
+                Action::Key(Key::T) => {
+                    show_teapot = !show_teapot;
+                    dbg!(show_teapot);
+                }
+                Action::Key(Key::E) => {
+                    use_environment = !use_environment;
+                    dbg!(use_environment);
+                    r.set_environment(use_environment.then(|| environment.clone()));
+                }
+                Action::Key(Key::H) => {
+                    use_shadows = !use_shadows;
+                    dbg!(use_shadows);
+                    r.set_shadow_maps(match use_shadows {
+                        true => shadow_maps.clone(),
+                        false => vec![],
+                    });
+                }
+                Action::Key(Key::O) => {
+                    use_ssao = !use_ssao;
+                    dbg!(use_ssao);
+                    r.set_ssao(use_ssao.then(|| Ssao::new(0.5, 16)));
+                    if use_ssao && shading == rst::Shading::Forward {
+                        shading = rst::Shading::Deferred;
+                        dbg!(shading);
+                        r.set_shading(shading);
+                    }
+                }
+                Action::Key(k) if matches!(k, Key::F1 | Key::F2 | Key::F3 | Key::F4 | Key::F5) => {
+                    let ind = match k {
+                        Key::F1 => 0,
+                        Key::F2 => 1,
+                        Key::F3 => 2,
+                        Key::F4 => 3,
+                        Key::F5 => 4,
+                        _ => panic!(),
+                    };
+                    let enabled = post.toggle(ind);
+                    dbg!(post.name(ind), enabled);
+                }
+                Action::Key(Key::C) => debug_view = None,
+                Action::Key(Key::Z) => debug_view = Some(DebugView::Depth),
+                Action::Key(Key::N) => debug_view = Some(DebugView::Normal),
+                Action::Key(Key::U) => debug_view = Some(DebugView::TexCoords),
+                Action::Key(Key::B) => debug_view = Some(DebugView::Barycentric),
+                Action::Key(Key::G) => {
+                    for view in DebugView::ALL {
+                        let filename = format!("output-{}.png", view.name());
+                        save_image(&r.debug_view(view), &filename)?;
+                        dbg!(filename);
+                    }
+                }
                 Action::Key(k)
                     if matches!(
                         k,
-                        Key::Key1 | Key::Key2 | Key::Key3 | Key::Key4 | Key::Key5 | Key::Key6
+                        Key::Key1
+                            | Key::Key2
+                            | Key::Key3
+                            | Key::Key4
+                            | Key::Key5
+                            | Key::Key6
+                            | Key::Key7
                     ) =>
                 {
-                    let use_shader = match k {
+                    use_shader = match k {
                         Key::Key1 => UseShader::Normal,
                         Key::Key2 => UseShader::Phong,
                         Key::Key3 => UseShader::Texture,
                         Key::Key4 => UseShader::Bump,
                         Key::Key5 => UseShader::Displacement,
                         Key::Key6 => UseShader::BilinearTexture,
+                        Key::Key7 => UseShader::Pbr,
                         _ => panic!(),
                     };
                     set_fragment_shader(&mut r, use_shader, obj_path, texture_file, hmap_file)?;
@@ -120,19 +321,79 @@ fn main() -> Result<()> {
         r.clear(rst::Buffers::all());
 
         r.set_model(get_model_matrix(angle, scale));
-        r.set_view(get_view_matrix(eye_pos));
+        r.set_view(view);
         r.set_projection(get_projection_matrix(45., 1., 0.1, 50.));
 
-        r.draw_triangle(&triangle_list);
+        if use_shadows {
+            // the sphere grid casts no shadows
+            for (i, (shadow_r, model_ids, teapot_ids)) in shadow_passes.iter_mut().enumerate() {
+                shadow_r.clear(rst::Buffers::all());
+                if !sphere_grid {
+                    let (ids, model) = match show_teapot {
+                        true => (teapot_ids, get_model_matrix(angle, scale) * teapot_model),
+                        false => (model_ids, get_model_matrix(angle, scale)),
+                    };
+                    shadow_r.set_model(model);
+                    shadow_r.draw(ids.0, ids.1, rst::Primitive::Triangle);
+                }
+                r.shadow_maps_mut()[i].capture(shadow_r);
+            }
+        }
+
+        if sphere_grid {
+            let spacing = 1.5;
+            let offset = (grid_size - 1) as f32 / 2.0;
+            for (i, material) in sphere_materials.iter().enumerate() {
+                let (col, row) = ((i % grid_size) as f32, (i / grid_size) as f32);
+                let position = Vec3::new(col - offset, offset - row, 0.0) * spacing;
+                r.set_model(glam::Mat4::from_translation(position));
+                r.set_material(Some(*material));
+                r.draw(sphere_ids.0, sphere_ids.1, rst::Primitive::Triangle);
+            }
+            r.set_material(None);
+        } else if show_teapot {
+            r.set_model(get_model_matrix(angle, scale) * teapot_model);
+            r.draw(teapot_ids.0, teapot_ids.1, rst::Primitive::Triangle);
+        } else {
+            r.draw(vert_id, ind_id, rst::Primitive::Triangle);
+        }
+        r.resolve();
 
         dbg!("display_image");
-        display_image(&r)?;
+        // the overlay is drawn after post processing, so the filters don't blur it
+        let (mut frame, view_name) = match debug_view {
+            Some(view) => (r.debug_view(view), view.name()),
+            None => (post.apply(&r), use_shader.name()),
+        };
+        frame.draw_text(8, 8, view_name, Rgb(255, 255, 255));
+        if sphere_grid {
+            let bottom = frame.height as i32 - 16;
+            frame.draw_text(8, bottom, "metallic ->   roughness v", Rgb(255, 255, 255));
+        }
+        if let Some(stats) = last_stats {
+            let overlay = format!(
+                "{:.1} ms\n{} triangles\noverdraw {:.2}",
+                stats.total().as_secs_f64() * 1000.0,
+                stats.triangles,
+                stats.overdraw()
+            );
+            frame.draw_text(8, 20, &overlay, Rgb(255, 255, 0));
+        }
+        // only the display itself, post processing and the overlay are not counted
+        let display_start = std::time::Instant::now();
+        display_image(&frame)?;
+        last_stats = r.take_stats();
+        if let Some(stats) = last_stats.as_mut() {
+            stats.display = display_start.elapsed();
+            frame_log.push(*stats);
+        }
         return Ok(Control::Continue);
     });
 
     return Ok(());
 }
 
+#[derive(Clone, Copy)]
 enum UseShader {
     Normal,
     Phong,
@@ -140,6 +401,21 @@ enum UseShader {
     BilinearTexture,
     Bump,
     Displacement,
+    Pbr,
+}
+
+impl UseShader {
+    fn name(&self) -> &'static str {
+        match self {
+            UseShader::Normal => "normal",
+            UseShader::Phong => "phong",
+            UseShader::Texture => "texture",
+            UseShader::BilinearTexture => "bilinear texture",
+            UseShader::Bump => "bump",
+            UseShader::Displacement => "displacement",
+            UseShader::Pbr => "pbr",
+        }
+    }
 }
 
 fn set_fragment_shader(
@@ -172,6 +448,16 @@ fn set_fragment_shader(
             r.set_texture(shader::Texture::new(&texture_path)?);
             displacement_fragment_shader
         }
+        UseShader::Pbr => {
+            let texture_path = format!("{}{}", obj_path, texture_file);
+            r.set_pbr(shader::PbrParams {
+                base_color: Vec3::ONE,
+                roughness: 0.4,
+                base_color_map: Some(shader::Texture::new(&texture_path)?),
+                ..Default::default()
+            });
+            pbr_fragment_shader
+        }
     };
     r.set_fragment_shader(active_shader);
     Ok(())
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use glam::{Vec2, Vec3};
+use obj::load_obj;
+use utils::triangle::Triangle;
+
+/**
+ * a single vertex of an indexed mesh,
+ * shared by every triangle referencing its index
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Vertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub tex_coords: Vec2,
+}
+
+/**
+ * an indexed triangle mesh: a vertex array plus an index array,
+ * every 3 indices (counter clockwise) make up one triangle.
+ */
+#[derive(Clone, Default, Debug)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    /**
+     * load a mesh from an obj file.
+     * if the model has no normals or texture coordinates (e.g. bunny),
+     * vertex normals are computed from the faces and uvs are left at zero.
+     */
+    pub fn load_obj(path: &str) -> Result<Self> {
+        let content = std::fs::read(path)?;
+
+        if let Ok(loadout) = load_obj::<obj::TexturedVertex, _, u32>(&content[..]) {
+            let vertices = loadout
+                .vertices
+                .iter()
+                .map(|v| Vertex {
+                    position: Vec3::from_array(v.position),
+                    normal: Vec3::from_array(v.normal),
+                    tex_coords: Vec2::new(v.texture[0], v.texture[1]),
+                })
+                .collect();
+            return Ok(Self {
+                vertices,
+                indices: to_faces(&loadout.indices),
+            });
+        }
+
+        let loadout = load_obj::<obj::Position, _, u32>(&content[..])?;
+        let vertices = loadout
+            .vertices
+            .iter()
+            .map(|v| Vertex {
+                position: Vec3::from_array(v.position),
+                ..Default::default()
+            })
+            .collect();
+        let mut mesh = Self {
+            vertices,
+            indices: to_faces(&loadout.indices),
+        };
+        mesh.compute_normals();
+        Ok(mesh)
+    }
+
+    /**
+     * a square floor on the plane y = 0, from -half_size to half_size on x and z.
+     * it faces +y, and its uv covers the whole texture once.
+     */
+    pub fn floor(half_size: f32) -> Self {
+        let vertex = |x: f32, z: f32, u: f32, v: f32| Vertex {
+            position: Vec3::new(x * half_size, 0.0, z * half_size),
+            normal: Vec3::Y,
+            tex_coords: Vec2::new(u, v),
+        };
+        Self {
+            vertices: vec![
+                vertex(-1.0, 1.0, 0.0, 0.0),
+                vertex(1.0, 1.0, 1.0, 0.0),
+                vertex(1.0, -1.0, 1.0, 1.0),
+                vertex(-1.0, -1.0, 0.0, 1.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        }
+    }
+
+    /**
+     * a uv sphere around the origin, with `segments` slices around y and `rings` stacks from top to bottom.
+     * u goes around y once, v goes from 0 at the bottom pole to 1 at the top pole,
+     * the seam has its vertices doubled so the uv doesn't wrap back.
+     */
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+        for i in 0..=rings {
+            let theta = i as f32 / rings as f32 * std::f32::consts::PI;
+            for j in 0..=segments {
+                let phi = j as f32 / segments as f32 * 2.0 * std::f32::consts::PI;
+                let normal = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    -theta.sin() * phi.sin(),
+                );
+                vertices.push(Vertex {
+                    position: normal * radius,
+                    normal,
+                    tex_coords: Vec2::new(
+                        j as f32 / segments as f32,
+                        1.0 - i as f32 / rings as f32,
+                    ),
+                });
+            }
+        }
+
+        let index = |i: u32, j: u32| (i * (segments + 1) + j) as usize;
+        let mut indices = Vec::with_capacity((2 * segments * rings) as usize);
+        for i in 0..rings {
+            for j in 0..segments {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                );
+                // the triangles touching a pole would be degenerate
+                if i != rings - 1 {
+                    indices.push([a, b, c]);
+                }
+                if i != 0 {
+                    indices.push([a, c, d]);
+                }
+            }
+        }
+        Self { vertices, indices }
+    }
+
+    /**
+     * set every vertex normal to the average of its adjacent face normals,
+     * weighted by face area (the cross product is not normalized on purpose).
+     */
+    pub fn compute_normals(&mut self) {
+        for v in self.vertices.iter_mut() {
+            v.normal = Vec3::ZERO;
+        }
+        for [a, b, c] in self.indices.iter().copied() {
+            let (pa, pb, pc) = (
+                self.vertices[a].position,
+                self.vertices[b].position,
+                self.vertices[c].position,
+            );
+            let n = (pb - pa).cross(pc - pa);
+            self.vertices[a].normal += n;
+            self.vertices[b].normal += n;
+            self.vertices[c].normal += n;
+        }
+        for v in self.vertices.iter_mut() {
+            v.normal = v.normal.normalize_or_zero();
+        }
+    }
+
+    /**
+     * expand the mesh to a triangle list,
+     * every shared vertex is copied once per triangle using it.
+     */
+    pub fn to_triangles(&self) -> Vec<Triangle> {
+        self.indices
+            .iter()
+            .map(|ind| {
+                let mut t = Triangle::new();
+                for (j, &vi) in ind.iter().enumerate() {
+                    let vertex = &self.vertices[vi];
+                    t.set_vertex(j, vertex.position);
+                    t.set_normal(j, vertex.normal);
+                    t.set_tex_coords(j, vertex.tex_coords.x, vertex.tex_coords.y);
+                }
+                t
+            })
+            .collect()
+    }
+}
+
+fn to_faces(indices: &[u32]) -> Vec<[usize; 3]> {
+    indices
+        .chunks_exact(3)
+        .map(|f| [f[0] as usize, f[1] as usize, f[2] as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compute_normals() {
+        // a unit square on the xy plane, facing +z
+        let mut mesh = Mesh {
+            vertices: vec![
+                Vertex {
+                    position: Vec3::new(0.0, 0.0, 0.0),
+                    ..Default::default()
+                },
+                Vertex {
+                    position: Vec3::new(1.0, 0.0, 0.0),
+                    ..Default::default()
+                },
+                Vertex {
+                    position: Vec3::new(1.0, 1.0, 0.0),
+                    ..Default::default()
+                },
+                Vertex {
+                    position: Vec3::new(0.0, 1.0, 0.0),
+                    ..Default::default()
+                },
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        };
+        mesh.compute_normals();
+        for v in &mesh.vertices {
+            assert!(v.normal.abs_diff_eq(Vec3::Z, f32::EPSILON));
+        }
+
+        let triangles = mesh.to_triangles();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[1].c(), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_sphere() {
+        let mesh = Mesh::sphere(2.0, 16, 8);
+        assert_eq!(mesh.vertices.len(), 17 * 9);
+        assert_eq!(mesh.indices.len(), 2 * 16 * 8 - 2 * 16);
+        for v in &mesh.vertices {
+            assert!((v.position.length() - 2.0).abs() < 1e-5);
+            assert!(v.normal.abs_diff_eq(v.position / 2.0, 1e-5));
+        }
+        // counter clockwise seen from outside
+        for [a, b, c] in mesh.indices.iter().copied() {
+            let (pa, pb, pc) = (
+                mesh.vertices[a].position,
+                mesh.vertices[b].position,
+                mesh.vertices[c].position,
+            );
+            let n = (pb - pa).cross(pc - pa);
+            assert!(n.length() > 1e-6);
+            assert!(n.dot(pa + pb + pc) > 0.0);
+        }
+    }
+}
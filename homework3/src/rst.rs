@@ -1,22 +1,129 @@
-use crate::shader::{self, FragmentShader, FragmentShaderPayload, Texture, VertexShader};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Instant;
 
-use glam::{Mat4, Vec3, Vec4};
-pub use utils::rasterizer::{Buffers, IndBufId, PosBufId, Primitive, Rasterizable};
+use crate::environment::Environment;
+use crate::gbuffer::{DebugView, GBuffer, GBufferSample};
+use crate::mesh::Vertex;
+use crate::shader::{
+    self, FragmentShader, FragmentShaderPayload, Material, PbrParams, Texture, VertexShader,
+    VertexShaderPayload,
+};
+use crate::shadow::ShadowMap;
+use crate::ssao::Ssao;
+use crate::Light;
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+pub use utils::rasterizer::{
+    BufRasterizer, Buffers, IndBufId, PosBufId, Primitive, Rasterizable, VertBufId,
+};
+use utils::stats::FrameStats;
+
+/**
+ * matrices shared by every vertex of a draw call,
+ * computed once per draw instead of once per triangle.
+ */
+struct DrawMatrices {
+    mvp: Mat4,
+    model_view: Mat4,
+    // (view * model)^-1^T, for transforming normals
+    normal: Mat4,
+}
+
+/**
+ * the result of the vertex stage for a single vertex.
+ * in indexed drawing it's cached, and shared by every triangle using the vertex.
+ */
+#[derive(Clone, Copy, Default)]
+struct VertexOut {
+    screen_pos: Vec3,
+    // 1/w in clip space, kept for perspective correct interpolation
+    w_reciprocal: f32,
+
+    // varyings, interpolated for each fragment
+    view_pos: Vec3,
+    color: Vec3,
+    normal: Vec3,
+    tex_coords: Vec2,
+}
+
+/**
+ * how the varyings (color, normal, uv, view position) are interpolated inside a triangle
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    // interpolate in view space, by weighting the screen space barycentric coordinates with 1/w
+    PerspectiveCorrect,
+    // interpolate by the screen space barycentric coordinates directly,
+    // textures on surfaces not facing the screen would look warped.
+    Affine,
+}
+
+/**
+ * when the fragment shader runs
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shading {
+    // shade every fragment passing the depth test right away,
+    // fragments overwritten later by closer ones are shaded for nothing.
+    Forward,
+    // only fill the G-buffer while drawing, then shade every visible pixel once in `resolve`.
+    Deferred,
+}
+
+/**
+ * how triangles are drawn
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    Filled,
+    // only the edges, without depth test, the edges behind are seen too
+    Wireframe,
+    // the edges of the visible triangles, the faces are filled by the background
+    HiddenLine,
+    // the filled triangles with their edges drawn over
+    SolidWithEdges,
+}
+
+#[derive(Clone, Copy)]
+pub struct MaterialId(pub u32);
 
 pub struct Rasterizer {
     model: Mat4,
     view: Mat4,
     projection: Mat4,
     texture: Option<Texture>,
+    pbr: PbrParams,
+    lights: Vec<Light>,
+    shadow_maps: Vec<ShadowMap>,
+    environment: Option<Environment>,
+    ssao: Option<Ssao>,
+
+    // buffers are shared with `draw` by Rc, so drawing don't need to clone them
+    vert_buf: HashMap<u32, Rc<Vec<Vertex>>>,
+    ind_buf: HashMap<u32, Rc<Vec<[usize; 3]>>>,
+    materials: HashMap<u32, Material>,
+    // the material of the following draws, 0 for the fragment shader and texture set directly
+    material: u32,
 
     frame_buf: Vec<utils::triangle::Rgb>,
     depth_buf: Vec<f32>,
+    g_buf: GBuffer,
 
     vertex_shader: Option<VertexShader>,
     fragment_shader: Option<FragmentShader>,
+    interpolation: Interpolation,
+    shading: Shading,
+    render_mode: RenderMode,
+    edge_color: Vec3,
+    // in pixels
+    edge_width: f32,
+    // None if not collecting stats
+    stats: Option<FrameStats>,
 
     width: u32,
     height: u32,
+    next_id: u32,
 }
 
 impl utils::rasterizer::Rasterizable for Rasterizer {
@@ -29,6 +136,12 @@ impl utils::rasterizer::Rasterizable for Rasterizer {
     }
 }
 
+impl utils::rasterizer::RasterizableMut for Rasterizer {
+    fn data_mut(&mut self) -> &mut [utils::rgb::Rgb] {
+        &mut self.frame_buf
+    }
+}
+
 impl Rasterizer {
     pub fn new(width: u32, height: u32) -> Self {
         let mut res = Self {
@@ -36,15 +149,33 @@ impl Rasterizer {
             view: Mat4::default(),
             projection: Mat4::default(),
             texture: None,
+            pbr: PbrParams::default(),
+            lights: crate::default_lights(),
+            shadow_maps: Vec::new(),
+            environment: None,
+            ssao: None,
+
+            vert_buf: HashMap::default(),
+            ind_buf: HashMap::default(),
+            materials: HashMap::default(),
+            material: 0,
 
             frame_buf: Vec::new(),
             depth_buf: Vec::new(),
+            g_buf: GBuffer::new(width, height),
 
             vertex_shader: None,
             fragment_shader: None,
+            interpolation: Interpolation::PerspectiveCorrect,
+            shading: Shading::Forward,
+            render_mode: RenderMode::Filled,
+            edge_color: Vec3::ONE * 255.0,
+            edge_width: 1.0,
+            stats: None,
 
             width,
             height,
+            next_id: 0,
         };
 
         res.frame_buf
@@ -54,11 +185,43 @@ impl Rasterizer {
         res
     }
 
+    pub fn load_vertices(&mut self, vertices: Vec<Vertex>) -> VertBufId {
+        let id = self.get_next_id();
+        self.vert_buf.insert(id, Rc::new(vertices));
+        VertBufId(id)
+    }
+
+    pub fn load_indices(&mut self, indices: Vec<[usize; 3]>) -> IndBufId {
+        let id = self.get_next_id();
+        self.ind_buf.insert(id, Rc::new(indices));
+        IndBufId(id)
+    }
+
+    pub fn load_material(&mut self, material: Material) -> MaterialId {
+        let id = self.get_next_id();
+        self.materials.insert(id, material);
+        MaterialId(id)
+    }
+
+    /**
+     * use a loaded material for the following draws,
+     * or None to go back to the fragment shader and texture set on the rasterizer.
+     */
+    pub fn set_material(&mut self, material: Option<MaterialId>) {
+        self.material = material.map_or(0, |m| m.0);
+    }
+
     pub fn set_model(&mut self, model: glam::Mat4) {
         self.model = model;
     }
     pub fn set_view(&mut self, view: glam::Mat4) {
         self.view = view;
+        for shadow_map in self.shadow_maps.iter_mut() {
+            shadow_map.set_camera_view(view);
+        }
+        if let Some(environment) = self.environment.as_mut() {
+            environment.set_camera_view(view);
+        }
     }
     pub fn set_projection(&mut self, projection: glam::Mat4) {
         self.projection = projection;
@@ -68,6 +231,50 @@ impl Rasterizer {
         self.texture = Some(texture);
     }
 
+    /**
+     * the parameters of the physically based shader set directly on the rasterizer
+     */
+    pub fn set_pbr(&mut self, pbr: PbrParams) {
+        self.pbr = pbr;
+    }
+
+    /**
+     * the lights passed to fragment shaders, `default_lights` for a new rasterizer.
+     * in deferred shading they're only looped over once for every visible pixel.
+     */
+    pub fn set_lights(&mut self, lights: Vec<Light>) {
+        self.lights = lights;
+    }
+
+    /**
+     * the shadow maps passed to fragment shaders,
+     * the i-th one is of the i-th light of `set_lights` and shadows only that light.
+     */
+    pub fn set_shadow_maps(&mut self, mut shadow_maps: Vec<ShadowMap>) {
+        for shadow_map in shadow_maps.iter_mut() {
+            shadow_map.set_camera_view(self.view);
+        }
+        self.shadow_maps = shadow_maps;
+    }
+
+    /**
+     * the shadow maps set by `set_shadow_maps`,
+     * to capture them again or change their settings without setting them again.
+     */
+    pub fn shadow_maps_mut(&mut self) -> &mut [ShadowMap] {
+        &mut self.shadow_maps
+    }
+
+    /**
+     * the environment lighting the scene, None for the constant ambient light
+     */
+    pub fn set_environment(&mut self, mut environment: Option<Environment>) {
+        if let Some(environment) = environment.as_mut() {
+            environment.set_camera_view(self.view);
+        }
+        self.environment = environment;
+    }
+
     pub fn set_vertex_shader(&mut self, shader: shader::VertexShader) {
         self.vertex_shader = Some(shader);
     }
@@ -75,6 +282,54 @@ impl Rasterizer {
         self.fragment_shader = Some(shader);
     }
 
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn set_shading(&mut self, shading: Shading) {
+        self.shading = shading;
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    /**
+     * collect the counts and the timings of every stage while drawing.
+     * timing every fragment shader run costs a bit, so it's off by default.
+     */
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats = enabled.then(FrameStats::default);
+    }
+
+    /**
+     * the stats collected since the last call, None if not enabled.
+     * it should be called at the end of every frame.
+     */
+    pub fn take_stats(&mut self) -> Option<FrameStats> {
+        let depth_buf = &self.depth_buf;
+        self.stats.as_mut().map(|stats| {
+            stats.covered = depth_buf.iter().filter(|d| d.is_finite()).count() as u64;
+            std::mem::take(stats)
+        })
+    }
+
+    /**
+     * the color (0..255) and width (in pixels) of the edges in the edge render modes
+     */
+    pub fn set_edge_style(&mut self, color: Vec3, width: f32) {
+        self.edge_color = color;
+        self.edge_width = width;
+    }
+
+    /**
+     * modulate the ambient light by screen space ambient occlusion.
+     * it needs the whole G-buffer, so it only works in deferred shading.
+     */
+    pub fn set_ssao(&mut self, ssao: Option<Ssao>) {
+        self.ssao = ssao;
+    }
+
     fn set_pixel(&mut self, point: &Vec3, color: &utils::triangle::Rgb) {
         let (x_range, y_range) = (0..self.width, 0..self.height);
         let (x, y) = (point.x as u32, point.y as u32);
@@ -92,99 +347,283 @@ impl Rasterizer {
         }
         if buffers.contains(Buffers::DEPTH) {
             self.depth_buf.fill(f32::INFINITY);
+            self.g_buf.clear();
+        }
+    }
+
+    /**
+     * the attributes of the visible surface at every pixel, filled while drawing.
+     * it's cleared together with the depth buffer.
+     */
+    pub fn g_buffer(&self) -> &GBuffer {
+        &self.g_buf
+    }
+
+    /**
+     * render an attribute of the G-buffer into an image, for debugging.
+     * it could be displayed or saved the same as the rasterizer itself.
+     */
+    pub fn debug_view(&self, view: DebugView) -> BufRasterizer {
+        self.g_buf.view(view)
+    }
+
+    /**
+     * run the fragment shader once for every pixel in the G-buffer.
+     * in deferred shading it should be called after all draws of a frame,
+     * in forward shading the frame buffer is already shaded, so it does nothing.
+     */
+    pub fn resolve(&mut self) {
+        if self.shading != Shading::Deferred {
+            return;
         }
+        let start = self.stats.map(|_| Instant::now());
+        let ambient_occlusion = self
+            .ssao
+            .as_ref()
+            .map(|ssao| ssao.compute(&self.g_buf, self.projection));
+        for ind in 0..self.g_buf.samples.len() {
+            if let Some(s) = self.g_buf.samples[ind] {
+                self.record(|stats| stats.shaded += 1);
+                let color = self.fragment_stage(
+                    s.material,
+                    s.view_pos,
+                    s.color,
+                    s.normal,
+                    s.tex_coords,
+                    ambient_occlusion.as_ref().map_or(1.0, |ao| ao[ind]),
+                );
+                let color = self.edge_stage(color, s.edge);
+                self.frame_buf[ind] = utils::triangle::Rgb::from(&color);
+            }
+        }
+        self.record_time(start, |stats, t| stats.shade += t);
     }
 
     pub fn draw_triangle(&mut self, triangle_list: &Vec<utils::triangle::Triangle>) {
+        let m = self.draw_matrices();
+
+        for t in triangle_list {
+            let start = self.stats.map(|_| Instant::now());
+            let v = [0, 1, 2].map(|i| self.vertex_stage(&m, t.v[i], t.normal[i], t.tex_coords[i]));
+            self.record_time(start, |stats, t| stats.vertex += t);
+            self.rasterize_triangle(v);
+        }
+    }
+
+    /**
+     * draw an indexed mesh loaded by `load_vertices` and `load_indices`.
+     * every vertex runs through the vertex stage only once,
+     * however many triangles share it.
+     */
+    pub fn draw(&mut self, vert_buf_id: VertBufId, ind_buf_id: IndBufId, typ: Primitive) {
+        if typ != Primitive::Triangle {
+            unimplemented!()
+        }
+
+        let vertices = Rc::clone(self.vert_buf.get(&vert_buf_id.0).unwrap());
+        let indices = Rc::clone(self.ind_buf.get(&ind_buf_id.0).unwrap());
+
+        let m = self.draw_matrices();
+
+        // vertex cache, indexed the same as the vertex buffer
+        let start = self.stats.map(|_| Instant::now());
+        let cache: Vec<VertexOut> = vertices
+            .iter()
+            .map(|v| self.vertex_stage(&m, v.position, v.normal, v.tex_coords))
+            .collect();
+        self.record_time(start, |stats, t| stats.vertex += t);
+
+        for ind in indices.iter() {
+            self.rasterize_triangle([cache[ind[0]], cache[ind[1]], cache[ind[2]]]);
+        }
+    }
+
+    fn record(&mut self, f: impl FnOnce(&mut FrameStats)) {
+        if let Some(stats) = self.stats.as_mut() {
+            f(stats);
+        }
+    }
+
+    fn record_time(
+        &mut self,
+        start: Option<Instant>,
+        f: impl FnOnce(&mut FrameStats, std::time::Duration),
+    ) {
+        if let (Some(stats), Some(start)) = (self.stats.as_mut(), start) {
+            f(stats, start.elapsed());
+        }
+    }
+
+    fn draw_matrices(&self) -> DrawMatrices {
+        let model_view = self.view * self.model;
+        DrawMatrices {
+            mvp: self.projection * model_view,
+            model_view,
+            normal: model_view.inverse().transpose(),
+        }
+    }
+
+    fn vertex_stage(
+        &self,
+        m: &DrawMatrices,
+        position: Vec3,
+        normal: Vec3,
+        tex_coords: Vec2,
+    ) -> VertexOut {
         let f1 = (50. - 0.1) / 2.0;
         let f2 = (50. + 0.1) / 2.0;
 
-        let mvp = self.projection * self.view * self.model;
+        let position = match self.vertex_shader {
+            Some(vertex_shader) => vertex_shader(&VertexShaderPayload { position }),
+            None => position,
+        };
 
-        // let mut count = 0;
-        for t in triangle_list {
-            // count += 1;
-            // dbg!(triangle_list.len(), count);
-            let mut newtri = t.clone();
-            let vertex4 = t.to_vec4();
-            // Vec4
+        let view_pos = (m.model_view * to_vec4(&position, 1.)).truncate();
 
-            let mm = &[
-                Vec4::from(self.view * self.model * vertex4[0]),
-                Vec4::from(self.view * self.model * vertex4[1]),
-                Vec4::from(self.view * self.model * vertex4[2]),
-            ];
+        let mut v = m.mvp * to_vec4(&position, 1.);
+        let w_reciprocal = 1. / v.w;
+        v *= w_reciprocal;
+        v.x = 0.5 * self.width as f32 * (v.x + 1.);
+        v.y = 0.5 * self.height as f32 * (v.y + 1.);
+        v.z = v.z * f1 + f2;
 
-            let viewspace_pos = &[mm[0].truncate(), mm[1].truncate(), mm[2].truncate()];
+        VertexOut {
+            screen_pos: v.truncate(),
+            w_reciprocal,
+            view_pos,
+            color: Vec3::new(148., 121., 92.),
+            normal: (m.normal * to_vec4(&normal, 0.)).truncate(),
+            tex_coords,
+        }
+    }
 
-            let v = &mut [mvp * vertex4[0], mvp * vertex4[1], mvp * vertex4[2]];
+    fn fragment_stage(
+        &self,
+        material: u32,
+        view_pos: Vec3,
+        color: Vec3,
+        normal: Vec3,
+        tex_coords: Vec2,
+        ambient_occlusion: f32,
+    ) -> Vec3 {
+        let (fragment_shader, texture, pbr) = match self.materials.get(&material) {
+            Some(m) => (m.fragment_shader, &m.texture, &m.pbr),
+            None => (self.fragment_shader.unwrap(), &self.texture, &self.pbr),
+        };
+        fragment_shader(&FragmentShaderPayload {
+            view_pos,
+            color,
+            normal,
+            tex_coords,
+            texture,
+            lights: &self.lights,
+            shadow_maps: &self.shadow_maps,
+            ambient_occlusion,
+            pbr,
+            environment: self.environment.as_ref(),
+        })
+    }
 
-            for vec in v.iter_mut() {
-                *vec = *vec / vec.w
-            }
+    /**
+     * draw the edges over the shaded color by the render mode.
+     * @edge: how much the pixel is covered by an edge, 0..1
+     */
+    fn edge_stage(&self, color: Vec3, edge: f32) -> Vec3 {
+        match self.render_mode {
+            RenderMode::Filled | RenderMode::Wireframe => color,
+            RenderMode::HiddenLine => Vec3::ZERO.lerp(self.edge_color, edge),
+            RenderMode::SolidWithEdges => color.lerp(self.edge_color, edge),
+        }
+    }
 
-            let inv_trans = (self.view * self.model).inverse().transpose();
-            let n = &[
-                inv_trans * to_vec4(&t.normal[0], 0.),
-                inv_trans * to_vec4(&t.normal[1], 0.),
-                inv_trans * to_vec4(&t.normal[2], 0.),
-            ];
-
-            for vert in v.iter_mut() {
-                vert.x = 0.5 * self.width as f32 * (vert.x + 1.);
-                vert.y = 0.5 * self.width as f32 * (vert.y + 1.);
-                vert.z = vert.z * f1 + f2;
+    fn draw_line(&mut self, begin: Vec3, end: Vec3, color: Vec3) {
+        let color = utils::triangle::Rgb::from(&color);
+        let steps = (end.x - begin.x).abs().max((end.y - begin.y).abs()).ceil() as u32;
+        for i in 0..=steps {
+            let point = begin.lerp(end, i as f32 / steps.max(1) as f32);
+            // set_pixel takes negative coordinates as 0
+            if point.x >= 0.0 && point.y >= 0.0 {
+                self.set_pixel(&point, &color);
             }
+        }
+    }
 
-            for i in 0..3 {
-                newtri.set_vertex(i, v[i].truncate());
+    /**
+     * turn the screen space barycentric coordinates of a fragment
+     * into the weights for interpolating varyings.
+     *
+     * barycentric coordinates on screen are not the same as the ones in view space,
+     * because of the perspective division. but attr/w and 1/w are linear on screen,
+     * so the view space weights are the screen ones divided by w, then normalized.
+     */
+    fn varying_weights(&self, barycentric: Vec3, w_reciprocal: Vec3) -> Vec3 {
+        match self.interpolation {
+            Interpolation::Affine => barycentric,
+            Interpolation::PerspectiveCorrect => {
+                let weights = barycentric * w_reciprocal;
+                weights / weights.dot(Vec3::ONE)
             }
+        }
+    }
 
-            for i in 0..3 {
-                newtri.set_normal(i, n[i].truncate());
-            }
+    fn rasterize_triangle(&mut self, v: [VertexOut; 3]) {
+        let screen_pos = [v[0].screen_pos, v[1].screen_pos, v[2].screen_pos];
 
-            newtri.set_color(0, utils::triangle::Rgb(148, 121, 92));
-            newtri.set_color(1, utils::triangle::Rgb(148, 121, 92));
-            newtri.set_color(2, utils::triangle::Rgb(148, 121, 92));
+        let start = self.stats.map(|_| Instant::now());
+        let shade_before = self.stats.map(|stats| stats.shade);
+        self.record(|stats| stats.triangles += 1);
 
-            self.rasterize_triangle(newtri, *viewspace_pos);
+        if self.render_mode == RenderMode::Wireframe {
+            for i in 0..3 {
+                self.draw_line(screen_pos[i], screen_pos[(i + 1) % 3], self.edge_color);
+            }
+            self.record_time(start, |stats, t| stats.raster += t);
+            return;
         }
-    }
 
-    fn rasterize_triangle(&mut self, t: utils::triangle::Triangle, view_pos: [Vec3; 3]) {
+        // the distance from a pixel to the edge opposite to vertex i is
+        // its barycentric coordinate i times the height of the triangle on that edge
+        let double_area = (screen_pos[1] - screen_pos[0])
+            .truncate()
+            .perp_dot((screen_pos[2] - screen_pos[0]).truncate())
+            .abs();
+        let heights = Vec3::from([0, 1, 2].map(|i| {
+            let edge = screen_pos[(i + 2) % 3] - screen_pos[(i + 1) % 3];
+            double_area / edge.truncate().length().max(f32::EPSILON)
+        }));
+
         // get the bounding box of the triangle
         let mut max_x = 0.0f32;
         let mut min_x = self.width as f32;
         let mut max_y = 0.0f32;
         let mut min_y = self.height as f32;
 
-        for vertex in t.v {
+        for vertex in screen_pos {
             max_x = max_x.max(vertex.x);
             min_x = min_x.min(vertex.x);
             max_y = max_y.max(vertex.y);
             min_y = min_y.min(vertex.y);
         }
         let max_x = (max_x as u32 + 1).min(self.width);
-        let min_x = (min_x as u32).max(0);
+        let min_x = min_x as u32;
         let max_y = (max_y as u32 + 1).min(self.height);
-        let min_y = (min_y as u32).max(0);
+        let min_y = min_y as u32;
+
+        let v_z = Vec3::new(v[0].screen_pos.z, v[1].screen_pos.z, v[2].screen_pos.z);
+        let v_w_reciprocal = Vec3::new(v[0].w_reciprocal, v[1].w_reciprocal, v[2].w_reciprocal);
 
         for x in min_x..max_x {
             for y in min_y..max_y {
                 let (xc, yc) = (x as f32 + 0.5, y as f32 + 0.5);
-                if !inside_triangle(xc, yc, &t) {
+                if !inside_triangle(xc, yc, &screen_pos) {
                     continue;
                 }
 
-                let [alpha, beta, gama] = compute_barcentric_2d(xc, yc, t.v);
-                let v = t.to_vec4();
-                let barcentric = Vec3::new(alpha, beta, gama);
-                let v_z = Vec3::new(v[0].z, v[1].z, v[2].z);
-                let v_w = Vec3::new(v[0].w, v[1].w, v[2].w);
-                let mut z_interpolated = (barcentric * v_z / v_w).dot(Vec3::ONE);
-                let w_reciprocal = 1. / (barcentric / v_w).dot(Vec3::ONE);
-                z_interpolated *= w_reciprocal;
+                let barcentric = Vec3::from(compute_barcentric_2d(xc, yc, screen_pos));
+
+                // z after the perspective division is linear on screen,
+                // so the depth is interpolated by screen space barycentric coordinates directly
+                let z_interpolated = barcentric.dot(v_z);
 
                 if z_interpolated < 0. {
                     continue;
@@ -194,35 +633,71 @@ impl Rasterizer {
                 if z_interpolated > self.depth_buf[buf_ind] {
                     continue;
                 }
+                self.record(|stats| stats.fragments += 1);
 
-                let interpolated_color = alpha * Vec3::from(t.color[0])
-                    + beta * Vec3::from(t.color[1])
-                    + gama * Vec3::from(t.color[2]);
+                let [alpha, beta, gama] =
+                    self.varying_weights(barcentric, v_w_reciprocal).to_array();
+
+                let interpolated_color = alpha * v[0].color + beta * v[1].color + gama * v[2].color;
 
                 let interpolated_normal =
-                    alpha * t.normal[0] + beta * t.normal[1] + gama * t.normal[2];
+                    alpha * v[0].normal + beta * v[1].normal + gama * v[2].normal;
 
                 let interpolated_texcoords =
-                    alpha * t.tex_coords[0] + beta * t.tex_coords[1] + gama * t.tex_coords[2];
+                    alpha * v[0].tex_coords + beta * v[1].tex_coords + gama * v[2].tex_coords;
 
                 let interpolated_shadingcoords =
-                    alpha * view_pos[0] + beta * view_pos[1] + gama * view_pos[2];
+                    alpha * v[0].view_pos + beta * v[1].view_pos + gama * v[2].view_pos;
+
+                let edge = match self.render_mode {
+                    RenderMode::Filled => 0.0,
+                    _ => {
+                        // fully covered within half of the width, and fading out in the next pixel
+                        let distance = (barcentric * heights).min_element();
+                        (1.0 - (distance - self.edge_width / 2.0)).clamp(0.0, 1.0)
+                    }
+                };
+
+                if self.shading == Shading::Forward {
+                    let shade_start = self.stats.map(|_| Instant::now());
+                    let pixel_color = self.fragment_stage(
+                        self.material,
+                        interpolated_shadingcoords,
+                        interpolated_color,
+                        interpolated_normal,
+                        interpolated_texcoords,
+                        1.0,
+                    );
+                    self.record_time(shade_start, |stats, t| {
+                        stats.shade += t;
+                        stats.shaded += 1;
+                    });
+                    let pixel_color = self.edge_stage(pixel_color, edge);
 
-                let pixel_color = self.fragment_shader.unwrap()(&FragmentShaderPayload {
+                    let pixel_color = utils::triangle::Rgb::from(&pixel_color);
+
+                    // z_interpolated < self.depth_buf[buf_ind]
+                    self.set_pixel(&Vec3::new(xc, yc, z_interpolated), &pixel_color);
+                }
+                self.depth_buf[buf_ind] = z_interpolated;
+                self.g_buf.samples[buf_ind] = Some(GBufferSample {
                     view_pos: interpolated_shadingcoords,
-                    color: interpolated_color,
                     normal: interpolated_normal,
                     tex_coords: interpolated_texcoords,
-                    texture: &self.texture,
+                    color: interpolated_color,
+                    material: self.material,
+                    edge,
+                    barycentric: Vec3::new(alpha, beta, gama),
                 });
-
-                let pixel_color = utils::triangle::Rgb::from(&pixel_color);
-
-                // z_interpolated < self.depth_buf[buf_ind]
-                self.set_pixel(&Vec3::new(xc, yc, z_interpolated), &pixel_color);
-                self.depth_buf[buf_ind] = z_interpolated;
             }
         }
+
+        // the shading inside is counted by itself
+        let shade = match (self.stats, shade_before) {
+            (Some(stats), Some(before)) => stats.shade - before,
+            _ => std::time::Duration::ZERO,
+        };
+        self.record_time(start, |stats, t| stats.raster += t.saturating_sub(shade));
         // TODO: From your HW3, get the triangle rasterization code.
         // TODO: Inside your rasterization loop:
         //    * v[i].w() is the vertex view space depth value z.
@@ -244,27 +719,45 @@ impl Rasterizer {
         // Use: Instead of passing the triangle's color directly to the frame buffer, pass the color to the shaders first to get the final color;
         // Use: auto pixel_color = fragment_shader(payload);
     }
+
+    fn get_next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
 }
 
 fn to_vec4(v3: &Vec3, w: f32) -> Vec4 {
     Vec4::new(v3.x, v3.y, v3.z, w)
 }
 
-fn inside_triangle(xc: f32, yc: f32, t: &utils::triangle::Triangle) -> bool {
-    let mut v = vec![];
-    for vec in t.v {
-        v.push(Vec3::new(vec.x, vec.y, 1.));
-    }
-
-    let f0 = v[1].cross(v[0]);
-    let f1 = v[2].cross(v[1]);
-    let f2 = v[0].cross(v[2]);
-
-    let p = Vec3::new(xc, yc, 1.);
+/**
+ * whether the pixel center is inside the triangle, by the top-left fill rule:
+ * a center right on an edge is only inside for the top and left edges,
+ * so the pixels on an edge shared by 2 triangles are drawn exactly once.
+ */
+fn inside_triangle(xc: f32, yc: f32, screen_pos: &[Vec3; 3]) -> bool {
+    let p = Vec2::new(xc, yc);
+    let v = screen_pos.map(|v| v.truncate());
+    // counter clockwise, so the inside is on the left of every edge
+    let v = match (v[1] - v[0]).perp_dot(v[2] - v[0]) {
+        area if area > 0. => v,
+        area if area < 0. => [v[0], v[2], v[1]],
+        _ => return false,
+    };
 
-    return (p.dot(f0) * f0.dot(v[2]) > 0.)
-        && (p.dot(f1) * f1.dot(v[0]) > 0.)
-        && (p.dot(f2) * f2.dot(v[1]) > 0.);
+    (0..3).all(|i| {
+        let (a, b) = (v[i], v[(i + 1) % 3]);
+        // computed from the ends in the same order whichever way the edge goes,
+        // so the 2 triangles sharing it get exactly opposite values
+        let e = match (a.x, a.y) < (b.x, b.y) {
+            true => (b - a).perp_dot(p - a),
+            false => -(a - b).perp_dot(p - b),
+        };
+        // y goes up on screen: left edges go down, top edges go to the left
+        let edge = b - a;
+        let top_left = edge.y < 0. || (edge.y == 0. && edge.x < 0.);
+        e > 0. || (e == 0. && top_left)
+    })
 }
 
 fn compute_barcentric_2d(x: f32, y: f32, v: [Vec3; 3]) -> [f32; 3] {
@@ -279,3 +772,314 @@ fn compute_barcentric_2d(x: f32, y: f32, v: [Vec3; 3]) -> [f32; 3] {
             - v[1].x * v[0].y);
     [c1, c2, c3]
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mesh::Mesh;
+
+    fn quad() -> Mesh {
+        let vertex = |x: f32, y: f32| Vertex {
+            position: Vec3::new(x, y, 0.0),
+            normal: Vec3::Z,
+            tex_coords: Vec2::new((x + 1.0) / 2.0, (y + 1.0) / 2.0),
+        };
+        Mesh {
+            vertices: vec![
+                vertex(-1.0, -1.0),
+                vertex(1.0, -1.0),
+                vertex(1.0, 1.0),
+                vertex(-1.0, 1.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        }
+    }
+
+    fn rasterizer() -> Rasterizer {
+        let mut r = Rasterizer::new(64, 64);
+        r.set_model(crate::get_model_matrix(30.0, 2.0));
+        r.set_view(crate::get_view_matrix(Vec3::new(0.0, 0.0, 10.0)));
+        r.set_projection(crate::get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+        r.set_vertex_shader(crate::vertex_shader);
+        r.set_fragment_shader(crate::normal_fragment_shader);
+        r
+    }
+
+    #[test]
+    fn test_draw_indexed_matches_triangle_list() {
+        let mesh = quad();
+
+        let mut by_list = rasterizer();
+        by_list.draw_triangle(&mesh.to_triangles());
+
+        let mut by_index = rasterizer();
+        let vert_id = by_index.load_vertices(mesh.vertices.clone());
+        let ind_id = by_index.load_indices(mesh.indices.clone());
+        by_index.draw(vert_id, ind_id, Primitive::Triangle);
+
+        assert!(by_index
+            .data()
+            .iter()
+            .any(|c| *c != utils::triangle::Rgb(0, 0, 0)));
+        assert_eq!(by_list.data(), by_index.data());
+        assert_eq!(by_list.depth_buf, by_index.depth_buf);
+    }
+
+    #[test]
+    fn test_fill_rule() {
+        // a square split on its diagonal, which goes right through the pixel centers
+        let (a, b, c, d) = (
+            Vec3::new(0., 0., 0.),
+            Vec3::new(8., 0., 0.),
+            Vec3::new(8., 8., 0.),
+            Vec3::new(0., 8., 0.),
+        );
+        for triangles in [[[a, b, c], [a, c, d]], [[c, b, a], [d, c, a]]] {
+            for x in 0..8 {
+                for y in 0..8 {
+                    let (xc, yc) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let count = triangles
+                        .iter()
+                        .filter(|t| inside_triangle(xc, yc, t))
+                        .count();
+                    assert_eq!(count, 1, "pixel ({}, {})", x, y);
+                }
+            }
+        }
+        // a degenerate triangle covers nothing
+        assert!(!inside_triangle(0.5, 0.5, &[a, c, a]));
+    }
+
+    #[test]
+    fn test_perspective_correct_weights() {
+        let mut r = rasterizer();
+        r.set_model(Mat4::IDENTITY);
+        let m = r.draw_matrices();
+
+        // a triangle going away from the eye
+        let p = [
+            Vec3::new(-1.0, -1.0, 5.0),
+            Vec3::new(1.0, -1.0, -20.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let v = p.map(|p| r.vertex_stage(&m, p, Vec3::Z, Vec2::ZERO));
+        let screen_pos = v.map(|v| v.screen_pos);
+        let w_reciprocal = Vec3::new(v[0].w_reciprocal, v[1].w_reciprocal, v[2].w_reciprocal);
+
+        // the middle point of p0 and p1 is not at the middle of them on screen,
+        // but the interpolation weights of it should be (0.5, 0.5, 0)
+        let mid = r.vertex_stage(&m, (p[0] + p[1]) / 2.0, Vec3::Z, Vec2::ZERO);
+        let barcentric = Vec3::from(compute_barcentric_2d(
+            mid.screen_pos.x,
+            mid.screen_pos.y,
+            screen_pos,
+        ));
+        assert!(!barcentric.abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 0.01));
+
+        let weights = r.varying_weights(barcentric, w_reciprocal);
+        assert!(weights.abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 0.0001));
+
+        r.set_interpolation(Interpolation::Affine);
+        assert_eq!(r.varying_weights(barcentric, w_reciprocal), barcentric);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mesh = quad();
+        let mut r = rasterizer();
+        assert!(r.take_stats().is_none());
+
+        r.set_stats_enabled(true);
+        r.draw_triangle(&mesh.to_triangles());
+        let stats = r.take_stats().unwrap();
+        assert_eq!(stats.triangles, 2);
+        assert!(stats.covered > 0);
+        // the quad covers every pixel once
+        assert_eq!(stats.fragments, stats.covered);
+        assert_eq!(stats.shaded, stats.fragments);
+        assert_eq!(stats.overdraw(), 1.0);
+
+        // drawn again over itself, every fragment passes the depth test again
+        r.set_shading(Shading::Deferred);
+        r.draw_triangle(&mesh.to_triangles());
+        r.draw_triangle(&mesh.to_triangles());
+        r.resolve();
+        let stats = r.take_stats().unwrap();
+        assert_eq!(stats.overdraw(), 2.0);
+        assert_eq!(stats.shaded, stats.covered);
+
+        assert_eq!(r.take_stats().unwrap().triangles, 0);
+    }
+
+    #[test]
+    fn test_render_modes() {
+        let mesh = quad();
+        let draw = |render_mode: RenderMode, shading: Shading| {
+            let mut r = rasterizer();
+            r.set_render_mode(render_mode);
+            r.set_shading(shading);
+            r.draw_triangle(&mesh.to_triangles());
+            r.resolve();
+            r
+        };
+        let black = utils::triangle::Rgb(0, 0, 0);
+        let white = utils::triangle::Rgb(255, 255, 255);
+
+        let filled = draw(RenderMode::Filled, Shading::Forward);
+        let covered = filled.data().iter().filter(|c| **c != black).count();
+
+        // edges replace the colors near them, the inner parts stay the same
+        let edges = draw(RenderMode::SolidWithEdges, Shading::Forward);
+        let changed = (filled.data().iter().zip(edges.data().iter()))
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(changed > 0 && changed < covered / 2);
+        assert!(edges.data().contains(&white));
+        let deferred = draw(RenderMode::SolidWithEdges, Shading::Deferred);
+        assert_eq!(edges.data(), deferred.data());
+
+        // only gray levels between the background and the edge color
+        let hidden_line = draw(RenderMode::HiddenLine, Shading::Forward);
+        assert!(hidden_line.data().iter().all(|c| c.0 == c.1 && c.1 == c.2));
+        let lit = hidden_line.data().iter().filter(|c| **c != black).count();
+        assert!(lit > 0 && lit < covered / 2);
+        assert_eq!(hidden_line.depth_buf, filled.depth_buf);
+
+        // no depth test or fill
+        let wireframe = draw(RenderMode::Wireframe, Shading::Forward);
+        assert!(wireframe.data().contains(&white));
+        assert!(wireframe.depth_buf.iter().all(|d| *d == f32::INFINITY));
+    }
+
+    #[test]
+    fn test_deferred_matches_forward() {
+        let mesh = quad();
+        let draw = |r: &mut Rasterizer| {
+            let vert_id = r.load_vertices(mesh.vertices.clone());
+            let ind_id = r.load_indices(mesh.indices.clone());
+            let material = r.load_material(Material {
+                fragment_shader: crate::phong_fragment_shader,
+                texture: None,
+                pbr: PbrParams::default(),
+            });
+            r.draw(vert_id, ind_id, Primitive::Triangle);
+            // a smaller quad in front of the first one, drawn with another material
+            r.set_model(crate::get_model_matrix(30.0, 1.0) * Mat4::from_translation(Vec3::Z));
+            r.set_material(Some(material));
+            r.draw(vert_id, ind_id, Primitive::Triangle);
+            r.resolve();
+        };
+
+        let mut forward = rasterizer();
+        draw(&mut forward);
+
+        let mut deferred = rasterizer();
+        deferred.set_shading(Shading::Deferred);
+        draw(&mut deferred);
+
+        assert_eq!(forward.data(), deferred.data());
+        assert!(deferred
+            .g_buffer()
+            .samples
+            .iter()
+            .any(|s| matches!(s, Some(s) if s.material != 0)));
+    }
+
+    #[test]
+    fn test_lights() {
+        let mesh = quad();
+        let draw = |shading: Shading, lights: Vec<Light>| {
+            let mut r = rasterizer();
+            r.set_fragment_shader(crate::phong_fragment_shader);
+            r.set_shading(shading);
+            r.set_lights(lights);
+            r.draw_triangle(&mesh.to_triangles());
+            r.resolve();
+            r
+        };
+        let brightness = |r: &Rasterizer| -> u64 {
+            r.data()
+                .iter()
+                .map(|c| c.0 as u64 + c.1 as u64 + c.2 as u64)
+                .sum()
+        };
+        let front = |z: f32| Light {
+            position: Vec3::new(0.0, 0.0, z),
+            intensity: Vec3::ONE * 500.,
+        };
+
+        // a new rasterizer is lit by the default lights
+        let mut unset = rasterizer();
+        unset.set_fragment_shader(crate::phong_fragment_shader);
+        unset.draw_triangle(&mesh.to_triangles());
+        let default = draw(Shading::Forward, crate::default_lights());
+        assert_eq!(default.data(), unset.data());
+
+        // every light of the list is added in the resolve pass
+        let none = draw(Shading::Deferred, vec![]);
+        let one = draw(Shading::Deferred, vec![front(10.0)]);
+        let eight: Vec<Light> = (0..8).map(|i| front(10.0 + i as f32)).collect();
+        let many = draw(Shading::Deferred, eight.clone());
+        assert!(brightness(&none) < brightness(&one));
+        assert!(brightness(&one) < brightness(&many));
+        let forward = draw(Shading::Forward, eight);
+        assert_eq!(forward.data(), many.data());
+    }
+
+    #[test]
+    fn test_shadowed_lights() {
+        let mesh = quad();
+        // a light in front of the quad, and a bigger quad between them
+        let light = Light {
+            position: Vec3::new(0.0, 0.0, 20.0),
+            intensity: Vec3::ONE * 500.,
+        };
+        let mut shadow_map = ShadowMap::new(light, Vec3::ZERO, 64, 60.0);
+        let mut shadow_r = shadow_map.rasterizer();
+        shadow_r.set_model(
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0)) * Mat4::from_scale(Vec3::splat(4.0)),
+        );
+        shadow_r.draw_triangle(&mesh.to_triangles());
+        shadow_map.capture(&shadow_r);
+
+        let draw = |lights: Vec<Light>, shadow_maps: Vec<ShadowMap>| {
+            let mut r = rasterizer();
+            r.set_fragment_shader(crate::phong_fragment_shader);
+            let view = crate::get_view_matrix(Vec3::new(0.0, 0.0, 10.0));
+            let lights = lights
+                .into_iter()
+                .map(|l| Light {
+                    position: view.transform_point3(l.position),
+                    ..l
+                })
+                .collect();
+            r.set_lights(lights);
+            r.set_shadow_maps(shadow_maps);
+            r.draw_triangle(&mesh.to_triangles());
+            r.data().to_vec()
+        };
+        let brightness = |data: &[utils::triangle::Rgb]| -> u64 {
+            data.iter()
+                .map(|c| c.0 as u64 + c.1 as u64 + c.2 as u64)
+                .sum()
+        };
+
+        // only the ambient light is left in the shadow, as with a light of no intensity
+        let unlit = Light {
+            intensity: Vec3::ZERO,
+            ..light
+        };
+        let lit = draw(vec![light], vec![]);
+        let shadowed = draw(vec![light], vec![shadow_map.clone()]);
+        assert!(brightness(&shadowed) < brightness(&lit));
+        assert_eq!(shadowed, draw(vec![unlit], vec![]));
+
+        // the shadow map is only of the light at the same index
+        let other = Light {
+            position: Vec3::new(0.0, 0.0, 19.0),
+            ..light
+        };
+        let one_shadowed = draw(vec![light, other], vec![shadow_map]);
+        assert_eq!(one_shadowed, draw(vec![unlit, other], vec![]));
+    }
+}
@@ -1,6 +1,10 @@
 use anyhow::Result;
 use glam::{Vec2, Vec3};
 
+use crate::environment::Environment;
+use crate::shadow::ShadowMap;
+use crate::Light;
+
 pub struct Texture {
     pub width: u32,
     pub height: u32,
@@ -16,6 +20,26 @@ impl Texture {
         Ok(Self { width, height, img })
     }
 
+    /**
+     * a procedural checkerboard of `cells` x `cells` squares, `size` x `size` texels,
+     * useful for checking how texture coordinates are interpolated.
+     */
+    pub fn checkerboard(size: u32, cells: u32) -> Self {
+        let cell_size = (size / cells).max(1);
+        let img = image::RgbImage::from_fn(size, size, |x, y| {
+            if (x / cell_size + y / cell_size) & 1 == 0 {
+                image::Rgb([255, 255, 255])
+            } else {
+                image::Rgb([32, 32, 32])
+            }
+        });
+        Self {
+            width: size,
+            height: size,
+            img,
+        }
+    }
+
     /**
      * from the left bottom corner
      * - u: horizontal
@@ -74,10 +98,118 @@ pub struct FragmentShaderPayload<'a> {
     pub normal: Vec3,
     pub tex_coords: Vec2,
     pub texture: &'a Option<Texture>,
+    // the lights of the rasterizer, the same for every fragment
+    pub lights: &'a [Light],
+    // shadow_maps[i] is the shadow map of lights[i], lights after the last one cast no shadows
+    pub shadow_maps: &'a [ShadowMap],
+    // how much the ambient light reaches the point, 1 without ssao
+    pub ambient_occlusion: f32,
+    // only read by the physically based shader
+    pub pbr: &'a PbrParams,
+    // image based lighting, replacing the constant ambient light
+    pub environment: Option<&'a Environment>,
+}
+
+impl<'a> FragmentShaderPayload<'a> {
+    /**
+     * how much a point is lit by the i-th light, from 0 (fully in shadow) to 1,
+     * always 1 for a light without a shadow map.
+     */
+    pub fn light_visibility(&self, i: usize, point: Vec3) -> f32 {
+        match self.shadow_maps.get(i) {
+            Some(shadow_map) => shadow_map.visibility(point),
+            None => 1.0,
+        }
+    }
 }
 
 pub type FragmentShader = fn(&FragmentShaderPayload) -> Vec3;
 
+/**
+ * a fragment shader together with the texture it samples,
+ * fragments in the G-buffer refer to it by id.
+ */
+pub struct Material {
+    pub fragment_shader: FragmentShader,
+    pub texture: Option<Texture>,
+    pub pbr: PbrParams,
+}
+
+/**
+ * the metallic-roughness parameters of `pbr_fragment_shader`.
+ * a map, if any, is sampled at the texture coordinates and multiplies its values:
+ * - base_color_map: the rgb of the base color
+ * - metallic_roughness_map: roughness in the green channel, metallic in the blue one (as gltf)
+ */
+pub struct PbrParams {
+    // linear rgb, 0..1
+    pub base_color: Vec3,
+    // 0 for dielectrics, 1 for metals
+    pub metallic: f32,
+    // 0 for a mirror, 1 for fully rough
+    pub roughness: f32,
+    pub base_color_map: Option<Texture>,
+    pub metallic_roughness_map: Option<Texture>,
+}
+
+impl Default for PbrParams {
+    fn default() -> Self {
+        Self {
+            base_color: Vec3::ONE * 0.8,
+            metallic: 0.0,
+            roughness: 0.5,
+            base_color_map: None,
+            metallic_roughness_map: None,
+        }
+    }
+}
+
+impl PbrParams {
+    /**
+     * the base color, metallic and roughness at the texture coordinates
+     */
+    pub fn sample(&self, tex_coords: Vec2) -> (Vec3, f32, f32) {
+        let mut base_color = self.base_color;
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(map) = &self.base_color_map {
+            // color maps are stored in srgb, as images usually are
+            let c: Vec3 = map.get_color(tex_coords.x, tex_coords.y).into();
+            base_color *= Vec3::from((c / 255.0).to_array().map(srgb_to_linear));
+        }
+        // the metallic and roughness are data, already linear
+        if let Some(map) = &self.metallic_roughness_map {
+            let c: Vec3 = map.get_color(tex_coords.x, tex_coords.y).into();
+            roughness *= c.y / 255.0;
+            metallic *= c.z / 255.0;
+        }
+        (
+            base_color,
+            metallic.clamp(0.0, 1.0),
+            roughness.clamp(0.0, 1.0),
+        )
+    }
+}
+
+/**
+ * decode an srgb channel (0..1) to linear
+ */
+pub fn srgb_to_linear(c: f32) -> f32 {
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+/**
+ * encode a linear channel (0..1) to srgb
+ */
+pub fn linear_to_srgb(c: f32) -> f32 {
+    match c <= 0.0031308 {
+        true => c * 12.92,
+        false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
 pub struct VertexShaderPayload {
     pub position: Vec3,
 }
@@ -86,6 +218,31 @@ pub type VertexShader = fn(&VertexShaderPayload) -> Vec3;
 
 mod test {
 
+    #[test]
+    fn test_pbr_base_color_map() {
+        use glam::{Vec2, Vec3};
+
+        assert_eq!(super::srgb_to_linear(0.0), 0.0);
+        assert!((super::srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((super::srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        for c in [0.0, 0.002, 0.1, 0.5, 1.0] {
+            let round_trip = super::srgb_to_linear(super::linear_to_srgb(c));
+            assert!((round_trip - c).abs() < 1e-5, "{}", c);
+        }
+
+        // white and dark gray (32) texels, next to each other
+        let pbr = super::PbrParams {
+            base_color: Vec3::ONE,
+            base_color_map: Some(super::Texture::checkerboard(2, 2)),
+            ..Default::default()
+        };
+        let (white, _, _) = pbr.sample(Vec2::new(0.25, 0.75));
+        let (dark, _, _) = pbr.sample(Vec2::new(0.75, 0.75));
+        assert!(white.abs_diff_eq(Vec3::ONE, 1e-6));
+        // 32 / 255 in srgb is much darker in linear
+        assert!(dark.abs_diff_eq(Vec3::splat(0.0144), 1e-4));
+    }
+
     #[test]
     fn test_texture() {
         let texture = super::Texture::new("output.png").unwrap();
@@ -0,0 +1,182 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::rst::{Rasterizer, Shading};
+use crate::{get_projection_matrix, Light};
+
+/**
+ * the depth of the scene seen from a light,
+ * for telling whether a shading point is occluded from the light.
+ *
+ * it's rendered by the same rasterizer as the scene:
+ * draw the shadow casters with the rasterizer from `ShadowMap::rasterizer`,
+ * then `capture` its depth.
+ */
+#[derive(Clone)]
+pub struct ShadowMap {
+    // the position is in world space
+    pub light: Light,
+    // a point is in shadow only if it's farther than the depth in the map by more than bias,
+    // it keeps surfaces from shadowing themselves (shadow acne).
+    pub bias: f32,
+    // percentage closer filtering: average the depth tests of (2r+1)^2 texels around the point
+    pub pcf_radius: u32,
+
+    size: u32,
+    fov: f32,
+    light_view: Mat4,
+    // from the camera view space to the light view space
+    camera_to_light: Mat4,
+    // linear depth (-z in the light view space), top row first, infinity if nothing is drawn
+    depth: Vec<f32>,
+}
+
+impl ShadowMap {
+    /**
+     * a square shadow map of a light looking at the target,
+     * covering a cone of fov degrees.
+     */
+    pub fn new(light: Light, target: Vec3, size: u32, fov: f32) -> Self {
+        let up = match (target - light.position).cross(Vec3::Y).length_squared() > f32::EPSILON {
+            true => Vec3::Y,
+            false => Vec3::Z,
+        };
+        let light_view = Mat4::look_at_rh(light.position, target, up);
+        Self {
+            light,
+            bias: 0.05,
+            pcf_radius: 1,
+            size,
+            fov,
+            light_view,
+            camera_to_light: light_view,
+            depth: vec![f32::INFINITY; (size * size) as usize],
+        }
+    }
+
+    /**
+     * a rasterizer looking from the light, only filling the depth.
+     * meshes drawn on it cast shadows after `capture`.
+     */
+    pub fn rasterizer(&self) -> Rasterizer {
+        let mut r = Rasterizer::new(self.size, self.size);
+        r.set_view(self.light_view);
+        r.set_projection(self.projection());
+        // deferred shading without `resolve` never runs a fragment shader
+        r.set_shading(Shading::Deferred);
+        r
+    }
+
+    /**
+     * copy the depth drawn on a rasterizer from `ShadowMap::rasterizer`
+     */
+    pub fn capture(&mut self, r: &Rasterizer) {
+        for (d, s) in self.depth.iter_mut().zip(r.g_buffer().samples.iter()) {
+            *d = match s {
+                Some(s) => -s.view_pos.z,
+                None => f32::INFINITY,
+            };
+        }
+    }
+
+    /**
+     * the view matrix of the camera,
+     * shading points passed to `visibility` are in its view space.
+     */
+    pub fn set_camera_view(&mut self, view: Mat4) {
+        self.camera_to_light = self.light_view * view.inverse();
+    }
+
+    /**
+     * the position of the light in the camera view space
+     */
+    pub fn light_position(&self) -> Vec3 {
+        self.camera_to_light
+            .inverse()
+            .transform_point3(self.light_view.transform_point3(self.light.position))
+    }
+
+    /**
+     * how much a point (in the camera view space) is lit by the light,
+     * from 0 (fully in shadow) to 1 (fully lit).
+     * points outside of the shadow map are lit.
+     */
+    pub fn visibility(&self, view_pos: Vec3) -> f32 {
+        let light_pos = self.camera_to_light.transform_point3(view_pos);
+        let depth = -light_pos.z;
+
+        let clip = self.projection() * Vec4::new(light_pos.x, light_pos.y, light_pos.z, 1.0);
+        let ndc = clip.truncate() / clip.w;
+        let x = 0.5 * self.size as f32 * (ndc.x + 1.0);
+        let y = 0.5 * self.size as f32 * (ndc.y + 1.0);
+
+        let r = self.pcf_radius as i32;
+        let (mut lit, mut total) = (0, 0);
+        for dy in -r..=r {
+            for dx in -r..=r {
+                total += 1;
+                let (sx, sy) = (x.floor() as i32 + dx, y.floor() as i32 + dy);
+                if sx < 0 || sy < 0 || sx >= self.size as i32 || sy >= self.size as i32 {
+                    lit += 1;
+                    continue;
+                }
+                let ind = ((self.size as i32 - 1 - sy) * self.size as i32 + sx) as usize;
+                if depth - self.bias <= self.depth[ind] {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / total as f32
+    }
+
+    fn projection(&self) -> Mat4 {
+        get_projection_matrix(self.fov, 1.0, 0.1, 50.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mesh::Mesh;
+    use crate::rst::Primitive;
+
+    #[test]
+    fn test_visibility() {
+        let light = Light {
+            position: Vec3::new(0.0, 10.0, 0.0),
+            intensity: Vec3::ONE * 100.0,
+        };
+        let mut shadow_map = ShadowMap::new(light, Vec3::ZERO, 128, 60.0);
+
+        // a small occluder at y = 5, right under the light
+        let occluder = Mesh::floor(1.0);
+        let mut r = shadow_map.rasterizer();
+        let vert_id = r.load_vertices(occluder.vertices);
+        let ind_id = r.load_indices(occluder.indices);
+        r.set_model(Mat4::from_translation(Vec3::new(0.0, 5.0, 0.0)));
+        r.draw(vert_id, ind_id, Primitive::Triangle);
+        shadow_map.capture(&r);
+
+        let camera_view = crate::get_view_matrix(Vec3::new(0.0, 2.0, 12.0));
+        shadow_map.set_camera_view(camera_view);
+        let visibility = |p: Vec3| shadow_map.visibility(camera_view.transform_point3(p));
+
+        // on the ground, under the occluder and away from it.
+        // the diagonal shared by the 2 triangles of the quad leaks no light
+        assert_eq!(visibility(Vec3::new(0.5, 0.0, 0.2)), 0.0);
+        for t in [-0.6, -0.3, 0.0, 0.3, 0.6] {
+            assert_eq!(visibility(Vec3::new(t, 0.0, t)), 0.0);
+            assert_eq!(visibility(Vec3::new(t, 0.0, -t)), 0.0);
+        }
+        assert_eq!(visibility(Vec3::new(4.0, 0.0, 0.0)), 1.0);
+        // the occluder doesn't shadow itself
+        assert_eq!(visibility(Vec3::new(0.5, 5.0, 0.2)), 1.0);
+        assert_eq!(visibility(Vec3::new(0.3, 5.0, 0.3)), 1.0);
+        // on the edge of the shadow, pcf gives a partial shadow
+        let edge = visibility(Vec3::new(2.0, 0.0, 0.0));
+        assert!(edge > 0.0 && edge < 1.0);
+
+        assert!(shadow_map
+            .light_position()
+            .abs_diff_eq(camera_view.transform_point3(light.position), 0.001));
+    }
+}
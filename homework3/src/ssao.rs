@@ -0,0 +1,224 @@
+use glam::{Mat3, Mat4, Vec3, Vec4};
+
+use crate::gbuffer::GBuffer;
+
+// the noise vectors tile the screen every NOISE_SIZE pixels,
+// a blur of the same size averages the noise out.
+const NOISE_SIZE: u32 = 4;
+
+/**
+ * screen space ambient occlusion.
+ *
+ * for every pixel, points are sampled in the hemisphere around its normal,
+ * a point hidden behind the surface in the G-buffer occludes the pixel.
+ * the hemisphere is rotated by a tiled noise to trade banding for noise,
+ * and the noise is blurred out at last.
+ */
+pub struct Ssao {
+    // the radius of the sampling hemisphere, in view space
+    pub radius: f32,
+    // keep a surface from occluding itself
+    pub bias: f32,
+    pub blur_radius: u32,
+    // in the tangent space, z is the normal
+    kernel: Vec<Vec3>,
+    noise: Vec<Vec3>,
+}
+
+impl Ssao {
+    pub fn new(radius: f32, samples: u32) -> Self {
+        let kernel = (0..samples)
+            .map(|i| {
+                let v = Vec3::new(
+                    random(i * 3) * 2.0 - 1.0,
+                    random(i * 3 + 1) * 2.0 - 1.0,
+                    random(i * 3 + 2),
+                )
+                .normalize_or_zero();
+                // more samples close to the center, they matter more
+                let scale = (i + 1) as f32 / samples as f32;
+                v * (0.1 + 0.9 * scale * scale)
+            })
+            .collect();
+        let noise = (0..NOISE_SIZE * NOISE_SIZE)
+            .map(|i| {
+                let seed = samples * 3 + i * 2;
+                Vec3::new(random(seed) * 2.0 - 1.0, random(seed + 1) * 2.0 - 1.0, 0.0)
+            })
+            .collect();
+        Self {
+            radius,
+            bias: 0.025,
+            blur_radius: NOISE_SIZE / 2,
+            kernel,
+            noise,
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.kernel.len()
+    }
+
+    /**
+     * the ambient occlusion of every pixel, laid out the same as the G-buffer.
+     * 1 for fully open, 0 for fully occluded, pixels not covered are 1.
+     * @projection: the projection matrix the G-buffer is drawn with
+     */
+    pub fn compute(&self, g_buf: &GBuffer, projection: Mat4) -> Vec<f32> {
+        let (width, height) = (g_buf.width, g_buf.height);
+        let mut occlusion = vec![1.0; g_buf.samples.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                let ind = ((height - 1 - y) * width + x) as usize;
+                let sample = match g_buf.samples[ind] {
+                    Some(s) => s,
+                    None => continue,
+                };
+                let (p, n) = (sample.view_pos, sample.normal.normalize_or_zero());
+
+                let r = self.noise[((y % NOISE_SIZE) * NOISE_SIZE + x % NOISE_SIZE) as usize];
+                let tangent = (r - n * r.dot(n)).normalize_or_zero();
+                let tangent = match tangent == Vec3::ZERO {
+                    true => n.any_orthonormal_vector(),
+                    false => tangent,
+                };
+                let tbn = Mat3::from_cols(tangent, n.cross(tangent), n);
+
+                let mut occluded = 0.0;
+                for k in &self.kernel {
+                    let sp = p + tbn * *k * self.radius;
+                    let scene_z = match self.scene_at(g_buf, projection, sp) {
+                        Some(z) => z,
+                        None => continue,
+                    };
+                    if scene_z >= sp.z + self.bias {
+                        // surfaces far in front of the pixel don't occlude it
+                        let range = (self.radius / (p.z - scene_z).abs()).min(1.0);
+                        occluded += range * range * (3.0 - 2.0 * range);
+                    }
+                }
+                occlusion[ind] = 1.0 - occluded / self.kernel.len() as f32;
+            }
+        }
+
+        self.blur(g_buf, &occlusion)
+    }
+
+    /**
+     * the view space z of the visible surface at the projection of a view space point
+     */
+    fn scene_at(&self, g_buf: &GBuffer, projection: Mat4, point: Vec3) -> Option<f32> {
+        let clip = projection * Vec4::new(point.x, point.y, point.z, 1.0);
+        let ndc = clip.truncate() / clip.w;
+        let x = 0.5 * g_buf.width as f32 * (ndc.x + 1.0);
+        let y = 0.5 * g_buf.height as f32 * (ndc.y + 1.0);
+        if !(0.0..g_buf.width as f32).contains(&x) || !(0.0..g_buf.height as f32).contains(&y) {
+            return None;
+        }
+        let ind = ((g_buf.height - 1 - y as u32) * g_buf.width + x as u32) as usize;
+        g_buf.samples[ind].map(|s| s.view_pos.z)
+    }
+
+    // box blur over the covered pixels only, so the background doesn't leak in
+    fn blur(&self, g_buf: &GBuffer, occlusion: &[f32]) -> Vec<f32> {
+        let (width, height) = (g_buf.width as i32, g_buf.height as i32);
+        let r = self.blur_radius as i32;
+        let mut res = occlusion.to_vec();
+        for y in 0..height {
+            for x in 0..width {
+                let ind = ((height - 1 - y) * width + x) as usize;
+                if g_buf.samples[ind].is_none() {
+                    continue;
+                }
+                let (mut sum, mut count) = (0.0, 0);
+                for by in (y - r).max(0)..(y + r + 1).min(height) {
+                    for bx in (x - r).max(0)..(x + r + 1).min(width) {
+                        let b_ind = ((height - 1 - by) * width + bx) as usize;
+                        if g_buf.samples[b_ind].is_some() {
+                            sum += occlusion[b_ind];
+                            count += 1;
+                        }
+                    }
+                }
+                res[ind] = sum / count as f32;
+            }
+        }
+        res
+    }
+}
+
+// a hash of i in 0..1, the kernel and the noise are the same every run
+fn random(i: u32) -> f32 {
+    let mut h = i.wrapping_mul(0x9e37_79b9) ^ 0x85eb_ca6b;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mesh::Mesh;
+    use crate::rst::{Primitive, Rasterizer, Shading};
+
+    // a floor and a wall behind it, meeting at z = -2
+    fn corner() -> Rasterizer {
+        let mut r = Rasterizer::new(64, 64);
+        r.set_view(crate::get_view_matrix(Vec3::new(0.0, 2.0, 6.0)));
+        r.set_projection(crate::get_projection_matrix(45.0, 1.0, 0.1, 50.0));
+        r.set_shading(Shading::Deferred);
+
+        let plane = Mesh::floor(2.0);
+        let vert_id = r.load_vertices(plane.vertices);
+        let ind_id = r.load_indices(plane.indices);
+        r.set_model(Mat4::IDENTITY);
+        r.draw(vert_id, ind_id, Primitive::Triangle);
+        r.set_model(
+            Mat4::from_translation(Vec3::new(0.0, 2.0, -2.0))
+                * Mat4::from_rotation_x(std::f32::consts::FRAC_PI_2),
+        );
+        r.draw(vert_id, ind_id, Primitive::Triangle);
+        r
+    }
+
+    #[test]
+    fn test_corner() {
+        let r = corner();
+        let g_buf = r.g_buffer();
+        let projection = crate::get_projection_matrix(45.0, 1.0, 0.1, 50.0);
+        let ssao = Ssao::new(1.0, 32);
+        let ao = ssao.compute(g_buf, projection);
+
+        // the pixels of the corner, and of the open floor in front of it
+        let (mut corner, mut open) = (vec![], vec![]);
+        for (s, ao) in g_buf.samples.iter().zip(ao.iter()) {
+            if let Some(s) = s {
+                let world_z = s.view_pos.z + 6.0;
+                let world_y = s.view_pos.y + 2.0;
+                if world_y.abs() < 0.1 && (world_z + 2.0).abs() < 0.1 {
+                    corner.push(*ao);
+                } else if world_y.abs() < 0.01 && world_z > 0.0 {
+                    open.push(*ao);
+                }
+            }
+        }
+        assert!(!corner.is_empty() && !open.is_empty());
+        let average = |v: &Vec<f32>| v.iter().sum::<f32>() / v.len() as f32;
+        assert!(average(&corner) < 0.9, "{}", average(&corner));
+        assert!(average(&open) > 0.95, "{}", average(&open));
+        assert!(ao.iter().all(|ao| (0.0..=1.0).contains(ao)));
+    }
+
+    #[test]
+    fn test_kernel() {
+        let ssao = Ssao::new(1.0, 16);
+        assert_eq!(ssao.samples(), 16);
+        for k in &ssao.kernel {
+            assert!(k.z >= 0.0 && k.length() <= 1.0);
+        }
+    }
+}
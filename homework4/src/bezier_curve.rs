@@ -0,0 +1,440 @@
+use glam::Vec2;
+
+use crate::de_casteljau;
+
+/**
+ * a bezier curve of any degree on t in [0, 1],
+ * with the utilities built on de Casteljau's algorithm.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BezierCurve {
+    pub control_points: Vec<Vec2>,
+}
+
+// subdivision stops here even if the curve is not flat yet
+const MAX_DEPTH: u32 = 16;
+
+impl BezierCurve {
+    pub fn new(control_points: Vec<Vec2>) -> Self {
+        Self { control_points }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.control_points.len().saturating_sub(1)
+    }
+
+    pub fn evaluate(&self, t: f32) -> Vec2 {
+        if self.control_points.is_empty() {
+            return Vec2::ZERO;
+        }
+        de_casteljau(&self.control_points, t).last().unwrap()[0]
+    }
+
+    /**
+     * the curves on [0, t] and [t, 1], both on their own [0, 1].
+     * their control points are the first and last points of every de Casteljau level.
+     * an empty curve splits into two empty ones.
+     */
+    pub fn split(&self, t: f32) -> (BezierCurve, BezierCurve) {
+        if self.control_points.is_empty() {
+            return (BezierCurve::new(vec![]), BezierCurve::new(vec![]));
+        }
+        let levels = de_casteljau(&self.control_points, t);
+        let left = levels.iter().map(|l| l[0]).collect();
+        let right = levels.iter().rev().map(|l| *l.last().unwrap()).collect();
+        (BezierCurve::new(left), BezierCurve::new(right))
+    }
+
+    // the hodograph, a curve of one degree lower
+    pub fn derivative(&self) -> BezierCurve {
+        let n = self.degree() as f32;
+        let points: Vec<Vec2> = self
+            .control_points
+            .windows(2)
+            .map(|p| n * (p[1] - p[0]))
+            .collect();
+        if points.is_empty() {
+            return BezierCurve::new(vec![Vec2::ZERO]);
+        }
+        BezierCurve::new(points)
+    }
+
+    /**
+     * flat when every control point is within tolerance of the chord,
+     * the curve is inside the hull of its control points so it's within tolerance too.
+     */
+    pub fn is_flat(&self, tolerance: f32) -> bool {
+        let (first, last) = match (self.control_points.first(), self.control_points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return true,
+        };
+        self.control_points
+            .iter()
+            .all(|p| distance_to_segment(*p, first, last) <= tolerance)
+    }
+
+    /**
+     * vertices of a polyline within tolerance of the curve with their t,
+     * subdividing at the middle until every piece is flat.
+     */
+    pub fn flatten_with_t(&self, tolerance: f32) -> Vec<(f32, Vec2)> {
+        let first = match self.control_points.first() {
+            Some(first) => *first,
+            None => return vec![],
+        };
+        let mut vertices = vec![(0.0, first)];
+        self.flatten_into(0.0, 1.0, tolerance, false, MAX_DEPTH, &mut vertices);
+        vertices
+    }
+
+    /**
+     * flat and moving at a constant speed: every control point is within tolerance
+     * of where it would be on the chord with its points evenly spaced.
+     */
+    fn is_linear(&self, tolerance: f32) -> bool {
+        let (first, last) = match (self.control_points.first(), self.control_points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return true,
+        };
+        let n = self.degree().max(1) as f32;
+        self.control_points
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.distance(first.lerp(last, i as f32 / n)) <= tolerance)
+    }
+
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec2> {
+        self.flatten_with_t(tolerance)
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    fn flatten_into(
+        &self,
+        t0: f32,
+        t1: f32,
+        tolerance: f32,
+        linear: bool,
+        depth: u32,
+        out: &mut Vec<(f32, Vec2)>,
+    ) {
+        let done = match linear {
+            true => self.is_linear(tolerance),
+            false => self.is_flat(tolerance),
+        };
+        if depth == 0 || done {
+            out.push((t1, *self.control_points.last().unwrap()));
+            return;
+        }
+        let t_mid = (t0 + t1) / 2.0;
+        let (left, right) = self.split(0.5);
+        left.flatten_into(t0, t_mid, tolerance, linear, depth - 1, out);
+        right.flatten_into(t_mid, t1, tolerance, linear, depth - 1, out);
+    }
+
+    /**
+     * the table from length to t, pieces are subdivided until t is
+     * within tolerance of linear in length too, not only flat.
+     */
+    pub fn arc_length(&self, tolerance: f32) -> ArcLength {
+        let mut vertices = vec![];
+        if let Some(first) = self.control_points.first() {
+            vertices.push((0.0, *first));
+            self.flatten_into(0.0, 1.0, tolerance, true, MAX_DEPTH, &mut vertices);
+        }
+
+        let mut samples = vec![];
+        let mut length = 0.0;
+        let mut last: Option<Vec2> = None;
+        for (t, p) in vertices {
+            if let Some(last) = last {
+                length += last.distance(p);
+            }
+            samples.push((t, length));
+            last = Some(p);
+        }
+        ArcLength { samples }
+    }
+
+    /**
+     * min and max corner of the curve,
+     * the extremes are at the ends or where the derivative is zero on an axis.
+     */
+    pub fn bounding_box(&self) -> (Vec2, Vec2) {
+        let mut ts = vec![0.0, 1.0];
+        let derivative = self.derivative();
+        for axis in 0..2 {
+            let coefficients: Vec<f32> =
+                derivative.control_points.iter().map(|p| p[axis]).collect();
+            bernstein_roots(&coefficients, 0.0, 1.0, MAX_DEPTH + 4, &mut ts);
+        }
+
+        ts.iter().map(|t| self.evaluate(*t)).fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        )
+    }
+
+    /**
+     * t and point of the curve closest to point,
+     * found on the flattened curve, then refined with newton's method.
+     */
+    pub fn closest_point(&self, point: Vec2) -> (f32, Vec2) {
+        let vertices = self.flatten_with_t(0.25);
+        let mut best = (0.0, self.evaluate(0.0));
+        for pair in vertices.windows(2) {
+            let ((t0, a), (t1, b)) = (pair[0], pair[1]);
+            let ab = b - a;
+            let s = if ab.length_squared() > 0.0 {
+                ((point - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let candidate = a + ab * s;
+            if candidate.distance(point) < best.1.distance(point) {
+                best = (t0 + (t1 - t0) * s, candidate);
+            }
+        }
+
+        // minimize |b(t) - p|^2 where (b(t) - p) . b'(t) = 0
+        let first = self.derivative();
+        let second = first.derivative();
+        let mut t = best.0;
+        for _ in 0..8 {
+            let d = self.evaluate(t) - point;
+            let d1 = first.evaluate(t);
+            let f = d.dot(d1);
+            let df = d1.dot(d1) + d.dot(second.evaluate(t));
+            if df.abs() < f32::EPSILON {
+                break;
+            }
+            t = (t - f / df).clamp(0.0, 1.0);
+        }
+
+        let refined = self.evaluate(t);
+        if refined.distance(point) <= best.1.distance(point) {
+            (t, refined)
+        } else {
+            (best.0, self.evaluate(best.0))
+        }
+    }
+}
+
+/**
+ * t for a length along the curve,
+ * from the length at every vertex of the subdivided curve.
+ */
+pub struct ArcLength {
+    // (t, length from the start)
+    samples: Vec<(f32, f32)>,
+}
+
+impl ArcLength {
+    pub fn length(&self) -> f32 {
+        self.samples.last().map(|s| s.1).unwrap_or(0.0)
+    }
+
+    pub fn t_at(&self, length: f32) -> f32 {
+        let length = length.clamp(0.0, self.length());
+        let i = self.samples.partition_point(|s| s.1 < length);
+        if i == 0 {
+            return 0.0;
+        }
+        if i == self.samples.len() {
+            return 1.0;
+        }
+        let ((t0, s0), (t1, s1)) = (self.samples[i - 1], self.samples[i]);
+        if s1 > s0 {
+            t0 + (t1 - t0) * (length - s0) / (s1 - s0)
+        } else {
+            t0
+        }
+    }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    if ab.length_squared() == 0.0 {
+        return p.distance(a);
+    }
+    let s = ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+    p.distance(a + ab * s)
+}
+
+/**
+ * roots in [t0, t1] of a polynomial in bernstein form:
+ * none if all the coefficients have the same sign,
+ * otherwise split in halves until small enough.
+ */
+fn bernstein_roots(coefficients: &[f32], t0: f32, t1: f32, depth: u32, roots: &mut Vec<f32>) {
+    if coefficients.iter().all(|c| *c > 0.0) || coefficients.iter().all(|c| *c < 0.0) {
+        return;
+    }
+    // zero everywhere, no single extreme
+    if coefficients.iter().all(|c| c.abs() < f32::EPSILON) {
+        return;
+    }
+    if depth == 0 || t1 - t0 < 1e-6 {
+        roots.push((t0 + t1) / 2.0);
+        return;
+    }
+
+    let mut left = vec![];
+    let mut right = vec![];
+    let mut level = coefficients.to_vec();
+    while !level.is_empty() {
+        left.push(level[0]);
+        right.push(*level.last().unwrap());
+        level = level.windows(2).map(|c| (c[0] + c[1]) / 2.0).collect();
+    }
+    right.reverse();
+
+    let t_mid = (t0 + t1) / 2.0;
+    bernstein_roots(&left, t0, t_mid, depth - 1, roots);
+    bernstein_roots(&right, t_mid, t1, depth - 1, roots);
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::BezierCurve;
+    use crate::recursive_bezier;
+
+    fn curve() -> BezierCurve {
+        BezierCurve::new(vec![
+            Vec2::new(10.0, 10.0),
+            Vec2::new(40.0, 200.0),
+            Vec2::new(160.0, -50.0),
+            Vec2::new(200.0, 120.0),
+            Vec2::new(260.0, 30.0),
+        ])
+    }
+
+    #[test]
+    fn test_evaluate_and_split() {
+        let curve = curve();
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            let expected = recursive_bezier(&curve.control_points, t);
+            assert!(curve.evaluate(t).abs_diff_eq(expected, 1e-3));
+        }
+
+        let (left, right) = curve.split(0.3);
+        assert_eq!(left.degree(), 4);
+        for u in [0.0, 0.25, 0.7, 1.0] {
+            let on_left = recursive_bezier(&curve.control_points, 0.3 * u);
+            assert!(left.evaluate(u).abs_diff_eq(on_left, 1e-3));
+            let on_right = recursive_bezier(&curve.control_points, 0.3 + 0.7 * u);
+            assert!(right.evaluate(u).abs_diff_eq(on_right, 1e-3));
+        }
+
+        let empty = BezierCurve::new(vec![]);
+        assert_eq!(empty.split(0.5), (empty.clone(), empty.clone()));
+    }
+
+    #[test]
+    fn test_derivative() {
+        let curve = curve();
+        let derivative = curve.derivative();
+        assert_eq!(derivative.degree(), 3);
+        let h = 1e-3;
+        for t in [0.1, 0.5, 0.9] {
+            let expected = (recursive_bezier(&curve.control_points, t + h)
+                - recursive_bezier(&curve.control_points, t - h))
+                / (2.0 * h);
+            assert!(derivative.evaluate(t).abs_diff_eq(expected, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_flatten() {
+        let curve = curve();
+        let tolerance = 0.5;
+        let vertices = curve.flatten_with_t(tolerance);
+        assert!(vertices.len() > 2);
+        assert_eq!(vertices.first().unwrap().0, 0.0);
+        assert_eq!(vertices.last().unwrap().0, 1.0);
+
+        for pair in vertices.windows(2) {
+            let ((t0, a), (t1, b)) = (pair[0], pair[1]);
+            assert!(a.abs_diff_eq(recursive_bezier(&curve.control_points, t0), 1e-3));
+            // the curve between two vertices stays close to the segment
+            for k in 1..4 {
+                let t = t0 + (t1 - t0) * k as f32 / 4.0;
+                let p = recursive_bezier(&curve.control_points, t);
+                assert!(super::distance_to_segment(p, a, b) <= tolerance + 1e-3);
+            }
+        }
+
+        // a straight line is flat already
+        let line = BezierCurve::new(vec![Vec2::ZERO, Vec2::new(5.0, 0.0), Vec2::new(10.0, 0.0)]);
+        assert_eq!(line.flatten(0.1).len(), 2);
+    }
+
+    #[test]
+    fn test_arc_length() {
+        // a line with control points bunched at the end, so t is not the length
+        let line = BezierCurve::new(vec![
+            Vec2::ZERO,
+            Vec2::new(90.0, 0.0),
+            Vec2::new(100.0, 0.0),
+        ]);
+        let arc_length = line.arc_length(0.01);
+        assert!((arc_length.length() - 100.0).abs() < 1e-2);
+        for s in [0.0, 25.0, 50.0, 99.0] {
+            let t = arc_length.t_at(s);
+            assert!((line.evaluate(t).x - s).abs() < 0.5, "{} at {}", s, t);
+        }
+
+        // a quarter of circle like curve, length close to pi / 2 * r
+        let quarter = BezierCurve::new(vec![
+            Vec2::new(100.0, 0.0),
+            Vec2::new(100.0, 55.228),
+            Vec2::new(55.228, 100.0),
+            Vec2::new(0.0, 100.0),
+        ]);
+        let length = quarter.arc_length(0.01).length();
+        assert!((length - std::f32::consts::FRAC_PI_2 * 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let curve = BezierCurve::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        ]);
+        let (min, max) = curve.bounding_box();
+        assert!(min.abs_diff_eq(Vec2::ZERO, 1e-5));
+        assert!(max.abs_diff_eq(Vec2::new(1.0, 0.75), 1e-5));
+
+        // every point of the curve is inside
+        let curve = self::curve();
+        let (min, max) = curve.bounding_box();
+        for i in 0..=100 {
+            let p = curve.evaluate(i as f32 / 100.0);
+            assert!(p.cmpge(min - 1e-3).all() && p.cmple(max + 1e-3).all());
+        }
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let curve = BezierCurve::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 100.0),
+            Vec2::new(100.0, 100.0),
+            Vec2::new(100.0, 0.0),
+        ]);
+        let (t, p) = curve.closest_point(Vec2::new(50.0, 200.0));
+        assert!((t - 0.5).abs() < 1e-4);
+        assert!(p.abs_diff_eq(Vec2::new(50.0, 75.0), 1e-3));
+
+        // outside the ends it's the end
+        let (t, p) = curve.closest_point(Vec2::new(-10.0, -10.0));
+        assert_eq!(t, 0.0);
+        assert_eq!(p, Vec2::ZERO);
+    }
+}
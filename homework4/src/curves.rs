@@ -0,0 +1,486 @@
+use anyhow::{anyhow, Result};
+use glam::{Vec2, Vec3};
+
+use crate::{de_casteljau, rst};
+
+/**
+ * a parametric curve, evaluated on its own domain of t.
+ */
+pub trait Curve {
+    fn domain(&self) -> (f32, f32);
+
+    fn evaluate(&self, t: f32) -> Vec2;
+
+    // no shorter than the curve, used for the steps to draw it
+    fn length_bound(&self) -> f32;
+}
+
+// length of the polyline through points
+fn polyline_length(points: &[Vec2]) -> f32 {
+    points.windows(2).map(|p| p[0].distance(p[1])).sum()
+}
+
+pub fn draw_curve(r: &mut rst::Rasterizer, curve: &dyn Curve, color: &utils::triangle::Rgb) {
+    let (start, end) = curve.domain();
+    // two steps per pixel, so a step never moves more than half pixel
+    let steps = ((curve.length_bound() * 2.0).ceil() as usize).max(1);
+    let points: Vec<Vec2> = (0..=steps)
+        .map(|i| curve.evaluate(start + (end - start) * i as f32 / steps as f32))
+        .collect();
+    r.draw_curve_points(&points, color);
+}
+
+/**
+ * de Boor's algorithm on homogeneous points,
+ * t should be inside [knots[degree], knots[n]].
+ */
+fn de_boor(degree: usize, knots: &[f32], points: &[Vec3], t: f32) -> Vec3 {
+    let n = points.len();
+    // the last span starting at or before t
+    let k = (degree..n).rev().find(|&k| knots[k] <= t).unwrap_or(degree);
+
+    let mut d: Vec<Vec3> = points[k - degree..=k].to_vec();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = j + k - degree;
+            let span = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if span > 0.0 {
+                (t - knots[i]) / span
+            } else {
+                0.0
+            };
+            d[j] = (1.0 - alpha) * d[j - 1] + alpha * d[j];
+        }
+    }
+    d[degree]
+}
+
+/**
+ * b-spline of any degree, with uniform or any non decreasing knots.
+ */
+pub struct BSpline {
+    pub degree: usize,
+    pub control_points: Vec<Vec2>,
+    pub knots: Vec<f32>,
+}
+
+impl BSpline {
+    /**
+     * knots should be non decreasing,
+     * and there should be control points + degree + 1 of them.
+     */
+    pub fn new(degree: usize, control_points: Vec<Vec2>, knots: Vec<f32>) -> Result<Self> {
+        if degree == 0 || control_points.len() <= degree {
+            return Err(anyhow!(
+                "a b-spline of degree {} needs more than {} control points",
+                degree,
+                control_points.len()
+            ));
+        }
+        if knots.len() != control_points.len() + degree + 1 {
+            return Err(anyhow!(
+                "a b-spline of degree {} with {} control points needs {} knots, but got {}",
+                degree,
+                control_points.len(),
+                control_points.len() + degree + 1,
+                knots.len()
+            ));
+        }
+        if knots.windows(2).any(|k| k[0] > k[1]) {
+            return Err(anyhow!("knots should be non decreasing"));
+        }
+        if knots[degree] >= knots[control_points.len()] {
+            return Err(anyhow!("the domain of the b-spline is empty"));
+        }
+
+        Ok(Self {
+            degree,
+            control_points,
+            knots,
+        })
+    }
+
+    // knots 0, 1, 2, ..., the curve touches no control point
+    pub fn uniform(degree: usize, control_points: Vec<Vec2>) -> Result<Self> {
+        let knots = (0..control_points.len() + degree + 1)
+            .map(|i| i as f32)
+            .collect();
+        Self::new(degree, control_points, knots)
+    }
+
+    // uniform knots with degree + 1 repeated at both ends,
+    // the curve starts at the first control point and ends at the last
+    pub fn clamped(degree: usize, control_points: Vec<Vec2>) -> Result<Self> {
+        let n = control_points.len();
+        let last = n.saturating_sub(degree) as f32;
+        let knots = (0..n + degree + 1)
+            .map(|i| (i as f32 - degree as f32).clamp(0.0, last))
+            .collect();
+        Self::new(degree, control_points, knots)
+    }
+
+    fn homogeneous_points(&self) -> Vec<Vec3> {
+        self.control_points.iter().map(|p| p.extend(1.0)).collect()
+    }
+}
+
+impl Curve for BSpline {
+    fn domain(&self) -> (f32, f32) {
+        (
+            self.knots[self.degree],
+            self.knots[self.control_points.len()],
+        )
+    }
+
+    fn evaluate(&self, t: f32) -> Vec2 {
+        let p = de_boor(self.degree, &self.knots, &self.homogeneous_points(), t);
+        p.truncate()
+    }
+
+    fn length_bound(&self) -> f32 {
+        polyline_length(&self.control_points)
+    }
+}
+
+/**
+ * b-spline with a weight for every control point,
+ * evaluated as a b-spline of the homogeneous points (w * p, w).
+ */
+pub struct Nurbs {
+    pub spline: BSpline,
+    pub weights: Vec<f32>,
+}
+
+impl Nurbs {
+    pub fn new(spline: BSpline, weights: Vec<f32>) -> Result<Self> {
+        if weights.len() != spline.control_points.len() {
+            return Err(anyhow!(
+                "{} weights for {} control points",
+                weights.len(),
+                spline.control_points.len()
+            ));
+        }
+        if weights.iter().any(|w| *w <= 0.0) {
+            return Err(anyhow!("weights should be positive"));
+        }
+        Ok(Self { spline, weights })
+    }
+}
+
+impl Curve for Nurbs {
+    fn domain(&self) -> (f32, f32) {
+        self.spline.domain()
+    }
+
+    fn evaluate(&self, t: f32) -> Vec2 {
+        let points: Vec<Vec3> = self
+            .spline
+            .control_points
+            .iter()
+            .zip(&self.weights)
+            .map(|(p, w)| (*p * *w).extend(*w))
+            .collect();
+        let p = de_boor(self.spline.degree, &self.spline.knots, &points, t);
+        p.truncate() / p.z
+    }
+
+    fn length_bound(&self) -> f32 {
+        self.spline.length_bound()
+    }
+}
+
+/**
+ * uniform catmull-rom spline through every control point,
+ * t = i at the i-th control point.
+ * the points before the first and after the last are mirrored ones.
+ */
+pub struct CatmullRom {
+    control_points: Vec<Vec2>,
+    // every segment as a cubic bezier, built once as the curve is evaluated by it
+    bezier: PiecewiseBezier,
+}
+
+impl CatmullRom {
+    pub fn new(control_points: Vec<Vec2>) -> Result<Self> {
+        if control_points.len() < 2 {
+            return Err(anyhow!(
+                "a catmull-rom spline needs at least 2 control points, but got {}",
+                control_points.len()
+            ));
+        }
+        let bezier = Self::to_bezier(&control_points);
+        Ok(Self {
+            control_points,
+            bezier,
+        })
+    }
+
+    pub fn control_points(&self) -> &[Vec2] {
+        &self.control_points
+    }
+
+    pub fn bezier(&self) -> &PiecewiseBezier {
+        &self.bezier
+    }
+
+    fn to_bezier(points: &[Vec2]) -> PiecewiseBezier {
+        let n = points.len();
+        let at = |i: isize| -> Vec2 {
+            if i < 0 {
+                2.0 * points[0] - points[1]
+            } else if i as usize >= n {
+                2.0 * points[n - 1] - points[n - 2]
+            } else {
+                points[i as usize]
+            }
+        };
+
+        let mut bezier_points = vec![points[0]];
+        for i in 0..n as isize - 1 {
+            bezier_points.push(at(i) + (at(i + 1) - at(i - 1)) / 6.0);
+            bezier_points.push(at(i + 1) - (at(i + 2) - at(i)) / 6.0);
+            bezier_points.push(at(i + 1));
+        }
+        // 3 more points for every one after the first, a whole segment at least
+        PiecewiseBezier {
+            control_points: bezier_points,
+        }
+    }
+}
+
+impl Curve for CatmullRom {
+    fn domain(&self) -> (f32, f32) {
+        (0.0, (self.control_points.len() - 1) as f32)
+    }
+
+    fn evaluate(&self, t: f32) -> Vec2 {
+        self.bezier.evaluate(t)
+    }
+
+    fn length_bound(&self) -> f32 {
+        self.bezier.length_bound()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuity {
+    // segments only share the end points
+    C0,
+    // handles around a joint are mirrored
+    C1,
+    // handles around a joint are on a line, with their own length
+    G1,
+}
+
+/**
+ * cubic bezier segments sharing the end points:
+ * points 3i..=3i+3 are the i-th segment, t = i at its start.
+ * points after the last whole segment are ignored.
+ */
+pub struct PiecewiseBezier {
+    control_points: Vec<Vec2>,
+}
+
+impl PiecewiseBezier {
+    /**
+     * there should be 4 control points at least, for a whole segment
+     */
+    pub fn new(control_points: Vec<Vec2>) -> Result<Self> {
+        if control_points.len() < 4 {
+            return Err(anyhow!(
+                "a piecewise bezier curve needs at least 4 control points, but got {}",
+                control_points.len()
+            ));
+        }
+        Ok(Self { control_points })
+    }
+
+    pub fn control_points(&self) -> &[Vec2] {
+        &self.control_points
+    }
+
+    pub fn into_control_points(self) -> Vec<Vec2> {
+        self.control_points
+    }
+
+    pub fn segments(&self) -> usize {
+        self.control_points.len().saturating_sub(1) / 3
+    }
+
+    /**
+     * move handles so the joints have the continuity.
+     * the handle at `fixed` is kept, and its partner across the joint is moved,
+     * for every other joint, the handle after it is moved.
+     */
+    pub fn enforce(&mut self, continuity: Continuity, fixed: Option<usize>) {
+        if continuity == Continuity::C0 {
+            return;
+        }
+
+        let points = &mut self.control_points;
+        for joint in (3..points.len().saturating_sub(1)).step_by(3) {
+            let (keep, adjust) = if fixed == Some(joint + 1) {
+                (joint + 1, joint - 1)
+            } else {
+                (joint - 1, joint + 1)
+            };
+
+            let center = points[joint];
+            let kept = center - points[keep];
+            points[adjust] = match continuity {
+                Continuity::C1 => center + kept,
+                Continuity::G1 => {
+                    let length = points[adjust].distance(center);
+                    center + kept.normalize_or_zero() * length
+                }
+                Continuity::C0 => points[adjust],
+            };
+        }
+    }
+}
+
+impl Curve for PiecewiseBezier {
+    fn domain(&self) -> (f32, f32) {
+        (0.0, self.segments() as f32)
+    }
+
+    fn evaluate(&self, t: f32) -> Vec2 {
+        let segment = (t.max(0.0) as usize).min(self.segments() - 1);
+        let points = &self.control_points[segment * 3..segment * 3 + 4];
+        de_casteljau(points, t - segment as f32).last().unwrap()[0]
+    }
+
+    fn length_bound(&self) -> f32 {
+        polyline_length(&self.control_points[..self.segments() * 3 + 1])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::{BSpline, CatmullRom, Continuity, Curve, Nurbs, PiecewiseBezier};
+    use crate::de_casteljau;
+
+    fn points() -> Vec<Vec2> {
+        vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(3.0, 2.0),
+            Vec2::new(4.0, 0.0),
+            Vec2::new(6.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn test_b_spline() {
+        let p = points();
+
+        // a uniform cubic b-spline starts at (p0 + 4 p1 + p2) / 6
+        let spline = BSpline::uniform(3, p.clone()).unwrap();
+        assert_eq!(spline.domain(), (3.0, 5.0));
+        let start = (p[0] + 4.0 * p[1] + p[2]) / 6.0;
+        assert!(spline.evaluate(3.0).abs_diff_eq(start, 1e-5));
+        let end = (p[2] + 4.0 * p[3] + p[4]) / 6.0;
+        assert!(spline.evaluate(5.0).abs_diff_eq(end, 1e-5));
+
+        // a clamped b-spline of degree n - 1 is the bezier curve
+        let spline = BSpline::clamped(4, p.clone()).unwrap();
+        assert_eq!(spline.domain(), (0.0, 1.0));
+        for t in [0.0, 0.3, 0.5, 1.0] {
+            let bezier = de_casteljau(&p, t).last().unwrap()[0];
+            assert!(spline.evaluate(t).abs_diff_eq(bezier, 1e-5));
+        }
+
+        // non uniform knots, the clamped cubic ends at the control points
+        let spline = BSpline::new(3, p.clone(), vec![0., 0., 0., 0., 0.2, 1., 1., 1., 1.]).unwrap();
+        assert!(spline.evaluate(0.0).abs_diff_eq(p[0], 1e-5));
+        assert!(spline.evaluate(1.0).abs_diff_eq(p[4], 1e-5));
+
+        assert!(BSpline::new(3, p.clone(), vec![0.0; 4]).is_err());
+        assert!(BSpline::new(3, p.clone(), vec![0., 0., 0., 0., 2., 1., 1., 1., 1.]).is_err());
+        assert!(BSpline::uniform(5, p).is_err());
+    }
+
+    #[test]
+    fn test_nurbs() {
+        // a quarter of the unit circle
+        let spline = BSpline::clamped(
+            2,
+            vec![
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+            ],
+        )
+        .unwrap();
+        let circle = Nurbs::new(spline, vec![1.0, 0.5f32.sqrt(), 1.0]).unwrap();
+        for i in 0..=10 {
+            let p = circle.evaluate(i as f32 / 10.0);
+            assert!((p.length() - 1.0).abs() < 1e-5, "{:?} not on circle", p);
+        }
+        let middle = circle.evaluate(0.5);
+        assert!(middle.abs_diff_eq(Vec2::splat(0.5f32.sqrt()), 1e-5));
+
+        // all weights one is the b-spline
+        let nurbs = Nurbs::new(BSpline::uniform(3, points()).unwrap(), vec![1.0; 5]).unwrap();
+        let spline = BSpline::uniform(3, points()).unwrap();
+        assert!(nurbs.evaluate(4.2).abs_diff_eq(spline.evaluate(4.2), 1e-5));
+
+        let spline = BSpline::uniform(3, points()).unwrap();
+        assert!(Nurbs::new(spline, vec![1.0, 1.0, 0.0, 1.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_catmull_rom() {
+        let p = points();
+        let curve = CatmullRom::new(p.clone()).unwrap();
+        assert_eq!(curve.domain(), (0.0, 4.0));
+        assert_eq!(curve.bezier().segments(), 4);
+        for (i, point) in p.iter().enumerate() {
+            assert!(curve.evaluate(i as f32).abs_diff_eq(*point, 1e-5));
+        }
+
+        // the tangent at p2 is (p3 - p1) / 2
+        let h = 1e-3;
+        let tangent = (curve.evaluate(2.0 + h) - curve.evaluate(2.0 - h)) / (2.0 * h);
+        assert!(tangent.abs_diff_eq((p[3] - p[1]) / 2.0, 1e-2));
+
+        // a line between 2 points, but nothing through a single point
+        let line = CatmullRom::new(p[..2].to_vec()).unwrap();
+        assert!(line.evaluate(0.5).abs_diff_eq((p[0] + p[1]) / 2.0, 1e-5));
+        assert!(CatmullRom::new(p[..1].to_vec()).is_err());
+        assert!(CatmullRom::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_piecewise_bezier() {
+        let mut curve = PiecewiseBezier::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(2.0, -1.0),
+            Vec2::new(2.0, 0.0),
+        ])
+        .unwrap();
+        assert_eq!(curve.segments(), 2);
+        assert!(curve.evaluate(1.0).abs_diff_eq(Vec2::new(1.0, 0.0), 1e-5));
+        assert!(curve.evaluate(2.0).abs_diff_eq(Vec2::new(2.0, 0.0), 1e-5));
+        assert!(curve.evaluate(0.5).abs_diff_eq(Vec2::new(0.5, 0.75), 1e-5));
+
+        // g1 keeps the length of the moved handle
+        curve.enforce(Continuity::G1, None);
+        assert!(curve.control_points()[4].abs_diff_eq(Vec2::new(1.0, -2.0), 1e-5));
+
+        // c1 mirrors the handle kept
+        curve.enforce(Continuity::C1, Some(4));
+        assert!(curve.control_points()[2].abs_diff_eq(Vec2::new(1.0, 2.0), 1e-5));
+        curve.enforce(Continuity::C1, None);
+        assert!(curve.control_points()[4].abs_diff_eq(Vec2::new(1.0, -2.0), 1e-5));
+
+        // no whole segment
+        assert!(PiecewiseBezier::new(points()[..3].to_vec()).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use glam::Vec2;
+use utils::{path::FillRule, rasterizer::RasterizableMut};
+
+use crate::{
+    bezier,
+    bezier_curve::BezierCurve,
+    curves::{draw_curve, BSpline, CatmullRom, Continuity, Curve, Nurbs, PiecewiseBezier},
+    de_casteljau, rst,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveKind {
+    Bezier,
+    UniformBSpline,
+    ClampedBSpline,
+    Nurbs,
+    CatmullRom,
+    PiecewiseBezier,
+}
+
+/**
+ * control points of a curve, edited with the mouse:
+ * click on empty space to add a point, drag a point to move it,
+ * and right click a point to delete it.
+ */
+pub struct BezierEditor {
+    pub kind: CurveKind,
+    // kept between the segments of piecewise bezier while dragging
+    pub continuity: Continuity,
+    // the t where de Casteljau's intermediate lines are shown
+    pub t: f32,
+    pub show_construction: bool,
+    // vertices of the curve flattened to a polyline within half pixel
+    pub show_flattening: bool,
+    // fill the polygon of the control points by the rule
+    pub fill: Option<FillRule>,
+    // how far from a point a click still picks it
+    pub pick_radius: f32,
+
+    control_points: Vec<Vec2>,
+    // weight of every control point, used by nurbs, always as many as the points
+    weights: Vec<f32>,
+    dragging: Option<usize>,
+    cursor: Vec2,
+}
+
+impl Default for BezierEditor {
+    fn default() -> Self {
+        Self {
+            kind: CurveKind::Bezier,
+            continuity: Continuity::C0,
+            t: 0.5,
+            show_construction: true,
+            show_flattening: false,
+            fill: None,
+            pick_radius: 10.0,
+            control_points: vec![],
+            weights: vec![],
+            dragging: None,
+            cursor: Vec2::ZERO,
+        }
+    }
+}
+
+impl BezierEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn control_points(&self) -> &[Vec2] {
+        &self.control_points
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /**
+     * replace the control points, with a positive weight for every one of them
+     */
+    pub fn set_control_points(&mut self, points: Vec<Vec2>, weights: Vec<f32>) -> Result<()> {
+        if points.len() != weights.len() {
+            return Err(anyhow!(
+                "{} weights for {} control points",
+                weights.len(),
+                points.len()
+            ));
+        }
+        if weights.iter().any(|w| *w <= 0.0) {
+            return Err(anyhow!("weights should be positive"));
+        }
+        self.control_points = points;
+        self.weights = weights;
+        self.dragging = None;
+        Ok(())
+    }
+
+    // index of the nearest control point within pick radius
+    pub fn pick(&self, at: Vec2) -> Option<usize> {
+        self.control_points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.distance(at)))
+            .filter(|(_, d)| *d <= self.pick_radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    // start dragging the point under the cursor, or add a new one there
+    pub fn press(&mut self, at: Vec2) {
+        self.dragging = match self.pick(at) {
+            Some(i) => Some(i),
+            None => {
+                self.control_points.push(at);
+                self.weights.push(1.0);
+                None
+            }
+        };
+    }
+
+    pub fn moved(&mut self, at: Vec2) {
+        self.cursor = at;
+        let i = match self.dragging {
+            Some(i) => i,
+            None => return,
+        };
+
+        let delta = at - self.control_points[i];
+        self.control_points[i] = at;
+        if self.kind == CurveKind::PiecewiseBezier {
+            // handles move along with their joint
+            if i % 3 == 0 {
+                for h in [i.wrapping_sub(1), i + 1] {
+                    if let Some(p) = self.control_points.get_mut(h) {
+                        *p += delta;
+                    }
+                }
+            }
+            if let Ok(mut curve) = PiecewiseBezier::new(self.control_points.clone()) {
+                curve.enforce(self.continuity, Some(i));
+                self.control_points = curve.into_control_points();
+            }
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.dragging = None;
+    }
+
+    pub fn delete(&mut self, at: Vec2) {
+        if let Some(i) = self.pick(at) {
+            self.control_points.remove(i);
+            self.weights.remove(i);
+            self.dragging = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.control_points.clear();
+        self.weights.clear();
+        self.dragging = None;
+    }
+
+    pub fn move_t(&mut self, dt: f32) {
+        self.t = (self.t + dt).clamp(0.0, 1.0);
+    }
+
+    // scale the weight of the point under the cursor
+    pub fn scale_weight(&mut self, factor: f32) {
+        if let Some(i) = self.pick(self.cursor) {
+            self.weights[i] = (self.weights[i] * factor).clamp(0.01, 100.0);
+        }
+    }
+
+    /**
+     * the curve of the control points by kind,
+     * b-splines and nurbs are cubic, or of lower degree with fewer points.
+     */
+    pub fn curve(&self) -> Option<Box<dyn Curve>> {
+        let points = self.control_points.clone();
+        let degree = 3.min(points.len().saturating_sub(1));
+        let curve: Box<dyn Curve> = match self.kind {
+            CurveKind::Bezier => return None,
+            CurveKind::UniformBSpline => Box::new(BSpline::uniform(degree, points).ok()?),
+            CurveKind::ClampedBSpline => Box::new(BSpline::clamped(degree, points).ok()?),
+            CurveKind::Nurbs => {
+                let spline = BSpline::clamped(degree, points).ok()?;
+                Box::new(Nurbs::new(spline, self.weights.clone()).ok()?)
+            }
+            CurveKind::CatmullRom => Box::new(CatmullRom::new(points).ok()?),
+            CurveKind::PiecewiseBezier => Box::new(PiecewiseBezier::new(points).ok()?),
+        };
+        Some(curve)
+    }
+
+    pub fn draw(&self, r: &mut rst::Rasterizer) {
+        use utils::triangle::Rgb;
+
+        if let Some(rule) = self.fill {
+            let polygon = utils::path::Path::polygon(&self.control_points);
+            r.fill_path(&polygon, rule, Rgb(0, 64, 128));
+        }
+
+        if self.kind != CurveKind::Bezier {
+            if let Some(curve) = self.curve() {
+                draw_curve(r, curve.as_ref(), &Rgb::GREEN);
+            }
+            for line in self.control_points.windows(2) {
+                r.draw_line(line[0], line[1], &Rgb(96, 96, 96));
+            }
+            for p in &self.control_points {
+                r.draw_circle(*p, 6.0);
+            }
+            return;
+        }
+
+        if self.control_points.len() >= 2 {
+            bezier(r, &self.control_points);
+        }
+
+        if self.show_construction && self.control_points.len() >= 2 {
+            let levels = de_casteljau(&self.control_points, self.t);
+            // the control polygon in gray, then every level in a brighter color
+            let colors = [
+                Rgb(96, 96, 96),
+                Rgb(0, 128, 255),
+                Rgb(255, 0, 255),
+                Rgb(255, 255, 0),
+            ];
+            for (level, points) in levels.iter().enumerate() {
+                let color = colors[level % colors.len()];
+                for line in points.windows(2) {
+                    r.draw_line(line[0], line[1], &color);
+                }
+                if level > 0 {
+                    for p in points {
+                        r.draw_circle(*p, 3.0);
+                    }
+                }
+            }
+        }
+
+        if self.show_flattening && self.control_points.len() >= 2 {
+            let curve = BezierCurve::new(self.control_points.clone());
+            for p in curve.flatten(0.5) {
+                r.draw_circle(p, 2.0);
+            }
+        }
+
+        for p in &self.control_points {
+            r.draw_circle(*p, 6.0);
+        }
+    }
+
+    /**
+     * save control points as a text file, one `x y` pair per line,
+     * followed by the weight if it's not one
+     */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content: String = self
+            .control_points
+            .iter()
+            .zip(&self.weights)
+            .map(|(p, w)| match w {
+                w if *w == 1.0 => format!("{} {}\n", p.x, p.y),
+                w => format!("{} {} {}\n", p.x, p.y, w),
+            })
+            .collect();
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let content = std::fs::read_to_string(path)?;
+        let mut points = vec![];
+        let mut weights = vec![];
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let values = line
+                .split_whitespace()
+                .map(|v| v.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()?;
+            match values[..] {
+                [x, y] => {
+                    points.push(Vec2::new(x, y));
+                    weights.push(1.0);
+                }
+                [x, y, w] if w > 0.0 => {
+                    points.push(Vec2::new(x, y));
+                    weights.push(w);
+                }
+                _ => return Err(anyhow!("invalid control point: {}", line)),
+            }
+        }
+        self.set_control_points(points, weights)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::{BezierEditor, CurveKind};
+    use crate::{curves::Continuity, de_casteljau};
+
+    #[test]
+    fn test_editing() {
+        let mut editor = BezierEditor::new();
+        editor.press(Vec2::new(10.0, 10.0));
+        editor.release();
+        editor.press(Vec2::new(100.0, 10.0));
+        editor.release();
+        assert_eq!(editor.control_points().len(), 2);
+
+        // pressing near a point drags it instead of adding one
+        editor.press(Vec2::new(12.0, 8.0));
+        editor.moved(Vec2::new(50.0, 60.0));
+        editor.release();
+        editor.moved(Vec2::new(0.0, 0.0));
+        assert_eq!(editor.control_points().len(), 2);
+        assert_eq!(editor.control_points()[0], Vec2::new(50.0, 60.0));
+
+        editor.delete(Vec2::new(101.0, 11.0));
+        assert_eq!(editor.control_points(), [Vec2::new(50.0, 60.0)]);
+        assert_eq!(editor.weights(), [1.0]);
+    }
+
+    #[test]
+    fn test_piecewise_dragging() {
+        let mut editor = BezierEditor::new();
+        editor.kind = CurveKind::PiecewiseBezier;
+        editor.continuity = Continuity::C1;
+        for (x, y) in [(0., 0.), (0., 100.), (100., 100.), (100., 0.), (200., 0.)] {
+            editor.press(Vec2::new(x, y));
+            editor.release();
+        }
+
+        // dragging the handle before the joint mirrors the one after
+        editor.press(Vec2::new(100.0, 100.0));
+        editor.moved(Vec2::new(80.0, 50.0));
+        editor.release();
+        assert!(editor.control_points()[4].abs_diff_eq(Vec2::new(120.0, -50.0), 1e-4));
+
+        // dragging the joint moves the handles around
+        editor.press(Vec2::new(100.0, 0.0));
+        editor.moved(Vec2::new(100.0, 10.0));
+        editor.release();
+        assert!(editor.control_points()[2].abs_diff_eq(Vec2::new(80.0, 60.0), 1e-4));
+        assert!(editor.control_points()[4].abs_diff_eq(Vec2::new(120.0, -40.0), 1e-4));
+        assert!(editor.curve().is_some());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir().join("homework4_control_points.txt");
+        let mut editor = BezierEditor::new();
+        let points = vec![Vec2::new(1.5, 2.0), Vec2::new(300.0, 400.25)];
+        assert!(editor
+            .set_control_points(points.clone(), vec![1.0])
+            .is_err());
+        assert!(editor
+            .set_control_points(points.clone(), vec![1.0, 0.0])
+            .is_err());
+        editor.set_control_points(points, vec![1.0, 2.5]).unwrap();
+        editor.save(&path).unwrap();
+
+        let mut loaded = BezierEditor::new();
+        loaded.load(&path).unwrap();
+        assert_eq!(loaded.control_points(), editor.control_points());
+        assert_eq!(loaded.weights(), editor.weights());
+
+        std::fs::write(&path, "1 2 3 4\n").unwrap();
+        assert!(loaded.load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_de_casteljau() {
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        ];
+        let levels = de_casteljau(&points, 0.5);
+        assert_eq!(
+            levels.iter().map(|l| l.len()).collect::<Vec<_>>(),
+            [4, 3, 2, 1]
+        );
+        assert!(levels[3][0].abs_diff_eq(Vec2::new(0.5, 0.75), 1e-6));
+    }
+}
@@ -1,10 +1,24 @@
 use glam::Vec2;
 
+pub mod bezier_curve;
+pub mod curves;
+pub mod editor;
 pub mod rst;
 
-pub fn naive_bezier(r: &mut rst::Rasterizer, control_points: &Vec<Vec2>) {
-    let range_length = 1000;
-    for t in 0..range_length {
+/**
+ * steps needed for a curve to have no gaps:
+ * the control polygon is never shorter than the curve,
+ * so with two steps per pixel of it, a step never moves more than half pixel.
+ */
+pub fn curve_steps(control_points: &[Vec2]) -> usize {
+    let length: f32 = control_points.windows(2).map(|p| p[0].distance(p[1])).sum();
+    ((length * 2.0).ceil() as usize).max(1)
+}
+
+pub fn naive_bezier(r: &mut rst::Rasterizer, control_points: &[Vec2]) {
+    let range_length = curve_steps(control_points);
+    let mut points = Vec::with_capacity(range_length + 1);
+    for t in 0..=range_length {
         let t = t as f32 / range_length as f32;
         let sub_t = 1.0 - t;
         let point = sub_t.powi(3) * control_points[0]
@@ -12,8 +26,9 @@ pub fn naive_bezier(r: &mut rst::Rasterizer, control_points: &Vec<Vec2>) {
             + 3.0 * t.powi(2) * sub_t * control_points[2]
             + t.powi(3) * control_points[3];
 
-        r.pixel_add_rgb(&point, &utils::triangle::Rgb::RED);
+        points.push(point);
     }
+    r.draw_curve_points(&points, &utils::triangle::Rgb::RED);
 }
 
 fn recursive_bezier(control_points: &Vec<Vec2>, t: f32) -> Vec2 {
@@ -28,13 +43,99 @@ fn recursive_bezier(control_points: &Vec<Vec2>, t: f32) -> Vec2 {
     recursive_bezier(&points, t)
 }
 
+/**
+ * every level of de Casteljau's algorithm at t,
+ * from the control points down to the single point on the curve.
+ */
+pub fn de_casteljau(control_points: &[Vec2], t: f32) -> Vec<Vec<Vec2>> {
+    let mut levels = vec![control_points.to_vec()];
+    while levels.last().unwrap().len() > 1 {
+        let points = levels.last().unwrap();
+        let next = points
+            .windows(2)
+            .map(|p| (1.0 - t) * p[0] + t * p[1])
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
 pub fn bezier(r: &mut rst::Rasterizer, control_points: &Vec<Vec2>) {
     // TODO: Iterate through all t = 0 to t = 1 with small steps, and call de Casteljau's
     // recursive Bezier algorithm.
-    let range_length = 1000;
-    for t in 0..range_length {
+    let range_length = curve_steps(control_points);
+    let mut points = Vec::with_capacity(range_length + 1);
+    for t in 0..=range_length {
         let t = t as f32 / range_length as f32;
-        let point = recursive_bezier(control_points, t);
-        r.pixel_add_rgb(&point, &utils::triangle::Rgb::GREEN);
+        points.push(recursive_bezier(control_points, t));
+    }
+    r.draw_curve_points(&points, &utils::triangle::Rgb::GREEN);
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+    use utils::rasterizer::Rasterizable;
+
+    use crate::{bezier, rst};
+
+    #[test]
+    fn test_curve_without_gaps() {
+        for anti_aliasing in [true, false] {
+            let mut r = rst::Rasterizer::new(200, 100);
+            r.set_anti_aliasing(anti_aliasing);
+            let control_points = vec![
+                Vec2::new(10.0, 50.0),
+                Vec2::new(60.0, -40.0),
+                Vec2::new(140.0, 140.0),
+                Vec2::new(190.0, 50.0),
+            ];
+            bezier(&mut r, &control_points);
+
+            // every column the curve passes is lit
+            for x in 10..190 {
+                let lit = (0..100).any(|y| r.data()[y * 200 + x].1 > 0);
+                assert!(lit, "gap at column {}", x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stroke_coverage() {
+        let mut r = rst::Rasterizer::new(20, 20);
+        r.set_stroke_width(4.0);
+        r.draw_curve_point(&Vec2::new(10.5, 10.5), &utils::triangle::Rgb::GREEN);
+        let green = |r: &rst::Rasterizer, x: usize, y: usize| r.data()[y * 20 + x].1;
+
+        // full inside, partial at the edge, nothing far away
+        assert_eq!(green(&r, 10, 10), 255);
+        assert_eq!(green(&r, 11, 11), 255);
+        let edge = green(&r, 12, 10);
+        assert!(edge > 0 && edge < 255);
+        assert_eq!(green(&r, 14, 10), 0);
+
+        // drawing twice never sums the coverage
+        r.draw_curve_point(&Vec2::new(10.5, 10.5), &utils::triangle::Rgb::GREEN);
+        assert_eq!(green(&r, 12, 10), edge);
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut r = rst::Rasterizer::new(100, 100);
+        bezier(&mut r, &vec![Vec2::ZERO, Vec2::new(50.0, 90.0), Vec2::new(90.0, 10.0)]);
+        assert!(r.svg().is_none());
+
+        r.set_recording(true);
+        bezier(&mut r, &vec![Vec2::ZERO, Vec2::new(50.0, 90.0), Vec2::new(90.0, 10.0)]);
+        r.draw_circle(Vec2::new(50.0, 50.0), 5.0);
+        r.draw_line(Vec2::ZERO, Vec2::ONE, &utils::triangle::Rgb::RED);
+        let svg = r.svg().unwrap();
+        assert_eq!(svg.len(), 3);
+        let text = svg.to_svg();
+        assert_eq!(text.matches("<polyline").count(), 1);
+        assert!(text.contains(r#"<circle cx="50" cy="50" r="5""#));
+
+        r.clear(rst::Buffers::all());
+        assert!(r.svg().unwrap().is_empty());
     }
 }
@@ -1,45 +1,127 @@
 use anyhow::Ok;
 use glam::Vec2;
-use homework4::{bezier, naive_bezier, rst};
-use utils::graphic::{save_image, Action, Control};
+use homework4::{
+    curves::Continuity,
+    editor::{BezierEditor, CurveKind},
+    naive_bezier, rst,
+};
+use utils::graphic::{save_image, Action, Control, Key};
+use utils::path::FillRule;
+use utils::rasterizer::RasterizableMut;
 
 fn main() {
     let frame_width = 700;
     let path = "output.png";
+    let svg_path = "output.svg";
+    let control_points_path = "control_points.txt";
 
     let mut r = rst::Rasterizer::new(frame_width, frame_width);
 
-    let mut control_points = vec![];
+    let mut editor = BezierEditor::new();
 
     utils::graphic::start_loop(frame_width, frame_width, move |actions, display_image| {
         for action in actions {
             match action {
                 Action::Stop => {
+                    // the last frame is saved as svg too, recorded as it's drawn once more
+                    r.set_recording(true);
+                    draw_frame(&mut r, &editor);
                     save_image(&r, path)?;
+                    if let Some(svg) = r.svg() {
+                        svg.save(svg_path)?;
+                    }
                     return Ok(Control::Stop);
                 }
                 // use move + click for get clicked point
                 Action::Clicked { x, y } => {
-                    if control_points.len() >= 4 {
-                        control_points.clear();
+                    editor.press(Vec2 { x: *x, y: *y });
+                    dbg!(editor.control_points());
+                }
+                Action::Moved { x, y } => editor.moved(Vec2 { x: *x, y: *y }),
+                Action::Released { .. } => editor.release(),
+                Action::RightClicked { x, y } => editor.delete(Vec2 { x: *x, y: *y }),
+                Action::Key(Key::Left) => editor.move_t(-0.05),
+                Action::Key(Key::Right) => editor.move_t(0.05),
+                Action::Key(Key::T) => {
+                    editor.show_construction = !editor.show_construction;
+                    dbg!(editor.show_construction);
+                }
+                Action::Key(Key::A) => {
+                    r.set_anti_aliasing(!r.anti_aliasing());
+                    dbg!(r.anti_aliasing());
+                }
+                Action::Key(Key::Equals) => r.set_stroke_width(r.stroke_width() + 0.5),
+                Action::Key(Key::Minus) => r.set_stroke_width(r.stroke_width() - 0.5),
+                Action::Key(k)
+                    if matches!(
+                        k,
+                        Key::Key1 | Key::Key2 | Key::Key3 | Key::Key4 | Key::Key5 | Key::Key6
+                    ) =>
+                {
+                    editor.kind = match k {
+                        Key::Key1 => CurveKind::Bezier,
+                        Key::Key2 => CurveKind::UniformBSpline,
+                        Key::Key3 => CurveKind::ClampedBSpline,
+                        Key::Key4 => CurveKind::Nurbs,
+                        Key::Key5 => CurveKind::CatmullRom,
+                        _ => CurveKind::PiecewiseBezier,
+                    };
+                    dbg!(editor.kind);
+                }
+                Action::Key(Key::K) => {
+                    editor.continuity = match editor.continuity {
+                        Continuity::C0 => Continuity::C1,
+                        Continuity::C1 => Continuity::G1,
+                        Continuity::G1 => Continuity::C0,
+                    };
+                    dbg!(editor.continuity);
+                }
+                // weight of the point under the cursor, for nurbs
+                Action::Key(Key::Up) => editor.scale_weight(1.25),
+                Action::Key(Key::Down) => editor.scale_weight(0.8),
+                Action::Key(Key::F) => {
+                    editor.show_flattening = !editor.show_flattening;
+                    dbg!(editor.show_flattening);
+                }
+                Action::Key(Key::R) => {
+                    editor.fill = match editor.fill {
+                        None => Some(FillRule::EvenOdd),
+                        Some(FillRule::EvenOdd) => Some(FillRule::NonZero),
+                        Some(FillRule::NonZero) => None,
+                    };
+                    dbg!(editor.fill);
+                }
+                Action::Key(Key::C) => editor.clear(),
+                Action::Key(Key::S) => {
+                    if let Err(e) = editor.save(control_points_path) {
+                        eprintln!("cannot save {}: {}", control_points_path, e);
+                    }
+                }
+                Action::Key(Key::L) => {
+                    if let Err(e) = editor.load(control_points_path) {
+                        eprintln!("cannot load {}: {}", control_points_path, e);
                     }
-                    control_points.push(Vec2 { x: *x, y: *y });
-                    dbg!(&control_points);
                 }
                 _ => (),
             }
         }
 
-        r.clear(rst::Buffers::all());
-        for p in &control_points {
-            r.draw_circle(*p, 10.0);
-        }
-        if control_points.len() >= 4 {
-            naive_bezier(&mut r, &control_points);
-            bezier(&mut r, &control_points);
-        }
+        draw_frame(&mut r, &editor);
         display_image(&r)?;
 
         Ok(Control::Continue)
     });
 }
+
+fn draw_frame(r: &mut rst::Rasterizer, editor: &BezierEditor) {
+    r.clear(rst::Buffers::all());
+    if editor.kind == CurveKind::Bezier && editor.control_points().len() == 4 {
+        naive_bezier(r, editor.control_points());
+    }
+    editor.draw(r);
+    let t_text = format!(
+        "{:?} {:?}, t = {:.2}",
+        editor.kind, editor.continuity, editor.t
+    );
+    r.draw_text(8, 8, &t_text, utils::triangle::Rgb(255, 255, 255));
+}
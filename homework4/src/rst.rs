@@ -1,10 +1,21 @@
 use glam::Vec2;
 pub use utils::rasterizer::{Buffers, IndBufId, PosBufId, Primitive, Rasterizable};
+use utils::{
+    path::{FillRule, Path},
+    svg::SvgDocument,
+};
 
 pub struct Rasterizer {
     frame_buf: Vec<utils::triangle::Rgb>,
     width: u32,
     height: u32,
+
+    // width in pixels of the curves drawn
+    stroke_width: f32,
+    anti_aliasing: bool,
+
+    // primitives drawn since the last clear, if recording
+    recording: Option<SvgDocument>,
 }
 
 impl utils::rasterizer::Rasterizable for Rasterizer {
@@ -17,12 +28,28 @@ impl utils::rasterizer::Rasterizable for Rasterizer {
     }
 }
 
+impl utils::rasterizer::RasterizableMut for Rasterizer {
+    fn data_mut(&mut self) -> &mut [utils::rgb::Rgb] {
+        &mut self.frame_buf
+    }
+
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: utils::rgb::Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.path(path, rule, color);
+        }
+        utils::path::fill_path(self, path, rule, color)
+    }
+}
+
 impl Rasterizer {
     pub fn new(width: u32, height: u32) -> Self {
         let mut res = Self {
             frame_buf: Vec::new(),
             width,
             height,
+            stroke_width: 1.0,
+            anti_aliasing: true,
+            recording: None,
         };
 
         res.frame_buf
@@ -35,6 +62,9 @@ impl Rasterizer {
     // origin point on top left of the screen,
     // x axis face to right and y axis face to down.
     fn buf_ind_at(&self, point: &Vec2) -> Option<usize> {
+        if point.x < 0.0 || point.y < 0.0 {
+            return None;
+        }
         let (x_range, y_range) = (0..self.width, 0..self.height);
         let (x, y) = (point.x as u32, point.y as u32);
         if !(x_range.contains(&x) && y_range.contains(&y)) {
@@ -68,13 +98,119 @@ impl Rasterizer {
         self.set_pixel(point, &(*color + already_color));
     }
 
+    // keep the brighter of the pixel and color scaled by coverage, for every channel
+    fn pixel_max_rgb(&mut self, point: &Vec2, color: &utils::triangle::Rgb, coverage: f32) {
+        let already_color = self.get_pixel(point);
+        let scale = |c: u8| (c as f32 * coverage).round() as u8;
+        let color = utils::triangle::Rgb(
+            scale(color.0).max(already_color.0),
+            scale(color.1).max(already_color.1),
+            scale(color.2).max(already_color.2),
+        );
+        self.set_pixel(point, &color);
+    }
+
+    pub fn set_stroke_width(&mut self, width: f32) {
+        self.stroke_width = width.max(0.0);
+    }
+
+    pub fn stroke_width(&self) -> f32 {
+        self.stroke_width
+    }
+
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: bool) {
+        self.anti_aliasing = anti_aliasing;
+    }
+
+    pub fn anti_aliasing(&self) -> bool {
+        self.anti_aliasing
+    }
+
+    /**
+     * draw a point of a curve with the stroke width.
+     * with anti aliasing, the pixels around get the coverage of a disk
+     * of stroke width by their distance to the point,
+     * otherwise the pixels with center inside the disk are set.
+     * coverage is never summed, so points of a curve may be as dense as needed.
+     */
+    pub fn draw_curve_point(&mut self, point: &Vec2, color: &utils::triangle::Rgb) {
+        let radius = self.stroke_width / 2.0;
+        let min = (*point - radius - 1.0).floor();
+        let max = (*point + radius + 1.0).ceil();
+
+        for y in min.y as i32..max.y as i32 {
+            for x in min.x as i32..max.x as i32 {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let dist = center.distance(*point);
+                let coverage = if self.anti_aliasing {
+                    (radius + 0.5 - dist).clamp(0.0, 1.0)
+                } else if dist <= radius || center.floor() == point.floor() {
+                    1.0
+                } else {
+                    0.0
+                };
+                if coverage > 0.0 {
+                    self.pixel_max_rgb(&center, color, coverage);
+                }
+            }
+        }
+    }
+
+    /**
+     * record the circles, lines, curves and paths drawn into an svg document,
+     * it's emptied with the color buffer.
+     */
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = match recording {
+            true => Some(SvgDocument::new(
+                self.width,
+                self.height,
+                utils::triangle::Rgb(0, 0, 0),
+            )),
+            false => None,
+        };
+    }
+
+    pub fn svg(&self) -> Option<&SvgDocument> {
+        self.recording.as_ref()
+    }
+
+    // draw the points of a curve, recorded as one polyline
+    pub fn draw_curve_points(&mut self, points: &[Vec2], color: &utils::triangle::Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.polyline(points, *color, self.stroke_width);
+        }
+        for point in points {
+            self.draw_curve_point(point, color);
+        }
+    }
+
     pub fn clear(&mut self, buffers: Buffers) {
         if buffers.contains(Buffers::COLOR) {
             self.frame_buf.fill(utils::triangle::Rgb(0, 0, 0));
+            if let Some(svg) = self.recording.as_mut() {
+                svg.clear();
+            }
+        }
+    }
+
+    // draw a line with the dda algorithm
+    pub fn draw_line(&mut self, from: Vec2, to: Vec2, color: &utils::triangle::Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.line(from, to, *color, 1.0);
+        }
+        let d = to - from;
+        let steps = d.x.abs().max(d.y.abs()).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let point = from + d * (i as f32 / steps as f32);
+            self.set_pixel(&point, color);
         }
     }
 
     pub fn draw_circle(&mut self, center: Vec2, radius: f32) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.circle(center, radius, utils::triangle::Rgb(255, 255, 255));
+        }
         let max_x = (center.x + radius) as u32 + 1;
         let min_x = (center.x - radius) as u32;
         let max_y = (center.y + radius) as u32 + 1;
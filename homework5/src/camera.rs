@@ -0,0 +1,170 @@
+use std::f32::consts::FRAC_PI_4;
+
+use glam::{Vec2, Vec3};
+
+/**
+ * a perspective camera at position looking at look_at,
+ * with a thin lens focusing at focus_distance when aperture > 0.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: Vec3,
+    pub look_at: Vec3,
+    pub up: Vec3,
+    // vertical field of view in degrees
+    pub fov: f32,
+    // width / height of the image
+    pub aspect: f32,
+    // diameter of the lens, 0 for a pinhole camera with everything in focus
+    pub aperture: f32,
+    // distance along the view direction of the plane in focus
+    pub focus_distance: f32,
+}
+
+impl Camera {
+    // a pinhole camera, focused at look_at
+    pub fn new(position: Vec3, look_at: Vec3, up: Vec3, fov: f32, aspect: f32) -> Self {
+        Self {
+            position,
+            look_at,
+            up,
+            fov,
+            aspect,
+            aperture: 0.0,
+            focus_distance: (look_at - position).length(),
+        }
+    }
+
+    pub fn with_depth_of_field(mut self, aperture: f32, focus_distance: f32) -> Self {
+        self.aperture = aperture;
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    pub fn is_pinhole(&self) -> bool {
+        self.aperture <= 0.0
+    }
+
+    // right, up and forward unit vectors of the camera.
+    // looking along up, any right orthogonal to the view direction does
+    fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = (self.look_at - self.position).normalize();
+        let right = forward.cross(self.up);
+        let right = match right.length_squared() > f32::EPSILON {
+            true => right.normalize(),
+            false => forward.any_orthonormal_vector(),
+        };
+        let up = right.cross(forward);
+        (right, up, forward)
+    }
+
+    /**
+     * the primary ray through a point of the image.
+     * @screen: 0..1 from the top left corner of the image, (0.5, 0.5) is the center
+     * @lens: uniform in 0..1, mapped onto the lens disk
+     * return: the origin and the normalized direction of the ray
+     */
+    pub fn generate_ray(&self, screen: Vec2, lens: Vec2) -> (Vec3, Vec3) {
+        let (right, up, forward) = self.basis();
+        let scale = (self.fov * 0.5).to_radians().tan();
+        // where the ray arrives on the plane at distance 1, y is upside down
+        let x = (screen.x * 2.0 - 1.0) * scale * self.aspect;
+        let y = (1.0 - screen.y * 2.0) * scale;
+        let dir = (right * x + up * y + forward).normalize();
+        if self.is_pinhole() {
+            return (self.position, dir);
+        }
+
+        // every ray through the lens meets the pinhole ray on the focus plane
+        let focus_point = self.position + dir * (self.focus_distance / dir.dot(forward));
+        let disk = concentric_disk(lens) * self.aperture * 0.5;
+        let origin = self.position + right * disk.x + up * disk.y;
+        (origin, (focus_point - origin).normalize())
+    }
+}
+
+// map the unit square onto the unit disk, keeping strata apart (Shirley and Chiu)
+fn concentric_disk(u: Vec2) -> Vec2 {
+    let offset = u * 2.0 - Vec2::ONE;
+    if offset == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    let (r, theta) = match offset.x.abs() > offset.y.abs() {
+        true => (offset.x, FRAC_PI_4 * (offset.y / offset.x)),
+        false => (
+            offset.y,
+            2.0 * FRAC_PI_4 - FRAC_PI_4 * (offset.x / offset.y),
+        ),
+    };
+    r * Vec2::new(theta.cos(), theta.sin())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate_ray() {
+        // the camera the renderer used to have: at the origin looking down -z
+        let camera = Camera::new(Vec3::ZERO, -Vec3::Z, Vec3::Y, 90.0, 2.0);
+        let (origin, dir) = camera.generate_ray(Vec2::splat(0.5), Vec2::ZERO);
+        assert_eq!(origin, Vec3::ZERO);
+        assert!(dir.abs_diff_eq(-Vec3::Z, 1e-6));
+        // the top right corner, tan(45) = 1
+        let (_, dir) = camera.generate_ray(Vec2::new(1.0, 0.0), Vec2::ZERO);
+        assert!(dir.abs_diff_eq(Vec3::new(2.0, 1.0, -1.0).normalize(), 1e-6));
+
+        // anywhere else, the center ray goes to look_at
+        let camera = Camera::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(4.0, 2.0, 3.0),
+            Vec3::Y,
+            60.0,
+            1.0,
+        );
+        let (_, dir) = camera.generate_ray(Vec2::splat(0.5), Vec2::ZERO);
+        assert!(dir.abs_diff_eq(Vec3::X, 1e-6));
+        let (_, dir) = camera.generate_ray(Vec2::new(0.5, 0.0), Vec2::ZERO);
+        assert!(dir.y > 0.0);
+    }
+
+    #[test]
+    fn test_looking_along_up() {
+        // straight down with y up, the view direction gives no right
+        let camera = Camera::new(Vec3::new(0.0, 5.0, 0.0), Vec3::ZERO, Vec3::Y, 90.0, 1.0);
+        let (right, up, forward) = camera.basis();
+        assert!(forward.abs_diff_eq(-Vec3::Y, 1e-6));
+        assert!(right.is_normalized() && up.is_normalized());
+        assert!(right.dot(forward).abs() < 1e-6 && up.dot(forward).abs() < 1e-6);
+        assert!(right.dot(up).abs() < 1e-6);
+
+        let (_, dir) = camera.generate_ray(Vec2::splat(0.5), Vec2::ZERO);
+        assert!(dir.abs_diff_eq(-Vec3::Y, 1e-6));
+        let (_, dir) = camera.generate_ray(Vec2::new(1.0, 0.0), Vec2::ZERO);
+        assert!(dir.is_finite());
+        assert!((dir.dot(forward) - 3.0f32.sqrt().recip()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        let camera =
+            Camera::new(Vec3::ZERO, -Vec3::Z, Vec3::Y, 90.0, 1.0).with_depth_of_field(0.5, 4.0);
+        let screen = Vec2::new(0.3, 0.8);
+        let pinhole = Camera {
+            aperture: 0.0,
+            ..camera
+        };
+        let (_, pinhole_dir) = pinhole.generate_ray(screen, Vec2::ZERO);
+        let focus_point = pinhole_dir * (4.0 / pinhole_dir.dot(-Vec3::Z));
+
+        for lens in [Vec2::ZERO, Vec2::ONE, Vec2::new(0.2, 0.9), Vec2::splat(0.5)] {
+            let (origin, dir) = camera.generate_ray(screen, lens);
+            // within the lens, on the plane of the camera
+            assert!(origin.z.abs() < 1e-6);
+            assert!(origin.length() <= 0.25 + 1e-6);
+            // and through the same point in focus
+            let t = (focus_point - origin).length();
+            assert!((origin + dir * t).abs_diff_eq(focus_point, 1e-4));
+        }
+    }
+}
@@ -2,5 +2,6 @@ pub mod scene;
 pub mod sphere;
 pub mod triangle;
 pub mod object;
-pub mod light;
+pub use utils::light;
 pub mod renderer;
+pub mod camera;
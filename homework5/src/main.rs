@@ -1,7 +1,16 @@
+use anyhow::Result;
 use glam::{Vec2, Vec3};
-use homework5::{light, object::MaterialType, scene, sphere, triangle, renderer};
+use homework5::{
+    camera::Camera,
+    light,
+    object::MaterialType,
+    renderer::{self, Output},
+    scene, sphere, triangle,
+};
 
-fn main() {
+fn main() -> Result<()> {
+    // a pinhole camera with everything in focus, or a lens focused on the glass sphere
+    let depth_of_field = false;
     let mut scene = scene::Scene::new(1280, 960);
 
     let mut sph1 = sphere::Sphere::new(&Vec3::new(-1.0, 0.0, -12.0), 2.0);
@@ -42,6 +51,20 @@ fn main() {
         &Vec3::new(30.0, 50.0, -12.0),
         0.5,
     )));
-    let r = renderer::Renderer{};
-    r.render(&scene)
+
+    // at the origin looking down -z
+    let camera = Camera::new(
+        Vec3::ZERO,
+        -Vec3::Z,
+        Vec3::Y,
+        90.0,
+        scene.width as f32 / scene.height as f32,
+    );
+    let camera = match depth_of_field {
+        true => camera.with_depth_of_field(0.2, 8.0),
+        false => camera,
+    };
+    let r = renderer::Renderer::new(camera, Output::File(String::from("output.png")));
+    r.render(&scene)?;
+    Ok(())
 }
@@ -36,7 +36,7 @@ impl ObjectRenderPayload {
 }
 
 // representing the interfaces of virtual class `Object` in cpp codes
-pub trait Object {
+pub trait Object: Send + Sync {
     // intersect on orig + t * dir
     fn intersect(
         &self,
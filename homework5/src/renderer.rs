@@ -1,8 +1,12 @@
 use std::ops::{Mul, Neg};
 
+use anyhow::Result;
 use glam::{Vec2, Vec3};
+use utils::{parallel::render_tiles, progress::ProgressReporter};
 
 use crate::{
+    camera::Camera,
+    light::light_samples,
     object::{self, MaterialType},
     scene,
 };
@@ -14,7 +18,24 @@ pub struct HitPayload<'a> {
     pub hit_obj: &'a Box<dyn object::Object>,
 }
 
-pub struct Renderer {}
+// where the rendered image goes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    // saved as an image file
+    File(String),
+    // only returned by render
+    Buffer,
+}
+
+pub struct Renderer {
+    pub camera: Camera,
+    pub output: Output,
+    // with depth of field, every pixel is sampled on an n x n grid over the lens
+    pub lens_samples: u32,
+    // threads rendering the tiles, 0 for a thread per core
+    pub threads: usize,
+    pub progress: ProgressReporter,
+}
 
 #[inline]
 fn reflect(i: Vec3, n: Vec3) -> Vec3 {
@@ -234,30 +255,35 @@ fn cast_ray(orig: &Vec3, dir: &Vec3, scene: &scene::Scene, depth: u32) -> Vec3 {
             // We also apply the lambert cosine law
             // [/comment]
             for light in scene.get_lights() {
-                let light_dir = light.position() - hit_point;
-                // square of the distance between hitPoint and the light
-                let light_distance_2 = light_dir.dot(light_dir);
-                let light_dir = light_dir.normalize();
-                let l_dot_n = light_dir.dot(n).max(0.0);
-                // is the point in shadow, and is the nearest occluding object closer to the object than the light itself?
-                let shadow_res = trace(&shadow_point_orig, &light_dir, scene.get_objects());
-                let in_shadow = match shadow_res {
-                    Some(shadow_res) => shadow_res.t_near * shadow_res.t_near < light_distance_2,
-                    None => false,
-                };
-
-                light_amt = match in_shadow {
-                    true => light_amt + Vec3::ZERO,
-                    false => light_amt + light.intensity() * l_dot_n,
-                };
-
-                let reflection_direction = reflect(-light_dir, n);
-                specular_color += reflection_direction
-                    .dot(*dir)
-                    .neg()
-                    .max(0.0)
-                    .powf(obj_payload.specular_exponent)
-                    .mul(light.intensity());
+                for (u, weight) in light_samples(light.as_ref(), scene.area_light_samples) {
+                    let sample = match light.sample(hit_point, u) {
+                        Some(sample) => sample,
+                        None => continue,
+                    };
+                    // the light arriving from the sample, averaged over the samples
+                    let light_intensity = sample.radiance / sample.pdf * weight;
+                    let light_dir = sample.direction;
+                    let l_dot_n = light_dir.dot(n).max(0.0);
+                    // is the point in shadow, and is the nearest occluding object closer to the object than the light itself?
+                    let shadow_res = trace(&shadow_point_orig, &light_dir, scene.get_objects());
+                    let in_shadow = match shadow_res {
+                        Some(shadow_res) => shadow_res.t_near < sample.distance,
+                        None => false,
+                    };
+
+                    light_amt = match in_shadow {
+                        true => light_amt + Vec3::ZERO,
+                        false => light_amt + light_intensity * l_dot_n,
+                    };
+
+                    let reflection_direction = reflect(-light_dir, n);
+                    specular_color += reflection_direction
+                        .dot(*dir)
+                        .neg()
+                        .max(0.0)
+                        .powf(obj_payload.specular_exponent)
+                        .mul(light_intensity);
+                }
             }
 
             hit_color = light_amt * payload.hit_obj.eval_diffuse_color(&st) * obj_payload.kd
@@ -268,74 +294,62 @@ fn cast_ray(orig: &Vec3, dir: &Vec3, scene: &scene::Scene, depth: u32) -> Vec3 {
     return hit_color;
 }
 
-fn update_progress(progress: f32) {
-    let bar_width = 70;
-    let pos = ((bar_width as f32 * progress) as usize).clamp(0, bar_width);
-    let mut strs = String::from("[");
-    strs.push_str(&String::from("=").repeat(pos));
-    if pos < bar_width {
-        strs.push('>');
-        strs.push_str(&String::from("_").repeat(bar_width - pos - 1));
+impl Renderer {
+    pub fn new(camera: Camera, output: Output) -> Self {
+        Self {
+            camera,
+            output,
+            lens_samples: 4,
+            threads: 0,
+            progress: ProgressReporter::new(),
+        }
     }
-    strs.push_str("] ");
-    strs.push_str(&format!("{}%", (progress * 100.0) as u32));
-
-    println!("{}", strs);
-}
-
-#[inline]
-fn get_buffer_index(height: usize, width: usize, x: usize, y: usize) -> usize {
-    let x = x.clamp(0, width);
-    let y = y.clamp(0, height);
-    y * width + x
-}
 
-impl Renderer {
     // [comment]
     // The main render function. This where we iterate over all pixels in the image, generate
     // primary rays and cast these rays into the scene. The content of the framebuffer is
-    // saved to a file.
+    // written to the output and returned.
     // [/comment]
-    pub fn render(&self, scene: &scene::Scene) {
-        let mut frame_buffer = vec![Vec3::ZERO; scene.width * scene.height];
-        let scale = (scene.fov * 0.5).to_radians().tan();
-        let image_aspect_ratio = (scene.width as f32) / (scene.height as f32);
-
-        // Use this variable as the eye position to start your rays.
-        let eye_pos = Vec3::ZERO;
-        // j represent the height value, which 0 on the top
-        for j in 0..scene.height {
-            // i represent the width value
-            for i in 0..scene.width {
-                // generate primary ray direction
-                // TODO: Find the x and y positions of the current pixel to get the direction
-                // vector that passes through it.
-                // Also, don't forget to multiply both of them with the variable *scale*, and
-                // x (horizontal) variable with the *imageAspectRatio*
-
-                // x and y is the position where ray arrived on z = -1
-                // and aware that y is upside down
-                let y =
-                    -(j as f32 + 0.5 - scene.height as f32 / 2.0) / (scene.height as f32 / 2.0) * scale;
-                let x = (i as f32 + 0.5 - scene.width as f32 / 2.0) / (scene.width as f32 / 2.0)
-                    * scale
-                    * image_aspect_ratio;
-
-                let dir = Vec3::new(x, y, -1.0) // Don't forget to normalize this direction!
-                    .normalize();
-                let buf_index = get_buffer_index(scene.height, scene.width, i, j);
-                frame_buffer[buf_index] = cast_ray(&eye_pos, &dir, scene, 0);
-            }
-            update_progress((j as f32) / (scene.height as f32));
-        }
-
-        // save framebuffer to file
-        let r = utils::rasterizer::BufRasterizer::from_vec3s(
-            frame_buffer,
-            scene.width as u32,
-            scene.height as u32,
+    pub fn render(&self, scene: &scene::Scene) -> Result<Vec<Vec3>> {
+        let n = match self.camera.is_pinhole() {
+            true => 1,
+            false => self.lens_samples.max(1),
+        };
+        self.progress.begin();
+        // i represent the width value, j the height value which 0 on the top
+        let frame_buffer = render_tiles(
+            scene.width,
+            scene.height,
+            self.threads,
+            |i, j| {
+                let screen = Vec2::new(
+                    (i as f32 + 0.5) / scene.width as f32,
+                    (j as f32 + 0.5) / scene.height as f32,
+                );
+                self.progress.add_rays((n * n) as u64);
+                let mut color = Vec3::ZERO;
+                for k in 0..n * n {
+                    let lens = Vec2::new(
+                        ((k % n) as f32 + 0.5) / n as f32,
+                        ((k / n) as f32 + 0.5) / n as f32,
+                    );
+                    let (orig, dir) = self.camera.generate_ray(screen, lens);
+                    color += cast_ray(&orig, &dir, scene, 0);
+                }
+                color / (n * n) as f32
+            },
+            |fraction| self.progress.update(fraction),
         );
-        utils::graphic::save_image(&r, "output.png").expect("save image error");
+
+        if let Output::File(path) = &self.output {
+            let r = utils::rasterizer::BufRasterizer::from_vec3s(
+                frame_buffer.clone(),
+                scene.width as u32,
+                scene.height as u32,
+            );
+            utils::graphic::save_image(&r, path)?;
+        }
+        Ok(frame_buffer)
     }
 }
 
@@ -343,11 +357,57 @@ impl Renderer {
 mod test {
     use super::*;
     #[test]
-    fn test_update_progress() {
-        update_progress(0.0);
-        update_progress(0.1);
-        update_progress(0.5);
-        update_progress(0.9);
-        update_progress(1.0);
+    fn test_render_camera() {
+        let mut scene = scene::Scene::new(16, 16);
+        scene.add_object(Box::new(crate::sphere::Sphere::new(&Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        let background = scene.background_color;
+        let center = 8 * 16 + 8;
+
+        let camera = Camera::new(Vec3::ZERO, Vec3::new(0.0, 0.0, -5.0), Vec3::Y, 90.0, 1.0);
+        let r = Renderer::new(camera, Output::Buffer);
+        let frame = r.render(&scene).unwrap();
+        assert_ne!(frame[center], background);
+        assert_eq!(frame[0], background);
+
+        // looking away from the sphere
+        let camera = Camera::new(Vec3::ZERO, Vec3::new(0.0, 0.0, 5.0), Vec3::Y, 90.0, 1.0);
+        let frame = Renderer::new(camera, Output::Buffer).render(&scene).unwrap();
+        assert_eq!(frame[center], background);
+
+        // the sphere in focus looks the same through a lens
+        let camera = Camera::new(Vec3::ZERO, Vec3::new(0.0, 0.0, -4.0), Vec3::Y, 90.0, 1.0)
+            .with_depth_of_field(0.2, 4.0);
+        let frame = Renderer::new(camera, Output::Buffer).render(&scene).unwrap();
+        assert_ne!(frame[center], background);
+
+        // the same image on any number of threads
+        let mut r = Renderer::new(camera, Output::Buffer);
+        r.threads = 1;
+        let serial = r.render(&scene).unwrap();
+        r.threads = 3;
+        assert_eq!(r.render(&scene).unwrap(), serial);
+        assert_eq!(serial, frame);
+    }
+
+    #[test]
+    fn test_render_progress() {
+        let mut scene = scene::Scene::new(20, 10);
+        scene.add_object(Box::new(crate::sphere::Sphere::new(&Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        let camera = Camera::new(Vec3::ZERO, -Vec3::Z, Vec3::Y, 90.0, 2.0).with_depth_of_field(0.1, 5.0);
+        let mut r = Renderer::new(camera, Output::Buffer);
+        r.lens_samples = 2;
+
+        let last = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let observed = last.clone();
+        r.progress = ProgressReporter::new()
+            .quiet()
+            .with_callback(move |p| *observed.lock().unwrap() = Some(*p));
+        r.render(&scene).unwrap();
+
+        let last = last.lock().unwrap().unwrap();
+        assert_eq!(last.fraction, 1.0);
+        assert_eq!(last.eta, Some(std::time::Duration::ZERO));
+        // 2 x 2 rays through the lens for every pixel
+        assert_eq!(last.rays, 20 * 10 * 4);
     }
 }
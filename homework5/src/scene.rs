@@ -7,10 +7,11 @@ pub struct Scene {
 
     pub width: usize,
     pub height: usize,
-    pub fov: f32,
     pub background_color: Vec3,
     pub max_depth: u32,
     pub epsilon: f32,
+    // area lights are sampled on an n x n grid
+    pub area_light_samples: u32,
 }
 
 impl Scene {
@@ -20,7 +21,6 @@ impl Scene {
             lights: vec![],
             width: w,
             height: h,
-            fov: 90.0,
             background_color: Vec3 {
                 x: 0.235294,
                 y: 0.67451,
@@ -28,6 +28,7 @@ impl Scene {
             },
             max_depth: 5,
             epsilon: 0.00001,
+            area_light_samples: 4,
         }
     }
 
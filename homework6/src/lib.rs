@@ -1,5 +1,5 @@
 pub mod scene;
-pub mod light;
+pub use utils::light;
 mod ray;
 pub mod object;
 mod bvh;
@@ -1,5 +1,6 @@
 use glam::Vec3;
 use homework6::{light::PointLight, renderer::Renderer, scene::Scene, triangle::MeshTriangle};
+use utils::progress::ProgressReporter;
 
 fn main() {
     let mut scene = Scene::new(1280, 960);
@@ -17,6 +18,9 @@ fn main() {
 
     scene.build_bvh(object_holder);
 
-    let r = Renderer {};
+    let r = Renderer {
+        threads: 0,
+        progress: ProgressReporter::new(),
+    };
     r.render(&scene);
 }
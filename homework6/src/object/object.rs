@@ -6,7 +6,7 @@ use crate::ray::Ray;
 use crate::object::intersection::Intersection;
 
 // representing the interfaces of virtual class `Object` in cpp codes
-pub trait Object {
+pub trait Object: Send + Sync {
     // intersect on orig + t * dir
     // intersect(ray:&Ray) -> bool
     fn intersect(
@@ -2,7 +2,7 @@ use std::ops::{Mul, Neg};
 
 use crate::{
     bvh::BVHAccel,
-    light::Light,
+    light::{light_samples, Light},
     object::{intersection::Intersection, material::MaterialType, object::Object},
     ray::Ray,
 };
@@ -28,6 +28,8 @@ pub struct Scene {
     pub background_color: Vec3,
     pub max_depth: u32,
     pub epsilon: f32,
+    // area lights are sampled on an n x n grid
+    pub area_light_samples: u32,
 
     bvh: Option<BVHAccel>,
 }
@@ -46,6 +48,7 @@ impl Scene {
             },
             max_depth: 5,
             epsilon: 0.00001,
+            area_light_samples: 4,
 
             bvh: None,
         }
@@ -160,33 +163,37 @@ impl Scene {
                 // We also apply the lambert cosine law
                 // [/comment]
                 for light in self.get_lights() {
-                    // do something with area light here for next assignment
-
-                    let light_dir = light.position() - hit_point;
-                    // square of the distance between hitPoint and the light
-                    let light_distance_2 = light_dir.dot(light_dir);
-                    let light_dir = light_dir.normalize();
-                    let l_dot_n = light_dir.dot(n).max(0.0);
-
-                    // is the point in shadow, and is the nearest occluding object closer to the object than the light itself?
-                    let in_shadow = self
-                        .bvh
-                        .as_ref()
-                        .unwrap()
-                        .intersect(&Ray::new(shadow_point_orig, light_dir));
-
-                    light_amt = match in_shadow {
-                        Some(_) => light_amt + Vec3::ZERO,
-                        None => light_amt + light.intensity() * l_dot_n,
-                    };
-
-                    let reflection_direction = reflect(-light_dir, n);
-                    specular_color += reflection_direction
-                        .dot(ray.direction)
-                        .neg()
-                        .max(0.0)
-                        .powf(m.specular_exponent)
-                        .mul(light.intensity());
+                    for (u, weight) in light_samples(light.as_ref(), self.area_light_samples) {
+                        let sample = match light.sample(hit_point, u) {
+                            Some(sample) => sample,
+                            None => continue,
+                        };
+                        // the light arriving from the sample, averaged over the samples
+                        let light_intensity = sample.radiance / sample.pdf * weight;
+                        let light_dir = sample.direction;
+                        let l_dot_n = light_dir.dot(n).max(0.0);
+
+                        // is the point in shadow, and is the nearest occluding object closer to the object than the light itself?
+                        let in_shadow = self
+                            .bvh
+                            .as_ref()
+                            .unwrap()
+                            .intersect(&Ray::new(shadow_point_orig, light_dir))
+                            .filter(|i| i.distance < sample.distance);
+
+                        light_amt = match in_shadow {
+                            Some(_) => light_amt + Vec3::ZERO,
+                            None => light_amt + light_intensity * l_dot_n,
+                        };
+
+                        let reflection_direction = reflect(-light_dir, n);
+                        specular_color += reflection_direction
+                            .dot(ray.direction)
+                            .neg()
+                            .max(0.0)
+                            .powf(m.specular_exponent)
+                            .mul(light_intensity);
+                    }
                 }
 
                 hit_color =
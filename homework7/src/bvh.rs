@@ -1,8 +1,9 @@
 use std::{rc::Rc, sync::Arc, time::Instant};
 
+use glam::Vec2;
+
 use crate::{
     bounds3::{Bounds3, Dimension},
-    global::get_random_float,
     object::{
         intersection::{Intersection, SampleResult},
         object::Object,
@@ -62,18 +63,22 @@ impl BVHBuildNode {
     }
 
     // note: make pos and pdf as option return would be more simple here too
-    pub fn get_sample(&self, p: f32) -> Option<SampleResult> {
+    // p: in 0..area, where on the areas of the objects in the node to sample
+    // v: the second dimension of the sample on the object
+    pub fn get_sample(&self, p: f32, v: f32) -> Option<SampleResult> {
         match &self.content {
             NodeContent::Leaf { object } => {
-                let mut res = object.sample();
+                // p within the object is uniform again
+                let u = Vec2::new((p / self.area).clamp(0.0, 1.0 - f32::EPSILON), v);
+                let mut res = object.sample(u);
                 res.as_mut()?.pdf *= self.area;
                 return res;
             }
             NodeContent::BiNode { left, right } => {
                 if p < left.area {
-                    left.get_sample(p)
+                    left.get_sample(p, v)
                 } else {
-                    right.get_sample(p)
+                    right.get_sample(p - left.area, v)
                 }
             }
         }
@@ -191,14 +196,75 @@ impl BVHAccel {
     }
 
     // note: making pos and pdf as result whould be more simple here
-    pub fn sample(&self) -> Option<SampleResult> {
+    // an object chosen on the weight of area by u.x, then sampled
+    pub fn sample(&self, u: Vec2) -> Option<SampleResult> {
         let root = match &self.root {
             Some(r) => r,
             None => return None,
         };
-        let p = get_random_float().sqrt() * root.area;
-        let mut res = root.get_sample(p);
+        let p = u.x * root.area;
+        let mut res = root.get_sample(p, u.y);
         res.as_mut()?.pdf /= root.area;
         return res;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{
+        object::material::{Material, MaterialType},
+        triangle::Triangle,
+    };
+
+    #[test]
+    fn test_sample_uniform_on_area() {
+        // right triangles of area 1, 2 and 4, apart along x
+        let m = Arc::new(Material::new(MaterialType::Diffuse, Vec3::ZERO, Vec3::ZERO));
+        let triangles: Vec<(Vec3, f32)> = [1.0f32, 2.0, 4.0]
+            .iter()
+            .enumerate()
+            .map(|(i, area)| (Vec3::new(10.0 * i as f32, 0.0, 0.0), *area))
+            .collect();
+        let objects: Vec<Arc<dyn Object>> = triangles
+            .iter()
+            .map(|(corner, area)| {
+                let leg = (2.0 * area).sqrt();
+                Arc::new(Triangle::new(
+                    *corner,
+                    *corner + Vec3::X * leg,
+                    *corner + Vec3::Y * leg,
+                    m.clone(),
+                )) as Arc<dyn Object>
+            })
+            .collect();
+        let bvh = BVHAccel::new(objects);
+
+        // a stratified grid, every triangle gets its share of the area,
+        // and the samples on it are centered on its centroid
+        let n = 64;
+        let mut counts = [0; 3];
+        let mut sums = [Vec3::ZERO; 3];
+        for i in 0..n * n {
+            let u = Vec2::new(
+                ((i % n) as f32 + 0.5) / n as f32,
+                ((i / n) as f32 + 0.5) / n as f32,
+            );
+            let s = bvh.sample(u).unwrap();
+            assert!((s.pdf - 1.0 / 7.0).abs() < 1e-5, "{}", s.pdf);
+            let k = (s.coords.x / 10.0) as usize;
+            counts[k] += 1;
+            sums[k] += s.coords;
+        }
+        for (k, (corner, area)) in triangles.iter().enumerate() {
+            let share = counts[k] as f32 / (n * n) as f32;
+            assert!((share - area / 7.0).abs() < 0.01, "{} {}", k, share);
+            let leg = (2.0 * area).sqrt();
+            let centroid = *corner + Vec3::new(leg, leg, 0.0) / 3.0;
+            let mean = sums[k] / counts[k] as f32;
+            assert!(mean.abs_diff_eq(centroid, 0.02 * leg), "{} {}", k, mean);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use glam::Vec2;
+
+/**
+ * how much a sample counts for the pixels around it, by its offset from their centers in pixels.
+ * every filter is the product of the same 1D filter on x and y.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    // a sample only counts for the pixels within radius, all the same
+    Box { radius: f32 },
+    // falls off linearly to 0 at radius
+    Tent { radius: f32 },
+    // exp(-alpha x^2), shifted to reach 0 at radius
+    Gaussian { radius: f32, alpha: f32 },
+    // the cubic of Mitchell and Netravali, with negative lobes, b = c = 1/3 looks the best
+    Mitchell { radius: f32, b: f32, c: f32 },
+}
+
+impl Default for Filter {
+    // every sample only counts for its own pixel
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn gaussian() -> Self {
+        Filter::Gaussian {
+            radius: 1.5,
+            alpha: 2.0,
+        }
+    }
+
+    pub fn mitchell() -> Self {
+        Filter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => 1.0 - x / radius,
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // the cubic is defined on 0..2
+                let t = 2.0 * x / radius;
+                let value = match t > 1.0 {
+                    true => {
+                        (-b - 6.0 * c) * t.powi(3)
+                            + (6.0 * b + 30.0 * c) * t.powi(2)
+                            + (-12.0 * b - 48.0 * c) * t
+                            + (8.0 * b + 24.0 * c)
+                    }
+                    false => {
+                        (12.0 - 9.0 * b - 6.0 * c) * t.powi(3)
+                            + (-18.0 + 12.0 * b + 6.0 * c) * t.powi(2)
+                            + (6.0 - 2.0 * b)
+                    }
+                };
+                value / 6.0
+            }
+        }
+    }
+
+    pub fn evaluate(&self, offset: Vec2) -> f32 {
+        self.evaluate_1d(offset.x) * self.evaluate_1d(offset.y)
+    }
+
+    /**
+     * the pixels a sample at position (in pixels from the top left corner) counts for,
+     * with the weight for each, as (x, y, weight).
+     */
+    pub fn splat(&self, position: Vec2, width: usize, height: usize) -> Vec<(usize, usize, f32)> {
+        let r = self.radius();
+        // pixel centers are at + 0.5, those at -r exactly are left to the pixel before
+        let range = |p: f32, size: usize| {
+            let first = ((p - r - 0.5).floor() as i64 + 1).max(0);
+            let last = ((p + r - 0.5).floor() as i64).min(size as i64 - 1);
+            first..=last
+        };
+        let mut weights = vec![];
+        for y in range(position.y, height) {
+            for x in range(position.x, width) {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weight = self.evaluate(center - position);
+                if weight != 0.0 {
+                    weights.push((x as usize, y as usize, weight));
+                }
+            }
+        }
+        weights
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        for filter in [
+            Filter::default(),
+            Filter::Tent { radius: 1.0 },
+            Filter::gaussian(),
+            Filter::mitchell(),
+        ] {
+            let r = filter.radius();
+            assert!(filter.evaluate(Vec2::ZERO) > 0.0);
+            assert_eq!(filter.evaluate(Vec2::new(r + 0.01, 0.0)), 0.0);
+            // symmetric, and not increasing away from the center (until the negative lobe)
+            assert_eq!(
+                filter.evaluate(Vec2::new(0.3, -0.2)),
+                filter.evaluate(Vec2::new(-0.3, 0.2))
+            );
+            assert!(filter.evaluate(Vec2::new(0.1, 0.0)) >= filter.evaluate(Vec2::new(0.4, 0.0)));
+        }
+        assert_eq!(
+            Filter::Tent { radius: 2.0 }.evaluate(Vec2::new(1.0, 1.0)),
+            0.25
+        );
+        // the mitchell filter reaches 0 smoothly at its radius, and is negative before
+        let mitchell = Filter::mitchell();
+        assert!(mitchell.evaluate(Vec2::new(1.99, 0.0)).abs() < 1e-4);
+        assert!(mitchell.evaluate(Vec2::new(1.5, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn test_splat() {
+        // a box of radius 0.5 only counts for the pixel of the sample
+        let splat = Filter::default().splat(Vec2::new(3.2, 4.7), 10, 10);
+        assert_eq!(splat, vec![(3, 4, 1.0)]);
+        let splat = Filter::default().splat(Vec2::new(3.0, 4.0), 10, 10);
+        assert_eq!(splat, vec![(3, 4, 1.0)]);
+
+        // a tent of radius 1 counts for the 2 x 2 pixels with centers around the sample
+        let splat = Filter::Tent { radius: 1.0 }.splat(Vec2::new(3.2, 4.7), 10, 10);
+        let pixels: Vec<(usize, usize)> = splat.iter().map(|(x, y, _)| (*x, *y)).collect();
+        assert_eq!(pixels, vec![(2, 4), (3, 4), (2, 5), (3, 5)]);
+        let sum: f32 = splat.iter().map(|(_, _, w)| w).sum();
+        assert!((sum - 1.0).abs() < 1e-5);
+
+        // nothing outside the image
+        let splat = Filter::gaussian().splat(Vec2::new(0.1, 9.9), 10, 10);
+        assert!(splat.iter().all(|(x, y, _)| *x < 10 && *y < 10));
+        let (_, _, w) = splat.iter().find(|(x, y, _)| (*x, *y) == (0, 9)).unwrap();
+        assert!((w - Filter::gaussian().evaluate(Vec2::new(0.4, -0.4))).abs() < 1e-5);
+    }
+}
@@ -1,5 +1,5 @@
 pub mod scene;
-pub mod light;
+pub use utils::light;
 mod ray;
 pub mod object;
 mod bvh;
@@ -7,3 +7,5 @@ pub mod triangle;
 pub mod renderer;
 pub mod bounds3;
 pub mod global;
+pub mod sampler;
+pub mod filter;
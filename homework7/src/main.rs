@@ -53,10 +53,7 @@ fn main() -> Result<()> {
     // no add light, add object light
     scene.build_bvh();
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 1,
-    };
+    let r = Renderer::new("fixed", 1);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -65,10 +62,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 2,
-    };
+    let r = Renderer::new("fixed", 2);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -77,10 +71,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 4,
-    };
+    let r = Renderer::new("fixed", 4);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -89,10 +80,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 8,
-    };
+    let r = Renderer::new("fixed", 8);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -101,10 +89,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 16,
-    };
+    let r = Renderer::new("fixed", 16);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -113,10 +98,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 64,
-    };
+    let r = Renderer::new("fixed", 64);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
@@ -125,10 +107,7 @@ fn main() -> Result<()> {
         start_time.elapsed().as_secs_f32()
     );
 
-    let r = Renderer {
-        prefix: String::from("fixed"),
-        spp: 1024,
-    };
+    let r = Renderer::new("fixed", 1024);
     let start_time = Instant::now();
     r.render(&scene);
     println!(
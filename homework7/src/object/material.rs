@@ -1,8 +1,8 @@
 use std::f32::consts::PI;
 
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 
-use crate::global::{self, get_random_float};
+use crate::global;
 
 // i is the incident ray, n is the normalized normal
 // i face to the surface, n face to the outside
@@ -136,12 +136,12 @@ impl Material {
         return self.m_emission.length() > global::EPSILON;
     }
 
-    // sample a ray by material properties
-    pub fn sameple(&self, wi: Vec3, n: Vec3) -> Vec3 {
+    // sample a ray by material properties, u is uniform in 0..1
+    pub fn sameple(&self, wi: Vec3, n: Vec3, u: Vec2) -> Vec3 {
         match self.m_type {
             MaterialType::Diffuse => {
                 // uniform sample on the hemisphere
-                let (x1, x2) = (get_random_float(), get_random_float());
+                let (x1, x2) = (u.x, u.y);
                 let z = (1.0 - 2.0 * x1).abs(); // the z value of dir
                 let r = (1.0 - z * z).sqrt(); // length of xy
                 let phi = 2.0 * PI * x2; // dir of xy
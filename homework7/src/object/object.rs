@@ -23,6 +23,7 @@ pub trait Object: Send + Sync {
     fn get_bounds(&self) -> &Bounds3;
     fn get_area(&self) -> f32;
     // return pos: Intersection, pdf: f32
-    fn sample(&self) -> Option<SampleResult>;
+    // u: uniform in 0..1, mapped onto the surface
+    fn sample(&self, u: Vec2) -> Option<SampleResult>;
     fn has_emit(&self) -> bool;
 }
@@ -8,7 +8,15 @@ use crossbeam_utils::thread;
 
 use glam::{Vec2, Vec3};
 
-use crate::{object::object::Object, ray::Ray, scene::Scene};
+use utils::{progress::ProgressReporter, rasterizer::RasterizableMut};
+
+use crate::{
+    filter::Filter,
+    object::object::Object,
+    ray::Ray,
+    sampler::{Sampler, StratifiedSampler},
+    scene::Scene,
+};
 
 pub struct HitPayload<'a> {
     pub t_near: f32,
@@ -20,102 +28,133 @@ pub struct HitPayload<'a> {
 pub struct Renderer {
     pub prefix: String,
     pub spp: i32,
+    pub progress: ProgressReporter,
+    // where the samples are in the pixels, and the numbers of their paths
+    pub sampler: Box<dyn Sampler>,
+    // how the samples are weighted into the pixels around them
+    pub filter: Filter,
 }
 
-fn update_progress(progress: f32) {
-    let bar_width = 70;
-    let pos = ((bar_width as f32 * progress) as usize).clamp(0, bar_width);
-    let mut strs = String::from("[");
-    strs.push_str(&String::from("=").repeat(pos));
-    if pos < bar_width {
-        strs.push('>');
-        strs.push_str(&String::from("_").repeat(bar_width - pos - 1));
-    }
-    strs.push_str("] ");
-    strs.push_str(&format!("{}%", (progress * 100.0) as u32));
-
-    println!("{}", strs);
-}
-
-#[inline]
-fn get_buffer_index(height: usize, width: usize, x: usize, y: usize) -> usize {
-    let x = x.clamp(0, width);
-    let y = y.clamp(0, height);
-    y * width + x
-}
+// box size: x: 0(right)..556(left) , y: 0(down)..548(up) , z: 0(out)..559(in)
+// while fov = 40.0 , so scale is 0.36
+const EYE_POS: Vec3 = Vec3::new(278.0, 273.0, -800.0);
 
 impl Renderer {
+    // jittered stratified samples, each counting for its own pixel only
+    pub fn new(prefix: &str, spp: i32) -> Self {
+        Self {
+            prefix: String::from(prefix),
+            spp,
+            progress: ProgressReporter::new(),
+            sampler: Box::new(StratifiedSampler::default()),
+            filter: Filter::default(),
+        }
+    }
+
     // [comment]
     // The main render function. This where we iterate over all pixels in the image, generate
     // primary rays and cast these rays into the scene. The content of the framebuffer is
     // saved to a file.
     // [/comment]
     pub fn render(&self, scene: &Scene) {
-        let mut frame_buffer = vec![Vec3::ZERO; scene.width * scene.height];
+        let frame_buffer = self.render_buffer(scene);
+
+        // save framebuffer to file
+        let spp = self.spp;
+        let eye_pos = EYE_POS;
+        let mut r = utils::rasterizer::BufRasterizer::from_vec3s(
+            frame_buffer,
+            scene.width as u32,
+            scene.height as u32,
+        );
+        r.draw_text(4, 4, &format!("spp {}", spp), utils::rgb::Rgb(255, 255, 255));
+        utils::graphic::save_image(
+            &r,
+            format!(
+                "{}-spp{}-{}-{}-{}-output.png",
+                self.prefix, spp, eye_pos.x, eye_pos.y, eye_pos.z
+            ),
+        )
+        .expect("save image error");
+    }
+
+    /**
+     * every pixel takes spp samples from the sampler, each traced along a path from the eye,
+     * the samples are weighted into the pixels around them by the filter.
+     * return: the pixels row by row, y facing down
+     */
+    pub fn render_buffer(&self, scene: &Scene) -> Vec<Vec3> {
+        let (width, height) = (scene.width, scene.height);
+        // kept by row and added up in row order, so the sums don't depend on which thread finishes first
+        let mut rows_splats: Vec<Option<RowSplats>> = (0..height).map(|_| None).collect();
         let (send, recv) = mpsc::channel();
 
         let scale = (scene.fov * 0.5).to_radians().tan();
-        let image_aspect_ratio = (scene.width as f32) / (scene.height as f32);
-
-        // Use this variable as the eye position to start your rays.
-        // let eye_pos = Vec3::new(278.0, 273.0, -800.0);
-        // box size: x: 0(right)..556(left) , y: 0(down)..548(up) , z: 0(out)..559(in)
-        // while fov = 40.0 , so scale is 0.36
-        let eye_pos = Vec3::new(278.0, 273.0, -800.0);
+        let image_aspect_ratio = (width as f32) / (height as f32);
+        // the rows around a row its samples count for
+        let reach = self.filter.radius().ceil() as usize;
 
         // change the spp value to change sample ammount
-        let spp = self.spp;
+        let spp = self.spp.max(1) as u32;
+        self.progress.begin();
 
         thread::scope(|s| {
             let mut handles = vec![];
 
             let handle = s.spawn(|_| {
                 let mut m = 0;
-                for CastRayResult { buf_index, res } in recv {
-                    if m % 40000 == 0 {
-                        update_progress((m as f32) / (frame_buffer.len() as f32));
-                    }
-                    frame_buffer[buf_index] += res;
+                for splats in recv {
+                    let RowSplats { row, .. } = splats;
+                    rows_splats[row] = Some(splats);
                     m += 1;
+                    // once every percent
+                    if m * 100 / height != (m - 1) * 100 / height {
+                        self.progress.update(m as f32 / height as f32);
+                    }
                 }
             });
             handles.push(handle);
 
             // j represent the height value, which 0 on the top
-            for j in 0..scene.height {
+            for j in 0..height {
                 let send = send.clone();
                 let handle = s.spawn(move |_| {
+                    let mut sampler = self.sampler.clone_box();
+                    let first_row = j.saturating_sub(reach);
+                    let rows = (j + reach).min(height - 1) + 1 - first_row;
+                    let mut splats = RowSplats {
+                        row: j,
+                        first_row,
+                        weighted: vec![Vec3::ZERO; rows * width],
+                        weights: vec![0.0; rows * width],
+                    };
+
                     // i represent the width value
-                    for i in 0..scene.width {
-                        // generate primary ray direction
-                        // TODO: Find the x and y positions of the current pixel to get the direction
-                        // vector that passes through it.
-                        // Also, don't forget to multiply both of them with the variable *scale*, and
-                        // x (horizontal) variable with the *imageAspectRatio*
-
-                        // x and y is the position where ray arrived on z = -1
-                        // and aware that y is upside down
-                        let y = -(j as f32 + 0.5 - scene.height as f32 / 2.0)
-                            / (scene.height as f32 / 2.0)
-                            * scale;
-                        let x = (i as f32 + 0.5 - scene.width as f32 / 2.0)
-                            / (scene.width as f32 / 2.0)
-                            * scale
-                            * image_aspect_ratio;
-
-                        let dir = Vec3::new(-x, y, 1.0) // Don't forget to normalize this direction!
-                            .normalize();
-                        let buf_index = get_buffer_index(scene.height, scene.width, i, j);
-                        let ray = Ray::new(eye_pos, dir);
-                        let mut res = Vec3::ZERO;
-                        for _ in 0..spp {
-                            res += scene.cast_ray(&ray, true) / spp as f32;
-                            // println!("after cast ray");
-                            // println!("after sent");
-                            // frame_buffer[buf_index] +=
+                    for i in 0..width {
+                        for k in 0..spp {
+                            sampler.start_sample((i, j), k, spp);
+                            let film = Vec2::new(i as f32, j as f32) + sampler.get_pixel_2d();
+
+                            // x and y is the position where ray arrived on z = 1
+                            // and aware that y is upside down
+                            let y = -(film.y - height as f32 / 2.0) / (height as f32 / 2.0) * scale;
+                            let x = (film.x - width as f32 / 2.0) / (width as f32 / 2.0)
+                                * scale
+                                * image_aspect_ratio;
+
+                            let dir = Vec3::new(-x, y, 1.0).normalize();
+                            let ray = Ray::new(EYE_POS, dir);
+                            let res = scene.cast_ray(&ray, true, sampler.as_mut());
+
+                            for (x, y, w) in self.filter.splat(film, width, height) {
+                                let index = (y - first_row) * width + x;
+                                splats.weighted[index] += res * w;
+                                splats.weights[index] += w;
+                            }
                         }
-                        send.send(CastRayResult { buf_index, res }).unwrap();
+                        self.progress.add_rays(spp as u64);
                     }
+                    send.send(splats).unwrap();
                 });
                 handles.push(handle);
             }
@@ -128,37 +167,118 @@ impl Renderer {
         })
         .unwrap();
 
-        // save framebuffer to file
-        let r = utils::rasterizer::BufRasterizer::from_vec3s(
-            frame_buffer,
-            scene.width as u32,
-            scene.height as u32,
-        );
-        utils::graphic::save_image(
-            &r,
-            format!(
-                "{}-spp{}-{}-{}-{}-output.png",
-                self.prefix, spp, eye_pos.x, eye_pos.y, eye_pos.z
-            ),
-        )
-        .expect("save image error");
+        let mut weighted = vec![Vec3::ZERO; width * height];
+        let mut weights = vec![0.0f32; width * height];
+        for splats in rows_splats.into_iter().flatten() {
+            let start = splats.first_row * width;
+            for (k, (w, l)) in splats.weights.iter().zip(splats.weighted).enumerate() {
+                weighted[start + k] += l;
+                weights[start + k] += w;
+            }
+        }
+
+        weighted
+            .into_iter()
+            .zip(weights)
+            .map(|(l, w)| match w > 0.0 {
+                // negative lobes may take it below 0
+                true => (l / w).max(Vec3::ZERO),
+                false => Vec3::ZERO,
+            })
+            .collect()
     }
 }
 
-struct CastRayResult {
-    pub buf_index: usize,
-    pub res: Vec3,
+// the samples of a row of pixels, weighted into the rows around it
+struct RowSplats {
+    // the row the samples are taken in
+    pub row: usize,
+    pub first_row: usize,
+    // the sum of weight * radiance and of the weights for every pixel of the rows
+    pub weighted: Vec<Vec3>,
+    pub weights: Vec<f32>,
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
     use super::*;
+    use crate::{
+        object::material::{Material, MaterialType},
+        sampler::{BlueNoiseSampler, HaltonSampler, RandomSampler, SobolSampler},
+        triangle::Triangle,
+    };
+
+    // a light facing the eye, covering the lower right half of the image to its diagonal
+    fn half_lit_scene() -> Scene {
+        let mut scene = Scene::new(8, 8);
+        let light = Arc::new(Material::new(MaterialType::Diffuse, Vec3::ONE, Vec3::ZERO));
+        let (x, y) = (EYE_POS.x, EYE_POS.y);
+        scene.add_object(Arc::new(Triangle::new(
+            Vec3::new(x - 1000.0, y - 1000.0, 0.0),
+            Vec3::new(x + 1000.0, y + 1000.0, 0.0),
+            Vec3::new(x + 1000.0, y - 1000.0, 0.0),
+            light,
+        )));
+        scene.build_bvh();
+        scene
+    }
+
     #[test]
-    fn test_update_progress() {
-        update_progress(0.0);
-        update_progress(0.1);
-        update_progress(0.5);
-        update_progress(0.9);
-        update_progress(1.0);
+    fn test_anti_aliasing() {
+        let scene = half_lit_scene();
+        let samplers: Vec<Box<dyn Sampler>> = vec![
+            Box::new(RandomSampler::default()),
+            Box::new(StratifiedSampler::default()),
+            Box::new(HaltonSampler::default()),
+            Box::new(SobolSampler::default()),
+            Box::new(BlueNoiseSampler::default()),
+        ];
+        for sampler in samplers {
+            let mut r = Renderer::new("test", 16);
+            r.progress = ProgressReporter::new().quiet();
+            r.sampler = sampler;
+            let frame = r.render_buffer(&scene);
+
+            // the light is seen from the left, x is mirrored (and dimmer away from the center)
+            assert!(frame[7 * 8].x > 0.85);
+            assert_eq!(frame[7], Vec3::ZERO);
+            // the pixels on the diagonal are partly lit
+            let partly = frame.iter().filter(|l| l.x > 0.05 && l.x < 0.8).count();
+            assert!(partly >= 6, "{} pixels partly lit", partly);
+
+            // the same on every run, whatever the threads do
+            assert_eq!(r.render_buffer(&scene), frame);
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let scene = half_lit_scene();
+        let mut r = Renderer::new("test", 4);
+        r.progress = ProgressReporter::new().quiet();
+        let frame = r.render_buffer(&scene);
+
+        for filter in [
+            Filter::Tent { radius: 1.0 },
+            Filter::gaussian(),
+            Filter::mitchell(),
+        ] {
+            r.filter = filter;
+            let filtered = r.render_buffer(&scene);
+            // far from the edge nothing changes, at the edge it's blurred
+            assert!((filtered[7 * 8].x - frame[7 * 8].x).abs() < 0.05);
+            assert_eq!(filtered[7], Vec3::ZERO);
+            assert!(filtered
+                .iter()
+                .all(|l| l.is_finite() && l.cmpge(Vec3::ZERO).all()));
+            let partly = filtered.iter().filter(|l| l.x > 0.05 && l.x < 0.8).count();
+            assert!(partly >= 6);
+            // the rows overlap, still the same image whichever thread finishes first
+            for _ in 0..4 {
+                assert_eq!(r.render_buffer(&scene), filtered);
+            }
+        }
     }
 }
@@ -0,0 +1,512 @@
+use std::sync::OnceLock;
+
+use glam::Vec2;
+
+/**
+ * where the random numbers of a path come from.
+ * every sample of a pixel asks for its dimensions in the same order:
+ * the position in the pixel first, then the light and bsdf samples of every bounce.
+ * the numbers only depend on the pixel, the sample and the dimension,
+ * so an image doesn't depend on the threads rendering it.
+ */
+pub trait Sampler: Send + Sync {
+    // begin the sample `index` of `count` of the pixel, dimensions start over from 0
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> Vec2;
+    // a sampler for another thread, at the same settings
+    fn clone_box(&self) -> Box<dyn Sampler>;
+
+    // where the sample is in the pixel, 0..1 from the top left corner
+    fn get_pixel_2d(&mut self) -> Vec2 {
+        self.get_2d()
+    }
+}
+
+// the state every sampler keeps, the sample being taken and its next dimension
+#[derive(Clone, Copy, Debug, Default)]
+struct SampleState {
+    pixel: (u32, u32),
+    index: u32,
+    count: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        *self = Self {
+            pixel: (pixel.0 as u32, pixel.1 as u32),
+            index,
+            count: count.max(1),
+            dimension: 0,
+        };
+    }
+
+    // the dimension to use, the next one is after it
+    fn next_dimension(&mut self) -> u32 {
+        self.dimension += 1;
+        self.dimension - 1
+    }
+
+    // a hash of the pixel and the dimension, the same for every sample of the pixel
+    fn pixel_seed(&self, dimension: u32) -> u32 {
+        hash(&[self.pixel.0, self.pixel.1, dimension])
+    }
+}
+
+fn mix(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+    h
+}
+
+pub fn hash(values: &[u32]) -> u32 {
+    values
+        .iter()
+        .fold(0x9e3779b9, |h, v| mix(h ^ v.wrapping_add(0x9e3779b9)))
+}
+
+// 24 random bits as a float in 0..1
+#[inline]
+fn to_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+/**
+ * a permutation of 0..n chosen by the seed, without storing it:
+ * a bijection on the next power of two, repeated until inside (Kensler, Correlated Multi-Jittered Sampling)
+ */
+pub fn permute(index: u32, n: u32, seed: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let mut i = index;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            return ((i as u64 + seed as u64) % n as u64) as u32;
+        }
+    }
+}
+
+/**
+ * independent uniform numbers for every sample, as the path tracer used to take
+ */
+#[derive(Clone, Debug, Default)]
+pub struct RandomSampler {
+    state: SampleState,
+}
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        self.state.start(pixel, index, count);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.state.next_dimension();
+        to_float(hash(&[self.state.pixel_seed(d), self.state.index]))
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+/**
+ * jittered stratified samples: the samples of a pixel are spread over count strata in 1D,
+ * and over a grid of about sqrt(count) x sqrt(count) strata in 2D.
+ * the strata are shuffled for every dimension, so that the dimensions are not correlated.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct StratifiedSampler {
+    state: SampleState,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        self.state.start(pixel, index, count);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.state.next_dimension();
+        let seed = self.state.pixel_seed(d);
+        let n = self.state.count;
+        let stratum = permute(self.state.index, n, seed);
+        let jitter = to_float(hash(&[seed, self.state.index]));
+        (stratum as f32 + jitter) / n as f32
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let d = self.state.next_dimension();
+        let seed = self.state.pixel_seed(d);
+        let nx = (self.state.count as f32).sqrt() as u32;
+        let ny = self.state.count.div_ceil(nx);
+        let stratum = permute(self.state.index, nx * ny, seed);
+        let jitter = Vec2::new(
+            to_float(hash(&[seed, self.state.index, 0])),
+            to_float(hash(&[seed, self.state.index, 1])),
+        );
+        Vec2::new(
+            ((stratum % nx) as f32 + jitter.x) / nx as f32,
+            ((stratum / nx) as f32 + jitter.y) / ny as f32,
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// the digits of index in the base, mirrored around the decimal point
+fn radical_inverse(base: u32, index: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let (mut index, mut reversed, mut inv_base_n) = (index, 0u64, 1.0);
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * base as u64 + (index - next * base) as u64;
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(1.0 - f32::EPSILON)
+}
+
+/**
+ * the halton sequence, dimension d is the radical inverse in the d-th prime base.
+ * every pixel takes the same points, shifted by a random offset of the pixel (Cranley Patterson rotation).
+ * past the 32 bases the dimensions start over with other offsets.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        self.state.start(pixel, index, count);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.state.next_dimension();
+        let base = PRIMES[d as usize % PRIMES.len()];
+        let offset = to_float(self.state.pixel_seed(d));
+        (radical_inverse(base, self.state.index) + offset).fract()
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+// the first two dimensions of the sobol sequence, a (0, 2) sequence
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut column = 1 << 31;
+    for bit in 0..32 {
+        if index & (1 << bit) != 0 {
+            x ^= 1 << (31 - bit);
+            y ^= column;
+        }
+        column ^= column >> 1;
+    }
+    (x, y)
+}
+
+/**
+ * the first two dimensions of the sobol sequence, padded for the others:
+ * every 2D dimension takes them with its own shuffle of the samples
+ * and its own random digit scrambling, both keeping their stratification.
+ * it's at its best with a power of two samples per pixel.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    // the point of this sample in a dimension, before scrambling
+    fn point(&self, seed: u32) -> (u32, u32) {
+        sobol_2d(permute(self.state.index, self.state.count, seed))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        self.state.start(pixel, index, count);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.state.next_dimension();
+        let seed = self.state.pixel_seed(d);
+        let (x, _) = self.point(seed);
+        to_float(x ^ mix(seed))
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let d = self.state.next_dimension();
+        let seed = self.state.pixel_seed(d);
+        let (x, y) = self.point(seed);
+        Vec2::new(
+            to_float(x ^ mix(seed)),
+            to_float(y ^ mix(seed ^ 0x5bd1e995)),
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+pub const BLUE_NOISE_SIZE: usize = 64;
+
+/**
+ * a 64 x 64 tile of the ranks 0..4096 where close pixels have far ranks, so any threshold of it is blue noise.
+ * made by void and cluster (Ulichney): every rank goes to the largest void,
+ * the pixel where a gaussian of the pixels already ranked is the lowest.
+ */
+pub fn blue_noise() -> &'static [u32] {
+    static MASK: OnceLock<Vec<u32>> = OnceLock::new();
+    MASK.get_or_init(|| {
+        let n = BLUE_NOISE_SIZE;
+        let sigma2 = 1.5f32 * 1.5;
+        // the gaussian by the offset, wrapping around the tile
+        let mut kernel = vec![0.0f32; n * n];
+        for dy in 0..n {
+            for dx in 0..n {
+                let (x, y) = (dx.min(n - dx) as f32, dy.min(n - dy) as f32);
+                kernel[dy * n + dx] = (-(x * x + y * y) / (2.0 * sigma2)).exp();
+            }
+        }
+
+        let mut energy = vec![0.0f32; n * n];
+        let mut ranks = vec![u32::MAX; n * n];
+        for rank in 0..(n * n) as u32 {
+            // ties go to a random pixel, not the first one
+            let void = (0..n * n)
+                .filter(|p| ranks[*p] == u32::MAX)
+                .min_by(|a, b| {
+                    energy[*a]
+                        .total_cmp(&energy[*b])
+                        .then(hash(&[*a as u32]).cmp(&hash(&[*b as u32])))
+                })
+                .unwrap();
+            ranks[void] = rank;
+            let (vx, vy) = (void % n, void / n);
+            for y in 0..n {
+                for x in 0..n {
+                    let (dx, dy) = ((x + n - vx) % n, (y + n - vy) % n);
+                    energy[y * n + x] += kernel[dy * n + dx];
+                }
+            }
+        }
+        ranks
+    })
+}
+
+/**
+ * the samples of every pixel are the same padded sobol points,
+ * shifted by the blue noise of the pixel (a different part of the tile for every dimension).
+ * the error of neighbouring pixels is not alike, so it looks like fine blue noise instead of blotches.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct BlueNoiseSampler {
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    // the blue noise of the pixel for a dimension, in 0..1
+    fn offset(&self, dimension: u32, component: u32) -> f32 {
+        let n = BLUE_NOISE_SIZE as u32;
+        let shift = hash(&[dimension, component]);
+        let x = (self.state.pixel.0 + (shift & 0xffff)) % n;
+        let y = (self.state.pixel.1 + (shift >> 16)) % n;
+        (blue_noise()[(y * n + x) as usize] as f32 + 0.5) / (n * n) as f32
+    }
+
+    // the point of this sample in a dimension, the same for every pixel
+    fn point(&self, dimension: u32) -> Vec2 {
+        let seed = hash(&[dimension]);
+        let (x, y) = sobol_2d(permute(self.state.index, self.state.count, seed));
+        Vec2::new(
+            to_float(x ^ mix(seed)),
+            to_float(y ^ mix(seed ^ 0x5bd1e995)),
+        )
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_sample(&mut self, pixel: (usize, usize), index: u32, count: u32) {
+        self.state.start(pixel, index, count);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let d = self.state.next_dimension();
+        (self.point(d).x + self.offset(d, 0)).fract()
+    }
+
+    fn get_2d(&mut self) -> Vec2 {
+        let d = self.state.next_dimension();
+        let offset = Vec2::new(self.offset(d, 0), self.offset(d, 1));
+        let p = self.point(d) + offset;
+        Vec2::new(p.x.fract(), p.y.fract())
+    }
+
+    fn clone_box(&self) -> Box<dyn Sampler> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn samplers() -> Vec<Box<dyn Sampler>> {
+        vec![
+            Box::new(RandomSampler::default()),
+            Box::new(StratifiedSampler::default()),
+            Box::new(HaltonSampler::default()),
+            Box::new(SobolSampler::default()),
+            Box::new(BlueNoiseSampler::default()),
+        ]
+    }
+
+    #[test]
+    fn test_permute() {
+        for n in [1, 2, 7, 16, 100] {
+            for seed in [0, 1, 12345, u32::MAX] {
+                let mut seen = vec![false; n as usize];
+                for i in 0..n {
+                    seen[permute(i, n, seed) as usize] = true;
+                }
+                assert!(seen.iter().all(|s| *s));
+            }
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        for mut sampler in samplers() {
+            let mut other = sampler.clone_box();
+            for dimension_count in [1, 5] {
+                sampler.start_sample((3, 4), 5, 16);
+                other.start_sample((3, 4), 5, 16);
+                for _ in 0..dimension_count {
+                    let (a, b) = (sampler.get_2d(), other.get_2d());
+                    assert_eq!(a, b);
+                    assert!(a.cmpge(Vec2::ZERO).all() && a.cmplt(Vec2::ONE).all());
+                    let (a, b) = (sampler.get_1d(), other.get_1d());
+                    assert_eq!(a, b);
+                    assert!((0.0..1.0).contains(&a));
+                }
+            }
+        }
+    }
+
+    // every one of the n x n strata has one of the n^2 samples of a pixel
+    fn stratified_2d(sampler: &mut dyn Sampler, n: u32, dimension: u32) -> bool {
+        let mut seen = vec![false; (n * n) as usize];
+        for index in 0..n * n {
+            sampler.start_sample((7, 9), index, n * n);
+            for _ in 0..dimension {
+                sampler.get_2d();
+            }
+            let p = sampler.get_2d() * n as f32;
+            seen[(p.y as u32 * n + p.x as u32) as usize] = true;
+        }
+        seen.iter().all(|s| *s)
+    }
+
+    #[test]
+    fn test_stratification() {
+        assert!(stratified_2d(&mut StratifiedSampler::default(), 4, 0));
+        assert!(stratified_2d(&mut StratifiedSampler::default(), 4, 3));
+        assert!(stratified_2d(&mut SobolSampler::default(), 4, 0));
+        assert!(stratified_2d(&mut SobolSampler::default(), 8, 2));
+        // white noise misses some
+        assert!(!stratified_2d(&mut RandomSampler::default(), 8, 0));
+
+        // and in 1D
+        let mut sampler = StratifiedSampler::default();
+        let mut seen = vec![false; 10];
+        for index in 0..10 {
+            sampler.start_sample((0, 0), index, 10);
+            seen[(sampler.get_1d() * 10.0) as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+
+        // the first halton points in base 2 and 3
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 3), 0.75);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_blue_noise() {
+        let mask = blue_noise();
+        let n = BLUE_NOISE_SIZE;
+        let mut sorted = mask.to_vec();
+        sorted.sort();
+        assert!(sorted.iter().enumerate().all(|(i, r)| i as u32 == *r));
+
+        // the means of 4 x 4 blocks vary much less than for white noise,
+        // where their variance would be 1 / 12 / 16
+        let mut variance = 0.0;
+        for by in 0..n / 4 {
+            for bx in 0..n / 4 {
+                let mut mean = 0.0;
+                for y in by * 4..by * 4 + 4 {
+                    for x in bx * 4..bx * 4 + 4 {
+                        mean += mask[y * n + x] as f32 / (n * n) as f32 / 16.0;
+                    }
+                }
+                variance += (mean - 0.5) * (mean - 0.5) / (n * n / 16) as f32;
+            }
+        }
+        assert!(variance < 1.0 / 12.0 / 16.0 / 4.0);
+
+        // the pixels of one sample differ with the mask
+        let mut sampler = BlueNoiseSampler::default();
+        sampler.start_sample((0, 0), 0, 4);
+        let a = sampler.get_pixel_2d();
+        sampler.start_sample((1, 0), 0, 4);
+        assert_ne!(a, sampler.get_pixel_2d());
+    }
+}
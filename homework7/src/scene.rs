@@ -4,7 +4,7 @@ use std::{
 
 use crate::{
     bvh::BVHAccel,
-    global::{get_random_float, EPSILON},
+    global::EPSILON,
     light::Light,
     object::{
         intersection::{Intersection, SampleResult},
@@ -12,6 +12,7 @@ use crate::{
         object::Object,
     },
     ray::Ray,
+    sampler::Sampler,
 };
 use glam::{Vec2, Vec3};
 
@@ -74,7 +75,8 @@ impl Scene {
     }
 
     // return pos: Intersection, pdf: f32
-    fn sample_light(&self) -> Option<SampleResult> {
+    // u.x chooses the emitting object, then both dimensions sample it
+    fn sample_light(&self, u: Vec2) -> Option<SampleResult> {
         let mut emit_area_sum = 0.0;
 
         for k in 0..self.objects.len() {
@@ -83,23 +85,27 @@ impl Scene {
             }
         }
 
-        let p = get_random_float() * emit_area_sum;
+        let p = u.x * emit_area_sum;
         emit_area_sum = 0.0;
 
         // means random choose an emitting object on the weight of area
         for k in 0..self.objects.len() {
             if self.objects[k].has_emit() {
-                emit_area_sum += self.objects[k].get_area();
+                let area = self.objects[k].get_area();
+                emit_area_sum += area;
 
                 if p <= emit_area_sum {
-                    return self.objects[k].sample();
+                    // where p is within the object is uniform again
+                    let x = ((p - (emit_area_sum - area)) / area).clamp(0.0, 1.0 - f32::EPSILON);
+                    return self.objects[k].sample(Vec2::new(x, u.y));
                 }
             }
         }
         None
     }
     // Implementation of Path Tracing
-    pub fn cast_ray(&self, ray: &Ray, from_camera: bool) -> Vec3 {
+    // the sampler gives the light and bsdf samples of every bounce
+    pub fn cast_ray(&self, ray: &Ray, from_camera: bool, sampler: &mut dyn Sampler) -> Vec3 {
         let intersection = match self.intersect(ray) {
             None => return Vec3::ZERO,
             Some(i) => i,
@@ -121,8 +127,14 @@ impl Scene {
             }
         }
 
+        // every bounce takes its dimensions in the same order, before using any of them
+        let light_u = sampler.get_2d();
+        let lights_u: Vec<Vec2> = self.lights.iter().map(|_| sampler.get_2d()).collect();
+        let roulette = sampler.get_1d();
+        let bsdf_u = sampler.get_2d();
+
         // l from the light directly
-        if let Some(sample_result) = self.sample_light() {
+        if let Some(sample_result) = self.sample_light(light_u) {
             let p = intersection.coords; // the point that ray bounced
             let x = sample_result.coords; // the point that light emitted
             let ws = (x - p).normalize(); // direction from hit point to light
@@ -139,14 +151,31 @@ impl Scene {
             }
         }
 
-        if get_random_float() > self.russian_roulette {
+        // l from the lights added to the scene
+        let p = intersection.coords;
+        for (light, u) in self.lights.iter().zip(lights_u) {
+            if let Some(s) = light.sample(p, u) {
+                let blocked = match self.intersect(&Ray::new(p, s.direction)) {
+                    Some(i) => i.distance < s.distance - EPSILON,
+                    None => false,
+                };
+                if !blocked {
+                    hit_color += s.radiance
+                        * intersection.m.eval(wo, s.direction, n)
+                        * s.direction.dot(n).max(0.0)
+                        / s.pdf;
+                }
+            }
+        }
+
+        if roulette > self.russian_roulette {
             // exit the recursive
             return hit_color;
         }
 
         // l bounced from the other surface
-        let wi = intersection.m.sameple(wo, n);
-        hit_color += self.cast_ray(&Ray::new(intersection.coords, wi), false)
+        let wi = intersection.m.sameple(wo, n, bsdf_u);
+        hit_color += self.cast_ray(&Ray::new(intersection.coords, wi), false, sampler)
             * intersection.m.eval(wi, wo, n)
             * wi.dot(n)
             / intersection.m.pdf(wi, wo, n)
@@ -7,7 +7,6 @@ use obj::load_obj;
 use crate::{
     bounds3::Bounds3,
     bvh::BVHAccel,
-    global::get_random_float,
     object::{
         intersection::{Intersection, SampleResult},
         material::{Material, MaterialType},
@@ -154,9 +153,9 @@ impl Object for Triangle {
         self.area
     }
 
-    fn sample(&self) -> Option<SampleResult> {
-        let x = get_random_float().sqrt();
-        let y = get_random_float();
+    fn sample(&self, u: Vec2) -> Option<SampleResult> {
+        let x = u.x.sqrt();
+        let y = u.y;
 
         // 1-x, x*(1-y), x*y is the barycentric coordinates
         // note that x ~ sqrt , so that the coordinate is averaged
@@ -287,8 +286,8 @@ impl Object for MeshTriangle {
         self.area
     }
 
-    fn sample(&self) -> Option<SampleResult> {
-        self.bvh.sample()
+    fn sample(&self, u: Vec2) -> Option<SampleResult> {
+        self.bvh.sample(u)
     }
 
     fn has_emit(&self) -> bool {
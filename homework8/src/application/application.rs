@@ -7,7 +7,10 @@ use utils::{
 use crate::{drawer::Drawer, rope::Rope};
 
 pub trait App {
-    fn render(&mut self, drawer: &mut dyn Drawer);
+    /** advances the simulation by one frame */
+    fn update(&mut self);
+    /** draws the current state, without advancing it */
+    fn render(&self, drawer: &mut dyn Drawer);
     fn handle_event(&mut self, action: &Action);
 }
 
@@ -67,17 +70,23 @@ impl Application {
             ),
         }
     }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
 }
 
 impl App for Application {
-    fn render(&mut self, drawer: &mut dyn Drawer) {
+    fn update(&mut self) {
         for _ in 0..self.config.steps_per_frame {
             self.rope_euler
                 .simulate_euler(1.0 / self.config.steps_per_frame as f32, self.config.gravity);
             self.rope_verlet
                 .simulate_verlet(1.0 / self.config.steps_per_frame as f32, self.config.gravity);
         }
+    }
 
+    fn render(&self, drawer: &mut dyn Drawer) {
         struct RenderCase<'a> {
             // rope
             rope: &'a Rope,
@@ -1,5 +1,10 @@
 use glam::Vec2;
-use utils::{rasterizer::Rasterizable, triangle::Rgb};
+use utils::{
+    path::{FillRule, Path},
+    rasterizer::{Rasterizable, RasterizableMut},
+    svg::SvgDocument,
+    triangle::Rgb,
+};
 
 use super::types::{Object, XYBound};
 
@@ -7,6 +12,7 @@ pub trait Drawer {
     fn clear(&mut self);
     fn draw_point(&mut self, p: Vec2, color: &Rgb);
     fn draw_line(&mut self, p1: Vec2, p2: Vec2, color: &Rgb);
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: &Rgb);
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -35,6 +41,8 @@ pub struct SimpleDrawer {
     line_width: f32, // 3.0
 
     frame_buf: Vec<Rgb>,
+    // points, lines and paths drawn since the last clear, if recording
+    recording: Option<SvgDocument>,
 }
 
 impl<'a> SimpleDrawer {
@@ -46,6 +54,7 @@ impl<'a> SimpleDrawer {
             point_size: conf.point_size,
             line_width: conf.line_width,
             frame_buf: vec![],
+            recording: None,
         };
 
         res.frame_buf.resize(width * height, res.background);
@@ -53,6 +62,22 @@ impl<'a> SimpleDrawer {
         res
     }
 
+    // record what is drawn into an svg document, it's emptied on clear
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = match recording {
+            true => Some(SvgDocument::new(
+                self.width as u32,
+                self.height as u32,
+                self.background,
+            )),
+            false => None,
+        };
+    }
+
+    pub fn svg(&self) -> Option<&SvgDocument> {
+        self.recording.as_ref()
+    }
+
     // return the buf index of point, if not in screen, return null
     // origin point on top left of the screen,
     // x axis face to right and y axis face to down.
@@ -107,6 +132,9 @@ impl<'a> SimpleDrawer {
     }
 
     pub fn draw_circle(&mut self, center: Vec2, radius: f32, color: &Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.circle(center, radius, *color);
+        }
         let bound = XYBound {
             min_x: center.x - radius,
             max_x: center.x + radius,
@@ -144,9 +172,18 @@ impl Rasterizable for SimpleDrawer {
     }
 }
 
+impl RasterizableMut for SimpleDrawer {
+    fn data_mut(&mut self) -> &mut [utils::rgb::Rgb] {
+        &mut self.frame_buf
+    }
+}
+
 impl Drawer for SimpleDrawer {
     fn clear(&mut self) {
-        self.frame_buf.fill(self.background)
+        self.frame_buf.fill(self.background);
+        if let Some(svg) = self.recording.as_mut() {
+            svg.clear();
+        }
     }
 
     fn draw_point(&mut self, p: Vec2, color: &Rgb) {
@@ -154,6 +191,9 @@ impl Drawer for SimpleDrawer {
     }
 
     fn draw_line(&mut self, p1: Vec2, p2: Vec2, color: &Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.line(p1, p2, *color, self.line_width);
+        }
         let half_width = self.line_width / 2.0;
         let l = p2 - p1;
         let direction = l.normalize();
@@ -186,6 +226,13 @@ impl Drawer for SimpleDrawer {
             Some(color)
         });
     }
+
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: &Rgb) {
+        if let Some(svg) = self.recording.as_mut() {
+            svg.path(path, rule, *color);
+        }
+        RasterizableMut::fill_path(self, path, rule, *color)
+    }
 }
 
 #[cfg(test)]
@@ -244,4 +291,41 @@ mod test {
 
         save_image(&drawer, "output.png").unwrap();
     }
+
+    #[test]
+    fn test_fill_path() {
+        let mut drawer = SimpleDrawer::new(100, 100, SimpleDrawerConfig::default());
+        let red = Rgb::new(255, 0, 0);
+        let path = Path::circle(Vec2::new(50.5, 50.5), 20.0);
+        Drawer::fill_path(&mut drawer, &path, FillRule::NonZero, &red);
+
+        assert_eq!(drawer.data()[50 * 100 + 50], red);
+        assert_eq!(drawer.data()[50 * 100 + 75], Rgb::default());
+        // the edge is partly covered
+        let edge = drawer.data()[50 * 100 + 70];
+        assert!(edge.0 > 0 && edge.0 < 255);
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut drawer = SimpleDrawer::new(100, 100, SimpleDrawerConfig::default());
+        let red = Rgb::new(255, 0, 0);
+        drawer.set_recording(true);
+        drawer.draw_point(Vec2::new(10.0, 10.0), &red);
+        drawer.draw_line(Vec2::new(10.0, 10.0), Vec2::new(90.0, 90.0), &red);
+        Drawer::fill_path(
+            &mut drawer,
+            &Path::circle(Vec2::new(50.0, 50.0), 20.0),
+            FillRule::EvenOdd,
+            &red,
+        );
+
+        let svg = drawer.svg().unwrap().to_svg();
+        assert!(svg.contains(r#"<circle cx="10" cy="10" r="3" fill="rgb(255,0,0)"/>"#));
+        assert!(svg.contains(r#"stroke-width="2""#));
+        assert!(svg.contains(r#"fill-rule="evenodd""#));
+
+        drawer.clear();
+        assert!(drawer.svg().unwrap().is_empty());
+    }
 }
@@ -7,10 +7,13 @@ use drawer::{Drawer, SimpleDrawer, SimpleDrawerConfig};
 use utils::{
     graphic::{save_image, start_loop, Action, Control},
     rasterizer::Rasterizable,
+    rgb::Rgb,
+    text::draw_text_scaled,
 };
 
 fn main() {
     let save_path = "output.png";
+    let svg_path = "output.svg";
     let mut r = SimpleDrawer::new(1000, 1000, SimpleDrawerConfig::default());
     let mut app = Application::new(AppConfig::default());
 
@@ -19,17 +22,29 @@ fn main() {
         for action in actions {
             match action {
                 Action::Stop => {
+                    // only the saved frame is recorded as svg, the displayed one drawn again
+                    r.set_recording(true);
+                    draw_frame(&mut r, &app);
                     save_image(&r, &save_path)?;
+                    if let Some(svg) = r.svg() {
+                        svg.save(svg_path)?;
+                    }
                     return Ok(Control::Stop);
                 }
                 _ => app.handle_event(action),
             }
         }
 
-        r.clear();
-        app.render(&mut r);
-
+        app.update();
+        draw_frame(&mut r, &app);
         display_image(&mut r)?;
         Ok(Control::Continue)
     })
 }
+
+fn draw_frame(r: &mut SimpleDrawer, app: &Application) {
+    r.clear();
+    app.render(r);
+    let steps = format!("steps per frame: {}", app.config().steps_per_frame);
+    draw_text_scaled(r, 10, 10, &steps, Rgb(255, 255, 255), 2);
+}
@@ -214,13 +214,13 @@ where
                     _ => Action::Key(virtual_code),
                 },
 
-                // when cursor moved, just update cursor_at, and is a idle action
+                // when cursor moved, update cursor_at, and is a moved action
                 WindowEvent::CursorMoved {
                     position: PhysicalPosition { x, y },
                     ..
                 } => {
                     cursor_at = (x as f32, y as f32);
-                    Action::Idle
+                    Action::Moved{x:cursor_at.0, y:cursor_at.1}
                 },
 
                 // when mouse clicked, should be a click action
@@ -229,12 +229,23 @@ where
                     button: MouseButton::Left,
                     ..
                 } => Action::Clicked{x:cursor_at.0, y:cursor_at.1},
+                WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    button: MouseButton::Left,
+                    ..
+                } => Action::Released{x:cursor_at.0, y:cursor_at.1},
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Right,
+                    ..
+                } => Action::RightClicked{x:cursor_at.0, y:cursor_at.1},
                 _ => Action::Idle,
             },
             _ => Action::Idle,
         };
 
-        if !matches!(action, Action::Idle) {
+        // cursor moves are too many to print
+        if !matches!(action, Action::Idle | Action::Moved { .. }) {
             dbg!(&action);
         }
 
@@ -250,6 +261,9 @@ pub enum Action {
     Stop,
     Key(Key),
     Clicked { x: f32, y: f32 },
+    Released { x: f32, y: f32 },
+    RightClicked { x: f32, y: f32 },
+    Moved { x: f32, y: f32 },
 }
 
 pub enum Control {
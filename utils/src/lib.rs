@@ -1,4 +1,12 @@
 pub mod triangle;
 pub mod graphic;
+pub mod parallel;
+pub mod path;
+pub mod post;
+pub mod progress;
+pub mod light;
 pub mod rasterizer;
-pub mod rgb;
\ No newline at end of file
+pub mod rgb;
+pub mod stats;
+pub mod svg;
+pub mod text;
\ No newline at end of file
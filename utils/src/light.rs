@@ -0,0 +1,540 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+/**
+ * the light arriving at a shading point from one sample of a light
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    // unit vector from the shading point toward the light
+    pub direction: Vec3,
+    // from the shading point to the sampled point, infinity for directional lights.
+    // only occluders nearer than it shadow the point.
+    pub distance: f32,
+    // what arrives along direction
+    pub radiance: Vec3,
+    // the density of direction per solid angle, 1 for lights of a single direction.
+    // radiance / pdf is the estimate of the light arriving at the point.
+    pub pdf: f32,
+}
+
+/**
+ * the lights shared by the ray tracers of homework5, 6 and 7
+ */
+pub trait Light: Send + Sync {
+    /**
+     * where the light is, the center of area lights.
+     * None for directional lights, they are infinitely far away.
+     */
+    fn position(&self) -> Option<Vec3>;
+    fn intensity(&self) -> Vec3;
+
+    /**
+     * sample the light seen from a point.
+     * @u: uniform in 0..1, area lights map it onto their surface
+     * return: None if nothing arrives at the point (out of a spot cone, behind an area light)
+     */
+    fn sample(&self, point: Vec3, u: Vec2) -> Option<LightSample>;
+
+    /**
+     * the density of `sample` giving the direction from the point, per solid angle.
+     * 0 for lights of a single direction, no random direction hits them.
+     */
+    fn pdf(&self, point: Vec3, direction: Vec3) -> f32;
+
+    /**
+     * the light comes from a single direction,
+     * one sample is exact and more samples don't help.
+     */
+    fn is_delta(&self) -> bool;
+}
+
+/**
+ * where to sample a light, with the weight of every sample:
+ * once for lights of a single direction, on an n x n grid for area lights.
+ */
+pub fn light_samples(light: &dyn Light, n: u32) -> Vec<(Vec2, f32)> {
+    if light.is_delta() {
+        return vec![(Vec2::splat(0.5), 1.0)];
+    }
+    let n = n.max(1);
+    let weight = 1.0 / (n * n) as f32;
+    (0..n * n)
+        .map(|i| {
+            let u = Vec2::new(
+                ((i % n) as f32 + 0.5) / n as f32,
+                ((i / n) as f32 + 0.5) / n as f32,
+            );
+            (u, weight)
+        })
+        .collect()
+}
+
+/**
+ * point and spot lights don't fall off with distance,
+ * their intensity is what arrives at the shading points (as in the assignments).
+ */
+pub struct PointLight {
+    position: Vec3,
+    intensity: Vec3,
+}
+
+impl Light for PointLight {
+    fn position(&self) -> Option<Vec3> {
+        Some(self.position)
+    }
+
+    fn intensity(&self) -> Vec3 {
+        self.intensity
+    }
+
+    fn sample(&self, point: Vec3, _u: Vec2) -> Option<LightSample> {
+        let to_light = self.position - point;
+        Some(LightSample {
+            direction: to_light.normalize(),
+            distance: to_light.length(),
+            radiance: self.intensity,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl PointLight {
+    pub fn new(p: &Vec3, i: f32) -> Self {
+        return Self {
+            position: *p,
+            intensity: i * Vec3::ONE,
+        };
+    }
+}
+
+/**
+ * parallel light from infinitely far away, like the sun
+ */
+pub struct DirectionalLight {
+    // the direction the light travels in, normalized
+    direction: Vec3,
+    intensity: Vec3,
+}
+
+impl Light for DirectionalLight {
+    fn position(&self) -> Option<Vec3> {
+        None
+    }
+
+    fn intensity(&self) -> Vec3 {
+        self.intensity
+    }
+
+    fn sample(&self, _point: Vec3, _u: Vec2) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f32::INFINITY,
+            radiance: self.intensity,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl DirectionalLight {
+    /**
+     * @direction: the direction the light travels in
+     */
+    pub fn new(direction: &Vec3, i: f32) -> Self {
+        Self {
+            direction: direction.normalize(),
+            intensity: i * Vec3::ONE,
+        }
+    }
+}
+
+/**
+ * a point light lighting a cone only.
+ * fully lit inside the inner angle, fading out smoothly to the outer angle.
+ */
+pub struct SpotLight {
+    position: Vec3,
+    // the axis of the cone, normalized
+    direction: Vec3,
+    cos_inner: f32,
+    cos_outer: f32,
+    intensity: Vec3,
+}
+
+impl Light for SpotLight {
+    fn position(&self) -> Option<Vec3> {
+        Some(self.position)
+    }
+
+    fn intensity(&self) -> Vec3 {
+        self.intensity
+    }
+
+    fn sample(&self, point: Vec3, _u: Vec2) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let direction = to_light.normalize();
+        let falloff = self.falloff(-direction);
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance: to_light.length(),
+            radiance: self.intensity * falloff,
+            pdf: 1.0,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl SpotLight {
+    /**
+     * @direction: the axis of the cone
+     * @inner_angle, @outer_angle: from the axis to the edge of the full light and of the cone, in degree
+     */
+    pub fn new(p: &Vec3, direction: &Vec3, inner_angle: f32, outer_angle: f32, i: f32) -> Self {
+        let outer_angle = outer_angle.max(inner_angle);
+        Self {
+            position: *p,
+            direction: direction.normalize(),
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+            intensity: i * Vec3::ONE,
+        }
+    }
+
+    // how much of the intensity goes along a direction from the light, 0..1
+    fn falloff(&self, direction: Vec3) -> f32 {
+        let cos = direction.dot(self.direction);
+        if self.cos_inner <= self.cos_outer {
+            return if cos >= self.cos_outer { 1.0 } else { 0.0 };
+        }
+        let t = ((cos - self.cos_outer) / (self.cos_inner - self.cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/**
+ * the shape of an area light, emitting on the side its normal faces
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum AreaShape {
+    // the parallelogram from corner spanned by edge_u and edge_v,
+    // the normal is edge_u x edge_v
+    Rectangle {
+        corner: Vec3,
+        edge_u: Vec3,
+        edge_v: Vec3,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f32,
+    },
+}
+
+/**
+ * a rectangle or a disk of uniform radiance, casting soft shadows.
+ * the radiance is the one leaving its surface, so the light arriving at a point
+ * falls off with the squared distance and the cosine on the light.
+ */
+pub struct AreaLight {
+    shape: AreaShape,
+    radiance: Vec3,
+}
+
+impl Light for AreaLight {
+    fn position(&self) -> Option<Vec3> {
+        Some(self.center())
+    }
+
+    // the radiance leaving the surface
+    fn intensity(&self) -> Vec3 {
+        self.radiance
+    }
+
+    fn sample(&self, point: Vec3, u: Vec2) -> Option<LightSample> {
+        let on_light = match self.shape {
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => corner + u.x * edge_u + u.y * edge_v,
+            AreaShape::Disk {
+                center,
+                normal,
+                radius,
+            } => {
+                // uniform on the disk, not denser at the center
+                let (r, phi) = (radius * u.x.sqrt(), 2.0 * PI * u.y);
+                let tangent = normal.any_orthonormal_vector();
+                let bitangent = normal.cross(tangent);
+                center + r * (phi.cos() * tangent + phi.sin() * bitangent)
+            }
+        };
+        let to_light = on_light - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let pdf = self.solid_angle_pdf(direction, distance);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.radiance,
+            pdf,
+        })
+    }
+
+    fn pdf(&self, point: Vec3, direction: Vec3) -> f32 {
+        let direction = direction.normalize();
+        let normal = self.normal();
+        let denom = direction.dot(normal);
+        if denom.abs() < f32::EPSILON {
+            return 0.0;
+        }
+        let t = (self.center() - point).dot(normal) / denom;
+        if t <= 0.0 {
+            return 0.0;
+        }
+        let hit = point + t * direction;
+        let inside = match self.shape {
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                // solve hit = corner + x * edge_u + y * edge_v,
+                // the edges needn't be orthogonal
+                let d = hit - corner;
+                let (uu, uv, vv) = (
+                    edge_u.length_squared(),
+                    edge_u.dot(edge_v),
+                    edge_v.length_squared(),
+                );
+                let (du, dv) = (d.dot(edge_u), d.dot(edge_v));
+                let det = uu * vv - uv * uv;
+                let (x, y) = ((vv * du - uv * dv) / det, (uu * dv - uv * du) / det);
+                (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)
+            }
+            AreaShape::Disk { center, radius, .. } => (hit - center).length() <= radius,
+        };
+        match inside {
+            true => self.solid_angle_pdf(direction, t),
+            false => 0.0,
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+}
+
+impl AreaLight {
+    /**
+     * @i: the radiance leaving the surface
+     */
+    pub fn new(shape: AreaShape, i: f32) -> Self {
+        let shape = match shape {
+            AreaShape::Disk {
+                center,
+                normal,
+                radius,
+            } => AreaShape::Disk {
+                center,
+                normal: normal.normalize(),
+                radius,
+            },
+            rectangle => rectangle,
+        };
+        Self {
+            shape,
+            radiance: i * Vec3::ONE,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match self.shape {
+            AreaShape::Rectangle { edge_u, edge_v, .. } => edge_u.cross(edge_v).length(),
+            AreaShape::Disk { radius, .. } => PI * radius * radius,
+        }
+    }
+
+    fn center(&self) -> Vec3 {
+        match self.shape {
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => corner + 0.5 * (edge_u + edge_v),
+            AreaShape::Disk { center, .. } => center,
+        }
+    }
+
+    fn normal(&self) -> Vec3 {
+        match self.shape {
+            AreaShape::Rectangle { edge_u, edge_v, .. } => edge_u.cross(edge_v).normalize(),
+            AreaShape::Disk { normal, .. } => normal,
+        }
+    }
+
+    /**
+     * uniform on the area, turned into per solid angle from the shading point.
+     * 0 if the point is behind the light.
+     */
+    fn solid_angle_pdf(&self, direction: Vec3, distance: f32) -> f32 {
+        let cos_light = -direction.dot(self.normal());
+        if cos_light <= 0.0 {
+            return 0.0;
+        }
+        distance * distance / (cos_light * self.area())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delta_lights() {
+        let point = PointLight::new(&Vec3::new(0.0, 4.0, 0.0), 0.5);
+        let s = point.sample(Vec3::ZERO, Vec2::ZERO).unwrap();
+        assert!(s.direction.abs_diff_eq(Vec3::Y, 1e-6));
+        assert_eq!((s.distance, s.pdf), (4.0, 1.0));
+        assert_eq!(s.radiance, Vec3::ONE * 0.5);
+        assert!(point.is_delta());
+
+        let sun = DirectionalLight::new(&Vec3::new(0.0, -2.0, 0.0), 1.0);
+        let s = sun.sample(Vec3::new(5.0, 0.0, 3.0), Vec2::ZERO).unwrap();
+        assert!(s.direction.abs_diff_eq(Vec3::Y, 1e-6));
+        assert_eq!(s.distance, f32::INFINITY);
+        assert_eq!(sun.pdf(Vec3::ZERO, Vec3::Y), 0.0);
+        assert_eq!(sun.position(), None);
+        assert_eq!(light_samples(&sun, 4), vec![(Vec2::splat(0.5), 1.0)]);
+
+        // a spot on y = 4 looking down, lit within 20 degrees, fading out to 30
+        let spot = SpotLight::new(&Vec3::new(0.0, 4.0, 0.0), &-Vec3::Y, 20.0, 30.0, 1.0);
+        let at = |deg: f32| {
+            let x = 4.0 * deg.to_radians().tan();
+            spot.sample(Vec3::new(x, 0.0, 0.0), Vec2::ZERO)
+        };
+        assert_eq!(at(0.0).unwrap().radiance, Vec3::ONE);
+        assert_eq!(at(19.0).unwrap().radiance, Vec3::ONE);
+        let edge = at(25.0).unwrap().radiance.x;
+        assert!(edge > 0.0 && edge < 1.0);
+        assert!(at(31.0).is_none());
+    }
+
+    #[test]
+    fn test_area_lights() {
+        // a 2 x 2 square on y = 4 facing down, and a disk of the same area
+        let square = AreaLight::new(
+            AreaShape::Rectangle {
+                corner: Vec3::new(-1.0, 4.0, -1.0),
+                edge_u: Vec3::new(2.0, 0.0, 0.0),
+                edge_v: Vec3::new(0.0, 0.0, 2.0),
+            },
+            1.0,
+        );
+        let disk = AreaLight::new(
+            AreaShape::Disk {
+                center: Vec3::new(0.0, 4.0, 0.0),
+                normal: -Vec3::Y,
+                radius: (4.0 / PI).sqrt(),
+            },
+            1.0,
+        );
+        assert!(!square.is_delta());
+        assert!((square.area() - 4.0).abs() < 1e-5);
+        assert!((disk.area() - 4.0).abs() < 1e-5);
+        assert!(square
+            .position()
+            .unwrap()
+            .abs_diff_eq(Vec3::new(0.0, 4.0, 0.0), 1e-6));
+        let samples = light_samples(&square, 3);
+        assert_eq!(samples.len(), 9);
+        assert!((samples.iter().map(|(_, w)| w).sum::<f32>() - 1.0).abs() < 1e-6);
+
+        for light in [&square as &dyn Light, &disk] {
+            // every sample is on the light, and the pdf agrees with `pdf`
+            let n = 8;
+            let mut irradiance = 0.0;
+            for i in 0..n * n {
+                let u = Vec2::new(
+                    ((i % n) as f32 + 0.5) / n as f32,
+                    ((i / n) as f32 + 0.5) / n as f32,
+                );
+                let s = light.sample(Vec3::ZERO, u).unwrap();
+                let on_light = s.direction * s.distance;
+                assert!((on_light.y - 4.0).abs() < 1e-4);
+                let pdf = light.pdf(Vec3::ZERO, s.direction);
+                assert!((pdf - s.pdf).abs() < 1e-3 * s.pdf, "{} {}", pdf, s.pdf);
+                irradiance += s.radiance.x * s.direction.y / s.pdf / (n * n) as f32;
+            }
+            // a bit less than a point of intensity area * radiance at distance 4 (0.25)
+            assert!((irradiance - 0.231).abs() < 0.01, "{}", irradiance);
+
+            // nothing behind it, and no pdf off the light
+            assert!(light
+                .sample(Vec3::new(0.0, 5.0, 0.0), Vec2::splat(0.5))
+                .is_none());
+            assert_eq!(light.pdf(Vec3::ZERO, Vec3::X), 0.0);
+            assert_eq!(light.pdf(Vec3::ZERO, -Vec3::Y), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_skewed_area_light() {
+        // a parallelogram on y = 4 facing down, its edges aren't orthogonal
+        let (corner, edge_u, edge_v) = (
+            Vec3::new(-1.0, 4.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 2.0),
+        );
+        let light = AreaLight::new(
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            },
+            1.0,
+        );
+        assert!((light.area() - 4.0).abs() < 1e-5);
+        let at = |x: f32, y: f32| light.pdf(Vec3::ZERO, corner + x * edge_u + y * edge_v);
+
+        // inside, but out of the edges projected on each other
+        let s = light.sample(Vec3::ZERO, Vec2::new(0.9, 0.9)).unwrap();
+        assert!((at(0.9, 0.9) - s.pdf).abs() < 1e-3 * s.pdf);
+        for (x, y) in [(0.1, 0.1), (0.5, 0.5), (0.95, 0.05)] {
+            assert!(at(x, y) > 0.0, "{} {}", x, y);
+        }
+        // outside, but in the edges projected on each other
+        assert_eq!(at(-0.2, 0.9), 0.0);
+        assert_eq!(at(1.1, 0.5), 0.0);
+    }
+}
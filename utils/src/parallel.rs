@@ -0,0 +1,137 @@
+use std::{
+    iter,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crossbeam::{
+    deque::{Injector, Stealer, Worker},
+    thread::scope,
+};
+use glam::Vec3;
+
+// side of the square tiles the image is split into, in pixels
+pub const TILE_SIZE: usize = 16;
+
+// a rectangle of pixels rendered by one thread at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// the tiles covering the image row by row, those on the right and bottom edges may be smaller
+pub fn tiles(width: usize, height: usize) -> Vec<Tile> {
+    let mut tiles = vec![];
+    for y in (0..height).step_by(TILE_SIZE) {
+        for x in (0..width).step_by(TILE_SIZE) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+// 0 means a thread per core
+pub fn thread_count(threads: usize) -> usize {
+    match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+// the next tile from the local queue, else a batch from the global queue, else one stolen from another thread
+fn find_tile(
+    local: &Worker<Tile>,
+    global: &Injector<Tile>,
+    stealers: &[Stealer<Tile>],
+) -> Option<Tile> {
+    local.pop().or_else(|| {
+        iter::repeat_with(|| {
+            global
+                .steal_batch_and_pop(local)
+                .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+        })
+        .find(|s| !s.is_retry())
+        .and_then(|s| s.success())
+    })
+}
+
+/**
+ * shade every pixel of a width x height image on `threads` threads (0 for a thread per core),
+ * the tiles are taken from a work stealing queue.
+ * every pixel only depends on shade(x, y), so the image doesn't depend on the scheduling.
+ * @progress: called with the part of the tiles done whenever another percent is done, from any thread
+ * return: the pixels row by row, y facing down
+ */
+pub fn render_tiles<S, P>(
+    width: usize,
+    height: usize,
+    threads: usize,
+    shade: S,
+    progress: P,
+) -> Vec<Vec3>
+where
+    S: Fn(usize, usize) -> Vec3 + Sync,
+    P: Fn(f32) + Sync,
+{
+    let tiles = tiles(width, height);
+    let total = tiles.len();
+    let global = Injector::new();
+    for tile in tiles {
+        global.push(tile);
+    }
+
+    let workers: Vec<Worker<Tile>> = (0..thread_count(threads).min(total.max(1)))
+        .map(|_| Worker::new_fifo())
+        .collect();
+    let stealers: Vec<Stealer<Tile>> = workers.iter().map(|w| w.stealer()).collect();
+    let done = AtomicUsize::new(0);
+
+    let rendered: Vec<(Tile, Vec<Vec3>)> = scope(|s| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|local| {
+                let (global, stealers, done) = (&global, &stealers, &done);
+                let (shade, progress) = (&shade, &progress);
+                s.spawn(move |_| {
+                    let mut rendered = vec![];
+                    while let Some(tile) = find_tile(&local, global, stealers) {
+                        let mut pixels = Vec::with_capacity(tile.width * tile.height);
+                        for y in tile.y..tile.y + tile.height {
+                            for x in tile.x..tile.x + tile.width {
+                                pixels.push(shade(x, y));
+                            }
+                        }
+                        rendered.push((tile, pixels));
+                        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        if done * 100 / total != (done - 1) * 100 / total {
+                            progress(done as f32 / total as f32);
+                        }
+                    }
+                    rendered
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+    .unwrap();
+
+    let mut frame_buffer = vec![Vec3::ZERO; width * height];
+    for (tile, pixels) in rendered {
+        for (row, line) in pixels.chunks(tile.width).enumerate() {
+            let start = (tile.y + row) * width + tile.x;
+            frame_buffer[start..start + tile.width].copy_from_slice(line);
+        }
+    }
+    frame_buffer
+}
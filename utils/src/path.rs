@@ -0,0 +1,304 @@
+use glam::Vec2;
+
+use crate::{rasterizer::RasterizableMut, rgb::Rgb};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    // control point, end point
+    QuadTo(Vec2, Vec2),
+    // two control points, end point
+    CubicTo(Vec2, Vec2, Vec2),
+    Close,
+}
+
+/**
+ * a 2D path of contours made of lines and bezier curves, in pixels with y facing down.
+ * every contour is closed when filled, whether it ends with `close` or not.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    pub commands: Vec<PathCommand>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    // inside where a ray crosses the outline an odd number of times
+    EvenOdd,
+    // inside where the outline winds around a non zero number of times
+    NonZero,
+}
+
+impl FillRule {
+    fn is_inside(&self, winding: i32) -> bool {
+        match self {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::NonZero => winding != 0,
+        }
+    }
+}
+
+// curves are flattened within this distance in pixels
+const FLATTEN_TOLERANCE: f32 = 0.1;
+// sub scanlines per pixel row for the vertical coverage
+const SUBSCANLINES: u32 = 16;
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn move_to(mut self, p: Vec2) -> Self {
+        self.commands.push(PathCommand::MoveTo(p));
+        self
+    }
+
+    pub fn line_to(mut self, p: Vec2) -> Self {
+        self.commands.push(PathCommand::LineTo(p));
+        self
+    }
+
+    pub fn quad_to(mut self, control: Vec2, p: Vec2) -> Self {
+        self.commands.push(PathCommand::QuadTo(control, p));
+        self
+    }
+
+    pub fn cubic_to(mut self, control1: Vec2, control2: Vec2, p: Vec2) -> Self {
+        self.commands
+            .push(PathCommand::CubicTo(control1, control2, p));
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    // a closed polygon through the points
+    pub fn polygon(points: &[Vec2]) -> Self {
+        let mut path = Self::new();
+        for (i, p) in points.iter().enumerate() {
+            path = match i {
+                0 => path.move_to(*p),
+                _ => path.line_to(*p),
+            };
+        }
+        path.close()
+    }
+
+    // a circle of four cubic arcs, clockwise on screen
+    pub fn circle(center: Vec2, radius: f32) -> Self {
+        let k = 0.552285 * radius;
+        let (x, y) = (Vec2::X * radius, Vec2::Y * radius);
+        let (kx, ky) = (Vec2::X * k, Vec2::Y * k);
+        Self::new()
+            .move_to(center + x)
+            .cubic_to(center + x + ky, center + kx + y, center + y)
+            .cubic_to(center - kx + y, center - x + ky, center - x)
+            .cubic_to(center - x - ky, center - kx - y, center - y)
+            .cubic_to(center + kx - y, center + x - ky, center + x)
+            .close()
+    }
+
+    /**
+     * every contour as a closed polyline (the first point is not repeated at the end),
+     * curves are subdivided until within tolerance of their chords.
+     */
+    pub fn flatten(&self, tolerance: f32) -> Vec<Vec<Vec2>> {
+        let mut contours = vec![];
+        let mut contour: Vec<Vec2> = vec![];
+        let mut current = Vec2::ZERO;
+        let mut start = Vec2::ZERO;
+
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(p) => {
+                    if contour.len() > 1 {
+                        contours.push(std::mem::take(&mut contour));
+                    }
+                    contour = vec![p];
+                    current = p;
+                    start = p;
+                }
+                PathCommand::LineTo(p) => {
+                    if contour.is_empty() {
+                        contour.push(current);
+                    }
+                    contour.push(p);
+                    current = p;
+                }
+                PathCommand::QuadTo(c, p) => {
+                    if contour.is_empty() {
+                        contour.push(current);
+                    }
+                    // the same curve as a cubic
+                    let c1 = current + (c - current) * 2.0 / 3.0;
+                    let c2 = p + (c - p) * 2.0 / 3.0;
+                    flatten_cubic([current, c1, c2, p], tolerance, 16, &mut contour);
+                    current = p;
+                }
+                PathCommand::CubicTo(c1, c2, p) => {
+                    if contour.is_empty() {
+                        contour.push(current);
+                    }
+                    flatten_cubic([current, c1, c2, p], tolerance, 16, &mut contour);
+                    current = p;
+                }
+                PathCommand::Close => {
+                    if contour.len() > 1 {
+                        contours.push(std::mem::take(&mut contour));
+                    }
+                    current = start;
+                }
+            }
+        }
+        if contour.len() > 1 {
+            contours.push(contour);
+        }
+
+        for contour in contours.iter_mut() {
+            if contour.len() > 1 && contour.first() == contour.last() {
+                contour.pop();
+            }
+        }
+        contours
+    }
+}
+
+// push the points after the first one of a cubic bezier
+fn flatten_cubic(p: [Vec2; 4], tolerance: f32, depth: u32, out: &mut Vec<Vec2>) {
+    let flat = [p[1], p[2]]
+        .iter()
+        .all(|c| distance_to_segment(*c, p[0], p[3]) <= tolerance);
+    if depth == 0 || flat {
+        out.push(p[3]);
+        return;
+    }
+
+    // de Casteljau at the middle
+    let (p01, p12, p23) = (
+        p[0].lerp(p[1], 0.5),
+        p[1].lerp(p[2], 0.5),
+        p[2].lerp(p[3], 0.5),
+    );
+    let (p012, p123) = (p01.lerp(p12, 0.5), p12.lerp(p23, 0.5));
+    let mid = p012.lerp(p123, 0.5);
+    flatten_cubic([p[0], p01, p012, mid], tolerance, depth - 1, out);
+    flatten_cubic([mid, p123, p23, p[3]], tolerance, depth - 1, out);
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    if ab.length_squared() == 0.0 {
+        return p.distance(a);
+    }
+    let s = ((p - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+    p.distance(a + ab * s)
+}
+
+// an edge of the outline from top to bottom
+struct Edge {
+    top: Vec2,
+    bottom: Vec2,
+    // +1 if the outline goes down along it, -1 if up
+    direction: i32,
+}
+
+/**
+ * fill the path with color, blended by coverage:
+ * every pixel row is scanned at 16 sub scanlines, and the spans inside by the rule
+ * add their exact horizontal overlap with every pixel.
+ */
+pub fn fill_path<R: RasterizableMut + ?Sized>(r: &mut R, path: &Path, rule: FillRule, color: Rgb) {
+    let mut edges = vec![];
+    for contour in path.flatten(FLATTEN_TOLERANCE) {
+        for i in 0..contour.len() {
+            let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+            if a.y == b.y {
+                continue;
+            }
+            edges.push(match a.y < b.y {
+                true => Edge {
+                    top: a,
+                    bottom: b,
+                    direction: 1,
+                },
+                false => Edge {
+                    top: b,
+                    bottom: a,
+                    direction: -1,
+                },
+            });
+        }
+    }
+    if edges.is_empty() {
+        return;
+    }
+
+    let (width, height) = r.size();
+    let min_y = edges.iter().map(|e| e.top.y).fold(f32::MAX, f32::min);
+    let max_y = edges.iter().map(|e| e.bottom.y).fold(f32::MIN, f32::max);
+    let rows = (min_y.floor().max(0.0) as u32)..(max_y.ceil().clamp(0.0, height as f32) as u32);
+
+    let data = r.data_mut();
+    let mut coverage = vec![0.0f32; width as usize];
+    let mut crossings: Vec<(f32, i32)> = vec![];
+    for y in rows {
+        coverage.fill(0.0);
+        for s in 0..SUBSCANLINES {
+            let sy = y as f32 + (s as f32 + 0.5) / SUBSCANLINES as f32;
+            crossings.clear();
+            for e in edges.iter().filter(|e| e.top.y <= sy && sy < e.bottom.y) {
+                let t = (sy - e.top.y) / (e.bottom.y - e.top.y);
+                crossings.push((e.top.x + (e.bottom.x - e.top.x) * t, e.direction));
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if rule.is_inside(winding) {
+                    add_span(
+                        &mut coverage,
+                        pair[0].0,
+                        pair[1].0,
+                        1.0 / SUBSCANLINES as f32,
+                    );
+                }
+            }
+        }
+
+        let row = &mut data[(y * width) as usize..((y + 1) * width) as usize];
+        for (pixel, c) in row.iter_mut().zip(&coverage) {
+            if *c > 0.0 {
+                *pixel = blend(*pixel, color, c.min(1.0));
+            }
+        }
+    }
+}
+
+// add weight times the overlap of [x0, x1] with every pixel
+fn add_span(coverage: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let (x0, x1) = (x0.max(0.0), x1.min(coverage.len() as f32));
+    if x0 >= x1 {
+        return;
+    }
+    let (first, last) = (
+        x0.floor() as usize,
+        (x1.ceil() as usize).min(coverage.len()),
+    );
+    for (x, c) in coverage.iter_mut().enumerate().take(last).skip(first) {
+        let overlap = (x1.min(x as f32 + 1.0) - x0.max(x as f32)).max(0.0);
+        *c += overlap * weight;
+    }
+}
+
+fn blend(background: Rgb, color: Rgb, alpha: f32) -> Rgb {
+    let mix = |b: u8, c: u8| (b as f32 + (c as f32 - b as f32) * alpha).round() as u8;
+    Rgb(
+        mix(background.0, color.0),
+        mix(background.1, color.1),
+        mix(background.2, color.2),
+    )
+}
@@ -0,0 +1,485 @@
+use anyhow::Result;
+use glam::Vec3;
+
+use crate::rasterizer::{BufRasterizer, Rasterizable};
+use crate::rgb;
+
+/**
+ * an image of float colors, 0..1 for the displayable range.
+ * rows are laid out the same as the rasterizers, top row first.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<Vec3>,
+}
+
+impl FloatImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![Vec3::ZERO; (width * height) as usize],
+        }
+    }
+
+    pub fn from_rasterizable<R: Rasterizable + ?Sized>(r: &R) -> Self {
+        let (width, height) = r.size();
+        let data = r.data().iter().map(|c| Vec3::from(*c) / 255.0).collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn to_rasterizer(&self) -> BufRasterizer {
+        let data = self
+            .data
+            .iter()
+            .map(|c| rgb::Rgb::from(&(c.clamp(Vec3::ZERO, Vec3::ONE) * 255.0)))
+            .collect();
+        BufRasterizer {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+
+    /**
+     * the color at column x and row y (from the top),
+     * coordinates out of the image are clamped to the edge.
+     */
+    pub fn get(&self, x: i32, y: i32) -> Vec3 {
+        let x = x.clamp(0, self.width as i32 - 1) as u32;
+        let y = y.clamp(0, self.height as i32 - 1) as u32;
+        self.data[(y * self.width + x) as usize]
+    }
+
+    fn map(&self, f: impl Fn(Vec3) -> Vec3) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(|c| f(*c)).collect(),
+        }
+    }
+}
+
+/**
+ * a single step of post processing, taking the whole frame and making a new one
+ */
+pub trait Filter {
+    fn name(&self) -> &'static str;
+    fn apply(&self, img: &FloatImage) -> FloatImage;
+}
+
+struct Stage {
+    filter: Box<dyn Filter>,
+    enabled: bool,
+}
+
+/**
+ * filters applied one after another, every one of them could be turned on and off.
+ *
+ * let post = PostChain::new()
+ *     .with(Fxaa::default())
+ *     .with(ToneCurve::Gamma(2.2));
+ * save_image(&post.apply(&r), "output.png")?;
+ */
+#[derive(Default)]
+pub struct PostChain {
+    stages: Vec<Stage>,
+}
+
+impl PostChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, filter: impl Filter + 'static) -> Self {
+        self.push(filter);
+        self
+    }
+
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.stages.push(Stage {
+            filter: Box::new(filter),
+            enabled: true,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn name(&self, ind: usize) -> &'static str {
+        self.stages[ind].filter.name()
+    }
+
+    pub fn is_enabled(&self, ind: usize) -> bool {
+        self.stages[ind].enabled
+    }
+
+    pub fn set_enabled(&mut self, ind: usize, enabled: bool) {
+        self.stages[ind].enabled = enabled;
+    }
+
+    /**
+     * turn a filter on if it's off, or off if it's on.
+     * return: whether the filter is on now
+     */
+    pub fn toggle(&mut self, ind: usize) -> bool {
+        let stage = &mut self.stages[ind];
+        stage.enabled = !stage.enabled;
+        stage.enabled
+    }
+
+    pub fn apply_float(&self, img: FloatImage) -> FloatImage {
+        self.stages
+            .iter()
+            .filter(|s| s.enabled)
+            .fold(img, |img, s| s.filter.apply(&img))
+    }
+
+    pub fn apply<R: Rasterizable + ?Sized>(&self, r: &R) -> BufRasterizer {
+        self.apply_float(FloatImage::from_rasterizable(r))
+            .to_rasterizer()
+    }
+}
+
+/**
+ * separable gaussian blur, the kernel covers 3 sigma on each side
+ */
+pub struct GaussianBlur {
+    pub sigma: f32,
+}
+
+impl GaussianBlur {
+    fn kernel(&self) -> Vec<f32> {
+        let radius = (self.sigma * 3.0).ceil().max(1.0) as i32;
+        let weights: Vec<f32> = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * self.sigma * self.sigma)).exp())
+            .collect();
+        let sum: f32 = weights.iter().sum();
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+impl Filter for GaussianBlur {
+    fn name(&self) -> &'static str {
+        "gaussian blur"
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        if self.sigma <= 0.0 {
+            return img.clone();
+        }
+        let kernel = self.kernel();
+        let radius = (kernel.len() / 2) as i32;
+
+        let pass = |img: &FloatImage, dx: i32, dy: i32| {
+            let mut res = FloatImage::new(img.width, img.height);
+            for y in 0..img.height as i32 {
+                for x in 0..img.width as i32 {
+                    let mut c = Vec3::ZERO;
+                    for (i, w) in kernel.iter().enumerate() {
+                        let offset = i as i32 - radius;
+                        c += *w * img.get(x + offset * dx, y + offset * dy);
+                    }
+                    res.data[(y as u32 * img.width + x as u32) as usize] = c;
+                }
+            }
+            res
+        };
+        pass(&pass(img, 1, 0), 0, 1)
+    }
+}
+
+/**
+ * make the bright parts glow: the part of the colors brighter than threshold
+ * is blurred and added back on the frame.
+ */
+pub struct Bloom {
+    pub threshold: f32,
+    pub sigma: f32,
+    pub intensity: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            sigma: 4.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl Filter for Bloom {
+    fn name(&self) -> &'static str {
+        "bloom"
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        let bright = img.map(|c| {
+            let l = luma(c);
+            match l > self.threshold {
+                true => c * (l - self.threshold) / l,
+                false => Vec3::ZERO,
+            }
+        });
+        let glow = GaussianBlur { sigma: self.sigma }.apply(&bright);
+
+        let mut res = img.clone();
+        for (c, g) in res.data.iter_mut().zip(glow.data.iter()) {
+            *c += *g * self.intensity;
+        }
+        res
+    }
+}
+
+/**
+ * fast approximate anti-aliasing.
+ *
+ * a simplified FXAA: edges are found by the luma contrast with the 4 neighbors,
+ * and every edge pixel is blended with the neighbor across the edge,
+ * by how much it differs from the average of its 3x3 neighborhood.
+ * there's no search for the ends of long edges.
+ */
+pub struct Fxaa {
+    // the contrast needed for an edge, relative to the brightest luma around
+    pub edge_threshold: f32,
+    // the contrast needed for an edge in dark areas
+    pub edge_threshold_min: f32,
+    // how much to blend, 0 to 1
+    pub subpixel: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            subpixel: 0.75,
+        }
+    }
+}
+
+impl Filter for Fxaa {
+    fn name(&self) -> &'static str {
+        "fxaa"
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        let l = img.map(|c| Vec3::splat(luma(c)));
+        let l = |x: i32, y: i32| l.get(x, y).x;
+
+        let mut res = img.clone();
+        for y in 0..img.height as i32 {
+            for x in 0..img.width as i32 {
+                let (m, n, s, e, w) = (l(x, y), l(x, y - 1), l(x, y + 1), l(x + 1, y), l(x - 1, y));
+                let max = m.max(n).max(s).max(e).max(w);
+                let min = m.min(n).min(s).min(e).min(w);
+                let range = max - min;
+                if range < self.edge_threshold_min.max(max * self.edge_threshold) {
+                    continue;
+                }
+
+                let (nw, ne, sw, se) = (
+                    l(x - 1, y - 1),
+                    l(x + 1, y - 1),
+                    l(x - 1, y + 1),
+                    l(x + 1, y + 1),
+                );
+                let edge_h = (nw + ne - 2.0 * n).abs()
+                    + 2.0 * (w + e - 2.0 * m).abs()
+                    + (sw + se - 2.0 * s).abs();
+                let edge_v = (nw + sw - 2.0 * w).abs()
+                    + 2.0 * (n + s - 2.0 * m).abs()
+                    + (ne + se - 2.0 * e).abs();
+
+                // a horizontal edge is blended with the pixel above or below
+                let (dx, dy) = match edge_h >= edge_v {
+                    true if (n - m).abs() >= (s - m).abs() => (0, -1),
+                    true => (0, 1),
+                    false if (w - m).abs() >= (e - m).abs() => (-1, 0),
+                    false => (1, 0),
+                };
+
+                let average = (2.0 * (n + s + e + w) + nw + ne + sw + se) / 12.0;
+                let t = ((average - m).abs() / range).clamp(0.0, 1.0);
+                let t = t * t * (3.0 - 2.0 * t);
+                let blend = t * t * self.subpixel;
+
+                let ind = (y as u32 * img.width + x as u32) as usize;
+                res.data[ind] = img.data[ind].lerp(img.get(x + dx, y + dy), blend);
+            }
+        }
+        res
+    }
+}
+
+/**
+ * darken the frame towards the corners.
+ * pixels within radius (1 for the distance from the center to a corner) are untouched.
+ */
+pub struct Vignette {
+    pub strength: f32,
+    pub radius: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.6,
+            radius: 0.5,
+        }
+    }
+}
+
+impl Filter for Vignette {
+    fn name(&self) -> &'static str {
+        "vignette"
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        let center = glam::Vec2::new(img.width as f32, img.height as f32) / 2.0;
+        let mut res = img.clone();
+        for y in 0..img.height {
+            for x in 0..img.width {
+                let p = glam::Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let d = (p - center).length() / center.length();
+                let t = ((d - self.radius) / (1.0 - self.radius).max(f32::EPSILON)).clamp(0.0, 1.0);
+                res.data[(y * img.width + x) as usize] *= 1.0 - self.strength * t * t;
+            }
+        }
+        res
+    }
+}
+
+/**
+ * a curve applied on every channel
+ */
+pub enum ToneCurve {
+    // gamma correction, c^(1/gamma)
+    Gamma(f32),
+    // c / (1 + c), for bringing colors brighter than 1 back to the range
+    Reinhard,
+    // the filmic curve of ACES, fitted by Krzysztof Narkowicz
+    Aces,
+}
+
+impl Filter for ToneCurve {
+    fn name(&self) -> &'static str {
+        match self {
+            ToneCurve::Gamma(_) => "gamma",
+            ToneCurve::Reinhard => "reinhard",
+            ToneCurve::Aces => "aces",
+        }
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        match self {
+            ToneCurve::Gamma(gamma) => img.map(|c| c.max(Vec3::ZERO).powf(1.0 / gamma)),
+            ToneCurve::Reinhard => img.map(|c| {
+                let c = c.max(Vec3::ZERO);
+                c / (c + Vec3::ONE)
+            }),
+            ToneCurve::Aces => img.map(|c| {
+                let c = c.max(Vec3::ZERO);
+                let (a, b, cc, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((c * (a * c + b)) / (c * (cc * c + d) + e)).clamp(Vec3::ZERO, Vec3::ONE)
+            }),
+        }
+    }
+}
+
+/**
+ * color grading by a 3D lookup table, colors between the entries are interpolated trilinearly.
+ * the table has size^3 entries, red changes the fastest and blue the slowest.
+ */
+pub struct ColorLut {
+    size: usize,
+    table: Vec<Vec3>,
+}
+
+impl ColorLut {
+    /**
+     * a table mapping every color to itself
+     */
+    pub fn identity(size: usize) -> Self {
+        Self::from_fn(size, |c| c)
+    }
+
+    /**
+     * a table sampling a color transformation
+     */
+    pub fn from_fn(size: usize, f: impl Fn(Vec3) -> Vec3) -> Self {
+        assert!(size >= 2);
+        let step = 1.0 / (size - 1) as f32;
+        let mut table = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push(f(Vec3::new(r as f32, g as f32, b as f32) * step));
+                }
+            }
+        }
+        Self { size, table }
+    }
+
+    /**
+     * load a table saved as a strip image, size * size pixels wide and size pixels high,
+     * made of size squares from blue 0 to blue 1. in every square, red goes right and green goes down.
+     */
+    pub fn from_image(path: &str) -> Result<Self> {
+        let img = image::io::Reader::open(path)?.decode()?.into_rgb8();
+        let size = img.height() as usize;
+        if size < 2 || img.width() as usize != size * size {
+            anyhow::bail!("a lut image should be size * size by size pixels");
+        }
+        let mut table = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let p = img.get_pixel((b * size + r) as u32, g as u32);
+                    table.push(Vec3::from(rgb::Rgb::from(p)) / 255.0);
+                }
+            }
+        }
+        Ok(Self { size, table })
+    }
+
+    pub fn lookup(&self, c: Vec3) -> Vec3 {
+        let p = c.clamp(Vec3::ZERO, Vec3::ONE) * (self.size - 1) as f32;
+        let p0 = p.floor().min(Vec3::splat((self.size - 2) as f32));
+        let t = p - p0;
+        let (r, g, b) = (p0.x as usize, p0.y as usize, p0.z as usize);
+        let at = |r: usize, g: usize, b: usize| self.table[(b * self.size + g) * self.size + r];
+
+        let c00 = at(r, g, b).lerp(at(r + 1, g, b), t.x);
+        let c10 = at(r, g + 1, b).lerp(at(r + 1, g + 1, b), t.x);
+        let c01 = at(r, g, b + 1).lerp(at(r + 1, g, b + 1), t.x);
+        let c11 = at(r, g + 1, b + 1).lerp(at(r + 1, g + 1, b + 1), t.x);
+        c00.lerp(c10, t.y).lerp(c01.lerp(c11, t.y), t.z)
+    }
+}
+
+impl Filter for ColorLut {
+    fn name(&self) -> &'static str {
+        "color lut"
+    }
+
+    fn apply(&self, img: &FloatImage) -> FloatImage {
+        img.map(|c| self.lookup(c))
+    }
+}
+
+pub fn luma(c: Vec3) -> f32 {
+    c.dot(Vec3::new(0.299, 0.587, 0.114))
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+use std::io::Write;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 50;
+
+/**
+ * how far a render is, as passed to the callback of a reporter.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    // 0..1
+    pub fraction: f32,
+    pub elapsed: Duration,
+    // the time left at the speed so far, None before anything is done
+    pub eta: Option<Duration>,
+    // primary rays cast so far
+    pub rays: u64,
+    pub rays_per_second: f64,
+}
+
+fn format_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    match s {
+        0..=59 => format!("{:.1}s", d.as_secs_f32()),
+        60..=3599 => format!("{}m{:02}s", s / 60, s % 60),
+        _ => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+// a bar with the percent done, then the speed and the time left
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fraction = self.fraction.clamp(0.0, 1.0);
+        let pos = (BAR_WIDTH as f32 * fraction) as usize;
+        let mut bar = "=".repeat(pos);
+        if pos < BAR_WIDTH {
+            bar.push('>');
+            bar.push_str(&"_".repeat(BAR_WIDTH - pos - 1));
+        }
+        write!(
+            f,
+            "[{}] {:3}% {:.2} Mrays/s",
+            bar,
+            (fraction * 100.0) as u32,
+            self.rays_per_second / 1e6
+        )?;
+        match (fraction < 1.0, self.eta) {
+            (true, Some(eta)) => write!(f, " eta {}", format_duration(eta)),
+            (true, None) => write!(f, " eta -"),
+            (false, _) => write!(f, " in {}", format_duration(self.elapsed)),
+        }
+    }
+}
+
+// called on every update of a reporter
+pub type ProgressCallback = Box<dyn Fn(&Progress) + Send + Sync>;
+
+/**
+ * reports the progress of a render, shared by the threads rendering it.
+ * the bar is redrawn in place on the terminal whenever another percent is done,
+ * and the callback is called on every update.
+ */
+pub struct ProgressReporter {
+    quiet: bool,
+    callback: Option<ProgressCallback>,
+
+    start: Mutex<Instant>,
+    rays: AtomicU64,
+    // the last percent drawn
+    drawn: Mutex<Option<u32>>,
+}
+
+impl Default for ProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        Self {
+            quiet: false,
+            callback: None,
+            start: Mutex::new(Instant::now()),
+            rays: AtomicU64::new(0),
+            drawn: Mutex::new(None),
+        }
+    }
+
+    // draw nothing, the callback is still called
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    pub fn with_callback<F: Fn(&Progress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    // start timing and counting again, at the beginning of a render
+    pub fn begin(&self) {
+        *self.start.lock().unwrap() = Instant::now();
+        self.rays.store(0, Ordering::Relaxed);
+        *self.drawn.lock().unwrap() = None;
+    }
+
+    pub fn add_rays(&self, rays: u64) {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+    }
+
+    // the progress with `fraction` of the render done
+    pub fn progress(&self, fraction: f32) -> Progress {
+        let elapsed = self.start.lock().unwrap().elapsed();
+        let rays = self.rays.load(Ordering::Relaxed);
+        let seconds = elapsed.as_secs_f64();
+        let eta = match fraction > 0.0 {
+            true => Some(elapsed.mul_f64((1.0 - fraction.min(1.0) as f64) / fraction as f64)),
+            false => None,
+        };
+        Progress {
+            fraction,
+            elapsed,
+            eta,
+            rays,
+            rays_per_second: match seconds > 0.0 {
+                true => rays as f64 / seconds,
+                false => 0.0,
+            },
+        }
+    }
+
+    pub fn update(&self, fraction: f32) {
+        let progress = self.progress(fraction);
+        if let Some(callback) = &self.callback {
+            callback(&progress);
+        }
+        if self.quiet {
+            return;
+        }
+
+        let percent = (fraction.clamp(0.0, 1.0) * 100.0) as u32;
+        let mut drawn = self.drawn.lock().unwrap();
+        if drawn.is_some_and(|drawn| drawn >= percent) {
+            return;
+        }
+        *drawn = Some(percent);
+        let mut out = std::io::stdout().lock();
+        // back to the start of the line to draw over the last bar, padded to cover all of it
+        let _ = write!(out, "\r{:<90}", progress.to_string());
+        if percent == 100 {
+            let _ = writeln!(out);
+        }
+        let _ = out.flush();
+    }
+}
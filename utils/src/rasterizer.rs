@@ -11,6 +11,28 @@ pub trait Rasterizable {
     }
 }
 
+/**
+ * a Rasterizable whose frame can be drawn on after rasterizing, e.g. to annotate it.
+ */
+pub trait RasterizableMut: Rasterizable {
+    fn data_mut(&mut self) -> &mut [rgb::Rgb];
+
+    /**
+     * draw a text with the embedded bitmap font, see `text::draw_text`.
+     * (x, y) is the top left corner in pixels, y faces down.
+     */
+    fn draw_text(&mut self, x: i32, y: i32, text: &str, color: rgb::Rgb) {
+        crate::text::draw_text(self, x, y, text, color)
+    }
+
+    /**
+     * fill a path with anti aliased edges, see `path::fill_path`.
+     */
+    fn fill_path(&mut self, path: &crate::path::Path, rule: crate::path::FillRule, color: rgb::Rgb) {
+        crate::path::fill_path(self, path, rule, color)
+    }
+}
+
 bitflags! {
     pub struct Buffers: u8 {
         const COLOR = 0x1<<0;
@@ -38,6 +60,9 @@ pub struct IndBufId(pub u32);
 #[derive(Clone, Copy)]
 pub struct ColBufId(pub u32);
 
+#[derive(Clone, Copy)]
+pub struct VertBufId(pub u32);
+
 /**
  * A simple implementation of Rasterizable trait.
  */
@@ -57,6 +82,12 @@ impl Rasterizable for BufRasterizer {
     }
 }
 
+impl RasterizableMut for BufRasterizer {
+    fn data_mut(&mut self) -> &mut [rgb::Rgb] {
+        &mut self.data
+    }
+}
+
 impl BufRasterizer {
     pub fn from_vec3s(vec3s: Vec<Vec3>, width: u32, height: u32) -> Self {
         let data = vec3s.into_iter().map(|x| {
@@ -64,4 +95,27 @@ impl BufRasterizer {
         }).collect();
         Self { width, height, data }
     }
+
+    /**
+     * visualize a depth buffer as a gray image, the nearer the brighter.
+     * depths are normalized between the nearest and the farthest finite one,
+     * and pixels never drawn (infinite depth) are black.
+     */
+    pub fn from_depth(depth: &[f32], width: u32, height: u32) -> Self {
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for d in depth.iter().filter(|d| d.is_finite()) {
+            min = min.min(*d);
+            max = max.max(*d);
+        }
+        let range = (max - min).max(f32::EPSILON);
+
+        let vec3s = depth
+            .iter()
+            .map(|d| match d.is_finite() {
+                true => Vec3::ONE * (1.0 - (d - min) / range),
+                false => Vec3::ZERO,
+            })
+            .collect();
+        Self::from_vec3s(vec3s, width, height)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+/**
+ * what a rasterizer did in a frame, and how long every stage took.
+ */
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct FrameStats {
+    // transforming the vertices
+    pub vertex: Duration,
+    // walking the pixels of the triangles and testing the depth
+    pub raster: Duration,
+    // running the fragment shaders
+    pub shade: Duration,
+    // showing the frame on the screen, measured by the loop
+    pub display: Duration,
+
+    pub triangles: u64,
+    // fragments passing the depth test
+    pub fragments: u64,
+    // fragment shader runs
+    pub shaded: u64,
+    // pixels covered by any triangle at the end of the frame
+    pub covered: u64,
+}
+
+impl FrameStats {
+    pub const CSV_HEADER: &'static str =
+        "vertex_ms,raster_ms,shade_ms,display_ms,total_ms,triangles,fragments,shaded,covered,overdraw";
+
+    pub fn total(&self) -> Duration {
+        self.vertex + self.raster + self.shade + self.display
+    }
+
+    /**
+     * fragments written for every covered pixel, 1 for no overdraw at all
+     */
+    pub fn overdraw(&self) -> f32 {
+        match self.covered {
+            0 => 0.0,
+            covered => self.fragments as f32 / covered as f32,
+        }
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{:.3},{:.3},{:.3},{:.3},{:.3},{},{},{},{},{:.3}",
+            ms(self.vertex),
+            ms(self.raster),
+            ms(self.shade),
+            ms(self.display),
+            ms(self.total()),
+            self.triangles,
+            self.fragments,
+            self.shaded,
+            self.covered,
+            self.overdraw()
+        )
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} ms (vertex {:.1}, raster {:.1}, shade {:.1}, display {:.1}), \
+             {} triangles, {} fragments, {} shaded, overdraw {:.2}",
+            ms(self.total()),
+            ms(self.vertex),
+            ms(self.raster),
+            ms(self.shade),
+            ms(self.display),
+            self.triangles,
+            self.fragments,
+            self.shaded,
+            self.overdraw()
+        )
+    }
+}
+
+/**
+ * the stats of every frame, for benchmarking
+ */
+#[derive(Default)]
+pub struct FrameLog {
+    frames: Vec<FrameStats>,
+}
+
+impl FrameLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, stats: FrameStats) {
+        self.frames.push(stats);
+    }
+
+    pub fn frames(&self) -> &[FrameStats] {
+        &self.frames
+    }
+
+    /**
+     * the average of all frames, None if there's no frame yet
+     */
+    pub fn average(&self) -> Option<FrameStats> {
+        let n = self.frames.len() as u64;
+        if n == 0 {
+            return None;
+        }
+        let sum = self.frames.iter().fold(FrameStats::default(), |sum, s| FrameStats {
+            vertex: sum.vertex + s.vertex,
+            raster: sum.raster + s.raster,
+            shade: sum.shade + s.shade,
+            display: sum.display + s.display,
+            triangles: sum.triangles + s.triangles,
+            fragments: sum.fragments + s.fragments,
+            shaded: sum.shaded + s.shaded,
+            covered: sum.covered + s.covered,
+        });
+        Some(FrameStats {
+            vertex: sum.vertex / n as u32,
+            raster: sum.raster / n as u32,
+            shade: sum.shade / n as u32,
+            display: sum.display / n as u32,
+            triangles: sum.triangles / n,
+            fragments: sum.fragments / n,
+            shaded: sum.shaded / n,
+            covered: sum.covered / n,
+        })
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "frame,{}", FrameStats::CSV_HEADER)?;
+        for (i, s) in self.frames.iter().enumerate() {
+            writeln!(file, "{},{}", i, s.csv_row())?;
+        }
+        Ok(())
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use glam::Vec2;
+
+use crate::{
+    path::{FillRule, Path, PathCommand},
+    rgb::Rgb,
+};
+
+/**
+ * 2D primitives recorded as an svg document, in pixels with y facing down,
+ * so a drawing can be saved resolution independent next to its png.
+ */
+#[derive(Clone, Debug)]
+pub struct SvgDocument {
+    width: u32,
+    height: u32,
+    background: Rgb,
+    elements: Vec<String>,
+}
+
+// three decimals are enough for pixels
+fn num(v: f32) -> String {
+    format!("{}", (v * 1000.0).round() / 1000.0)
+}
+
+fn color(c: Rgb) -> String {
+    format!("rgb({},{},{})", c.0, c.1, c.2)
+}
+
+fn point(p: Vec2) -> String {
+    format!("{} {}", num(p.x), num(p.y))
+}
+
+impl SvgDocument {
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            background,
+            elements: vec![],
+        }
+    }
+
+    // remove every element, e.g. when the frame is cleared
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, fill: Rgb) {
+        self.elements.push(format!(
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+            num(center.x),
+            num(center.y),
+            num(radius),
+            color(fill)
+        ));
+    }
+
+    pub fn line(&mut self, from: Vec2, to: Vec2, stroke: Rgb, width: f32) {
+        self.elements.push(format!(
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" stroke-linecap="round"/>"#,
+            num(from.x),
+            num(from.y),
+            num(to.x),
+            num(to.y),
+            color(stroke),
+            num(width)
+        ));
+    }
+
+    pub fn polyline(&mut self, points: &[Vec2], stroke: Rgb, width: f32) {
+        if points.len() < 2 {
+            return;
+        }
+        let points: Vec<String> = points.iter().map(|p| point(*p)).collect();
+        self.elements.push(format!(
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linejoin="round"/>"#,
+            points.join(" "),
+            color(stroke),
+            num(width)
+        ));
+    }
+
+    pub fn path(&mut self, path: &Path, rule: FillRule, fill: Rgb) {
+        let d: Vec<String> = path
+            .commands
+            .iter()
+            .map(|command| match *command {
+                PathCommand::MoveTo(p) => format!("M {}", point(p)),
+                PathCommand::LineTo(p) => format!("L {}", point(p)),
+                PathCommand::QuadTo(c, p) => format!("Q {} {}", point(c), point(p)),
+                PathCommand::CubicTo(c1, c2, p) => {
+                    format!("C {} {} {}", point(c1), point(c2), point(p))
+                }
+                PathCommand::Close => "Z".to_string(),
+            })
+            .collect();
+        let rule = match rule {
+            FillRule::EvenOdd => "evenodd",
+            FillRule::NonZero => "nonzero",
+        };
+        self.elements.push(format!(
+            r#"<path d="{}" fill="{}" fill-rule="{}"/>"#,
+            d.join(" "),
+            color(fill),
+            rule
+        ));
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height
+        );
+        svg.push('\n');
+        svg.push_str(&format!(
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            color(self.background)
+        ));
+        svg.push('\n');
+        for element in &self.elements {
+            svg.push_str(element);
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_svg())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use crate::rasterizer::RasterizableMut;
+use crate::rgb::Rgb;
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+// a column and two rows of spacing between glyphs
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/**
+ * the size in pixels of a text drawn at scale 1,
+ * lines are split by '\n'.
+ */
+pub fn text_size(text: &str) -> (u32, u32) {
+    let lines = text.split('\n');
+    let (mut width, mut height) = (0, 0);
+    for line in lines {
+        let n = line.chars().count() as u32;
+        width = width.max((n * ADVANCE).saturating_sub(1));
+        height += LINE_HEIGHT;
+    }
+    (width, height - (LINE_HEIGHT - GLYPH_HEIGHT))
+}
+
+/**
+ * draw a text with the embedded 5x7 font.
+ * (x, y) is the top left corner in pixels, the origin is the top left of the frame
+ * and y faces down. pixels out of the frame are skipped.
+ * chars out of the printable ascii are drawn as '?'.
+ */
+pub fn draw_text<R: RasterizableMut + ?Sized>(r: &mut R, x: i32, y: i32, text: &str, color: Rgb) {
+    draw_text_scaled(r, x, y, text, color, 1)
+}
+
+/**
+ * `draw_text` with every font pixel drawn as a scale x scale block
+ */
+pub fn draw_text_scaled<R: RasterizableMut + ?Sized>(
+    r: &mut R,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: Rgb,
+    scale: u32,
+) {
+    let (width, height) = r.size();
+    let (width, height) = (width as i32, height as i32);
+    let scale = scale.max(1) as i32;
+    let data = r.data_mut();
+
+    let mut set_pixel = |px: i32, py: i32| {
+        if (0..width).contains(&px) && (0..height).contains(&py) {
+            data[(py * width + px) as usize] = color;
+        }
+    };
+
+    for (row, line) in text.split('\n').enumerate() {
+        let top = y + row as i32 * LINE_HEIGHT as i32 * scale;
+        for (col, c) in line.chars().enumerate() {
+            let left = x + col as i32 * ADVANCE as i32 * scale;
+            for (gy, bits) in glyph(c).iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - gx) & 1 == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            set_pixel(left + gx as i32 * scale + sx, top + gy as i32 * scale + sy);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * the rows of a glyph from the top, the highest of the 5 bits is the leftmost pixel
+ */
+pub fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT as usize] {
+    let c = match c {
+        '\t' => ' ',
+        ' '..='~' => c,
+        _ => '?',
+    };
+    &FONT[c as usize - ' ' as usize]
+}
+
+// printable ascii, from ' ' to '~'
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_HEIGHT as usize]; 95] = [
+    // ' '
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+    // '!'
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+    // '"'
+    [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000],
+    // '#'
+    [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010],
+    // '$'
+    [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100],
+    // '%'
+    [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+    // '&'
+    [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+    // '\''
+    [0b00100, 0b00100, 0b00100, 0b00000, 0b00000, 0b00000, 0b00000],
+    // '('
+    [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+    // ')'
+    [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+    // '*'
+    [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000],
+    // '+'
+    [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+    // ','
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+    // '-'
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+    // '.'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+    // '/'
+    [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+    // '0'
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+    // '1'
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+    // '2'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+    // '3'
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+    // '4'
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+    // '5'
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+    // '6'
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+    // '7'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+    // '8'
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+    // '9'
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+    // ':'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+    // ';'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000],
+    // '<'
+    [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010],
+    // '='
+    [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+    // '>'
+    [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000],
+    // '?'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    // '@'
+    [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110],
+    // 'A'
+    [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+    // 'B'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+    // 'C'
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+    // 'D'
+    [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+    // 'E'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+    // 'F'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+    // 'G'
+    [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+    // 'H'
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+    // 'I'
+    [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+    // 'J'
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+    // 'K'
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+    // 'L'
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+    // 'M'
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+    // 'N'
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+    // 'O'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+    // 'P'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+    // 'Q'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+    // 'R'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+    // 'S'
+    [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+    // 'T'
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+    // 'U'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+    // 'V'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+    // 'W'
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+    // 'X'
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+    // 'Y'
+    [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+    // 'Z'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+    // '['
+    [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110],
+    // '\\'
+    [0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000],
+    // ']'
+    [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110],
+    // '^'
+    [0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000],
+    // '_'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+    // '`'
+    [0b01000, 0b00100, 0b00010, 0b00000, 0b00000, 0b00000, 0b00000],
+    // 'a'
+    [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111],
+    // 'b'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110],
+    // 'c'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110],
+    // 'd'
+    [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111],
+    // 'e'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110],
+    // 'f'
+    [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000],
+    // 'g'
+    [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+    // 'h'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001],
+    // 'i'
+    [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110],
+    // 'j'
+    [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100],
+    // 'k'
+    [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010],
+    // 'l'
+    [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+    // 'm'
+    [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001],
+    // 'n'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001],
+    // 'o'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110],
+    // 'p'
+    [0b00000, 0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000],
+    // 'q'
+    [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b00001],
+    // 'r'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000],
+    // 's'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110],
+    // 't'
+    [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110],
+    // 'u'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101],
+    // 'v'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+    // 'w'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010],
+    // 'x'
+    [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001],
+    // 'y'
+    [0b00000, 0b10001, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110],
+    // 'z'
+    [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111],
+    // '{'
+    [0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010],
+    // '|'
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+    // '}'
+    [0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000],
+    // '~'
+    [0b00000, 0b00000, 0b01000, 0b10101, 0b00010, 0b00000, 0b00000],
+];
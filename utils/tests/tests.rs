@@ -27,4 +27,513 @@ mod tests {
         let color = t.get_color();
         assert!(color == Rgb::from(&Vec3::from_array(colors[0])));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod rasterizer_tests {
+    use utils::rasterizer::BufRasterizer;
+    use utils::rgb::Rgb;
+
+    #[test]
+    fn buf_rasterizer_from_depth_test() {
+        let depth = [1.0, 3.0, 2.0, f32::INFINITY];
+        let r = BufRasterizer::from_depth(&depth, 2, 2);
+
+        assert_eq!(r.data[0], Rgb(255, 255, 255));
+        assert_eq!(r.data[1], Rgb(0, 0, 0));
+        assert_eq!(r.data[2], Rgb(127, 127, 127));
+        assert_eq!(r.data[3], Rgb(0, 0, 0));
+    }
+}
+
+#[cfg(test)]
+mod post_tests {
+    use glam::Vec3;
+    use utils::post::*;
+    use utils::rasterizer::{BufRasterizer, Rasterizable};
+    use utils::rgb::Rgb;
+
+    fn image(width: u32, height: u32, f: impl Fn(u32, u32) -> Vec3) -> FloatImage {
+        let mut img = FloatImage::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                img.data[(y * width + x) as usize] = f(x, y);
+            }
+        }
+        img
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.abs_diff_eq(b, 1e-4)
+    }
+
+    #[test]
+    fn gaussian_blur_test() {
+        let flat = image(8, 8, |_, _| Vec3::splat(0.5));
+        let blurred = GaussianBlur { sigma: 1.5 }.apply(&flat);
+        assert!(blurred.data.iter().all(|c| close(*c, Vec3::splat(0.5))));
+
+        // a single dot spreads out, keeping its energy and symmetry
+        let dot = image(9, 9, |x, y| match (x, y) {
+            (4, 4) => Vec3::ONE,
+            _ => Vec3::ZERO,
+        });
+        let blurred = GaussianBlur { sigma: 1.0 }.apply(&dot);
+        let sum: Vec3 = blurred.data.iter().sum();
+        assert!(close(sum, Vec3::ONE));
+        assert!(blurred.get(4, 4).x < 1.0);
+        assert!(close(blurred.get(3, 4), blurred.get(5, 4)));
+        assert!(close(blurred.get(4, 3), blurred.get(3, 4)));
+    }
+
+    #[test]
+    fn bloom_test() {
+        let img = image(9, 9, |x, y| match (x, y) {
+            (4, 4) => Vec3::ONE,
+            _ => Vec3::splat(0.2),
+        });
+        let bloom = Bloom {
+            sigma: 1.0,
+            ..Default::default()
+        };
+        let bloomed = bloom.apply(&img);
+        assert!(bloomed.get(5, 4).x > 0.2);
+        // colors under the threshold don't glow
+        assert!(close(bloomed.get(0, 0), Vec3::splat(0.2)));
+    }
+
+    #[test]
+    fn fxaa_test() {
+        // a diagonal staircase edge
+        let img = image(8, 8, |x, y| match x > y {
+            true => Vec3::ONE,
+            false => Vec3::ZERO,
+        });
+        let res = Fxaa::default().apply(&img);
+        assert_ne!(res, img);
+        assert!(res.data.iter().all(|c| c.x >= 0.0 && c.x <= 1.0));
+
+        let flat = image(8, 8, |_, _| Vec3::splat(0.3));
+        assert_eq!(Fxaa::default().apply(&flat), flat);
+    }
+
+    #[test]
+    fn vignette_test() {
+        let img = image(10, 10, |_, _| Vec3::ONE);
+        let res = Vignette::default().apply(&img);
+        assert!(close(res.get(5, 5), Vec3::ONE));
+        assert!(res.get(0, 0).x < 0.7);
+        assert!(res.get(0, 0).x < res.get(2, 2).x);
+    }
+
+    #[test]
+    fn tone_curve_test() {
+        let img = image(1, 1, |_, _| Vec3::new(0.25, 1.0, 3.0));
+        let gamma = ToneCurve::Gamma(2.0).apply(&img);
+        assert!(close(gamma.data[0], Vec3::new(0.5, 1.0, 3.0f32.sqrt())));
+        let reinhard = ToneCurve::Reinhard.apply(&img);
+        assert!(close(reinhard.data[0], Vec3::new(0.2, 0.5, 0.75)));
+        let aces = ToneCurve::Aces.apply(&img);
+        assert!(aces.data[0].x < aces.data[0].y && aces.data[0].z <= 1.0);
+    }
+
+    #[test]
+    fn color_lut_test() {
+        let c = Vec3::new(0.1, 0.45, 0.8);
+        assert!(close(ColorLut::identity(17).lookup(c), c));
+
+        // linear transformations are exact with trilinear interpolation
+        let swap = ColorLut::from_fn(4, |c| Vec3::new(c.z, c.x, 1.0 - c.y));
+        assert!(close(swap.lookup(c), Vec3::new(0.8, 0.1, 0.55)));
+    }
+
+    #[test]
+    fn post_chain_test() {
+        let r = BufRasterizer {
+            width: 2,
+            height: 1,
+            data: vec![Rgb(64, 64, 64), Rgb(255, 0, 0)],
+        };
+        let mut post = PostChain::new()
+            .with(ToneCurve::Reinhard)
+            .with(ColorLut::from_fn(2, |c| Vec3::ONE - c));
+        assert_eq!(post.len(), 2);
+        assert_eq!(post.name(1), "color lut");
+
+        let res = post.apply(&r);
+        assert_eq!(res.size(), (2, 1));
+        assert_eq!(res.data[1], Rgb(127, 255, 255));
+
+        assert!(!post.toggle(0));
+        assert!(!post.is_enabled(0));
+        let res = post.apply(&r);
+        assert_eq!(res.data[1], Rgb(0, 255, 255));
+
+        post.set_enabled(1, false);
+        assert_eq!(post.apply(&r).data, r.data);
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::time::Duration;
+    use utils::stats::{FrameLog, FrameStats};
+
+    #[test]
+    fn frame_log_test() {
+        let mut log = FrameLog::new();
+        assert!(log.average().is_none());
+
+        for i in 1..=3 {
+            log.push(FrameStats {
+                vertex: Duration::from_millis(i),
+                shade: Duration::from_millis(2 * i),
+                triangles: 10 * i,
+                fragments: 300,
+                covered: 100 * i,
+                ..Default::default()
+            });
+        }
+        let average = log.average().unwrap();
+        assert_eq!(average.vertex, Duration::from_millis(2));
+        assert_eq!(average.total(), Duration::from_millis(6));
+        assert_eq!(average.triangles, 20);
+        assert_eq!(log.frames()[2].overdraw(), 1.0);
+        assert_eq!(FrameStats::default().overdraw(), 0.0);
+
+        let path = std::env::temp_dir().join("frame_log_test.csv");
+        log.write_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("frame,vertex_ms"));
+        assert_eq!(lines[1], "0,1.000,0.000,2.000,0.000,3.000,10,300,0,100,3.000");
+        assert_eq!(
+            lines[0].split(',').count(),
+            lines[1].split(',').count()
+        );
+    }
+}
+
+#[cfg(test)]
+mod text_tests {
+    use utils::rasterizer::{BufRasterizer, RasterizableMut};
+    use utils::rgb::Rgb;
+    use utils::text::{draw_text_scaled, glyph, text_size, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+    fn frame(width: u32, height: u32) -> BufRasterizer {
+        BufRasterizer {
+            width,
+            height,
+            data: vec![Rgb::default(); (width * height) as usize],
+        }
+    }
+
+    fn lit(r: &BufRasterizer) -> Vec<(u32, u32)> {
+        (0..r.data.len() as u32)
+            .filter(|i| r.data[*i as usize] != Rgb::default())
+            .map(|i| (i % r.width, i / r.width))
+            .collect()
+    }
+
+    #[test]
+    fn draw_text_test() {
+        let mut r = frame(16, 12);
+        r.draw_text(2, 3, "L", Rgb::RED);
+        // the vertical bar of L and its bottom row, top left at (2, 3)
+        let mut expected: Vec<(u32, u32)> = (3..10).map(|y| (2, y)).collect();
+        expected.extend((3..7).map(|x| (x, 9)));
+        expected.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(lit(&r), expected);
+        assert!(r.data.iter().all(|c| *c == Rgb::RED || *c == Rgb::default()));
+
+        // the second char and the second line
+        let mut r = frame(16, 20);
+        r.draw_text(0, 0, " .\n.", Rgb::GREEN);
+        assert_eq!(lit(&r), vec![(7, 5), (8, 5), (7, 6), (8, 6), (1, 14), (2, 14), (1, 15), (2, 15)]);
+
+        // partly and fully out of the frame
+        let mut r = frame(8, 8);
+        r.draw_text(-3, -3, "#", Rgb::BLUE);
+        r.draw_text(100, -100, "#", Rgb::BLUE);
+        assert!(!lit(&r).is_empty());
+
+        let mut r = frame(16, 16);
+        draw_text_scaled(&mut r, 0, 0, "|", Rgb::RED, 2);
+        assert_eq!(lit(&r).len(), 7 * 4);
+    }
+
+    #[test]
+    fn font_test() {
+        assert_eq!(text_size("abc"), (17, GLYPH_HEIGHT));
+        assert_eq!(text_size("ab\nabcd"), (23, 2 * GLYPH_HEIGHT + 2));
+        assert_eq!(glyph('\u{e9}'), glyph('?'));
+        assert!(glyph(' ').iter().all(|row| *row == 0));
+        for c in '!'..='~' {
+            let g = glyph(c);
+            assert!(g.iter().any(|row| *row != 0), "{}", c);
+            assert!(g.iter().all(|row| *row >> GLYPH_WIDTH == 0), "{}", c);
+        }
+    }
+}
+
+mod path_tests {
+    use glam::Vec2;
+    use utils::path::{FillRule, Path, PathCommand};
+    use utils::rasterizer::{BufRasterizer, RasterizableMut};
+    use utils::rgb::Rgb;
+
+    fn frame(width: u32, height: u32) -> BufRasterizer {
+        BufRasterizer {
+            width,
+            height,
+            data: vec![Rgb::default(); (width * height) as usize],
+        }
+    }
+
+    fn coverage(r: &BufRasterizer, x: u32, y: u32) -> f32 {
+        r.data[(y * r.width + x) as usize].0 as f32 / 255.0
+    }
+
+    fn square(min: f32, max: f32, clockwise: bool) -> Vec<Vec2> {
+        let mut points = vec![
+            Vec2::new(min, min),
+            Vec2::new(max, min),
+            Vec2::new(max, max),
+            Vec2::new(min, max),
+        ];
+        if !clockwise {
+            points.reverse();
+        }
+        points
+    }
+
+    #[test]
+    fn path_flatten_test() {
+        let path = Path::new()
+            .move_to(Vec2::new(0.0, 0.0))
+            .line_to(Vec2::new(10.0, 0.0))
+            .quad_to(Vec2::new(10.0, 10.0), Vec2::new(0.0, 10.0))
+            .close()
+            .line_to(Vec2::new(-5.0, 0.0));
+        assert_eq!(path.commands[3], PathCommand::Close);
+
+        let contours = path.flatten(0.1);
+        assert_eq!(contours.len(), 2);
+        assert!(contours[0].len() > 4);
+        assert_eq!(contours[0].last(), Some(&Vec2::new(0.0, 10.0)));
+        // a contour after close starts where the closed one started
+        assert_eq!(contours[1], vec![Vec2::new(0.0, 0.0), Vec2::new(-5.0, 0.0)]);
+    }
+
+    #[test]
+    fn fill_path_test() {
+        // pixel aligned edges are sharp, half pixel ones are half covered
+        let mut r = frame(20, 20);
+        let path = Path::polygon(&square(4.0, 8.5, true));
+        r.fill_path(&path, FillRule::NonZero, Rgb(255, 255, 255));
+        assert_eq!(coverage(&r, 4, 4), 1.0);
+        assert_eq!(coverage(&r, 7, 7), 1.0);
+        assert_eq!(coverage(&r, 3, 5), 0.0);
+        assert!((coverage(&r, 8, 5) - 0.5).abs() < 0.01);
+        assert!((coverage(&r, 8, 8) - 0.25).abs() < 0.01);
+        assert_eq!(coverage(&r, 9, 5), 0.0);
+
+        // the area of a circle
+        let mut r = frame(40, 40);
+        let circle = Path::circle(Vec2::new(20.0, 20.0), 12.0);
+        r.fill_path(&circle, FillRule::EvenOdd, Rgb(255, 255, 255));
+        let area: f32 = (0..40 * 40).map(|i| coverage(&r, i % 40, i / 40)).sum();
+        let expected = std::f32::consts::PI * 144.0;
+        assert!((area - expected).abs() / expected < 0.01, "area {}", area);
+
+        // out of the frame
+        let mut r = frame(8, 8);
+        r.fill_path(&Path::polygon(&square(-10.0, 20.0, true)), FillRule::NonZero, Rgb::RED);
+        assert!(r.data.iter().all(|c| *c == Rgb::RED));
+    }
+
+    #[test]
+    fn fill_rule_test() {
+        let nested = |inner_clockwise: bool| {
+            let mut path = Path::polygon(&square(2.0, 18.0, true));
+            path.commands.extend(Path::polygon(&square(6.0, 14.0, inner_clockwise)).commands);
+            path
+        };
+        let fill = |path: &Path, rule: FillRule| {
+            let mut r = frame(20, 20);
+            r.fill_path(path, rule, Rgb(255, 255, 255));
+            (coverage(&r, 3, 10), coverage(&r, 10, 10))
+        };
+
+        // the same direction winds twice inside
+        assert_eq!(fill(&nested(true), FillRule::EvenOdd), (1.0, 0.0));
+        assert_eq!(fill(&nested(true), FillRule::NonZero), (1.0, 1.0));
+        // the opposite direction is a hole both ways
+        assert_eq!(fill(&nested(false), FillRule::EvenOdd), (1.0, 0.0));
+        assert_eq!(fill(&nested(false), FillRule::NonZero), (1.0, 0.0));
+
+        // the center of a pentagram
+        let star: Vec<Vec2> = (0..5)
+            .map(|i| {
+                let angle = i as f32 * 4.0 * std::f32::consts::PI / 5.0;
+                Vec2::new(20.0 + 18.0 * angle.sin(), 20.0 - 18.0 * angle.cos())
+            })
+            .collect();
+        let mut r = frame(40, 40);
+        r.fill_path(&Path::polygon(&star), FillRule::EvenOdd, Rgb(255, 255, 255));
+        assert_eq!(coverage(&r, 20, 20), 0.0);
+        assert_eq!(coverage(&r, 20, 5), 1.0);
+        let mut r = frame(40, 40);
+        r.fill_path(&Path::polygon(&star), FillRule::NonZero, Rgb(255, 255, 255));
+        assert_eq!(coverage(&r, 20, 20), 1.0);
+    }
+}
+
+mod svg_tests {
+    use glam::Vec2;
+    use utils::path::{FillRule, Path};
+    use utils::rgb::Rgb;
+    use utils::svg::SvgDocument;
+
+    #[test]
+    fn svg_document_test() {
+        let mut svg = SvgDocument::new(200, 100, Rgb(0, 0, 0));
+        assert!(svg.is_empty());
+        svg.circle(Vec2::new(10.0, 20.5), 3.0, Rgb::RED);
+        svg.line(Vec2::ZERO, Vec2::new(1.0 / 3.0, 2.0), Rgb::GREEN, 2.0);
+        svg.polyline(&[Vec2::ZERO, Vec2::ONE, Vec2::new(2.0, 0.0)], Rgb::BLUE, 1.0);
+        // a single point is no line
+        svg.polyline(&[Vec2::ZERO], Rgb::BLUE, 1.0);
+        let path = Path::new()
+            .move_to(Vec2::ZERO)
+            .quad_to(Vec2::new(5.0, 0.0), Vec2::new(5.0, 5.0))
+            .cubic_to(Vec2::ONE, Vec2::ONE, Vec2::ZERO)
+            .close();
+        svg.path(&path, FillRule::EvenOdd, Rgb(1, 2, 3));
+        assert_eq!(svg.len(), 4);
+
+        let text = svg.to_svg();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("<svg"));
+        assert!(lines[0].contains(r#"width="200" height="100" viewBox="0 0 200 100""#));
+        assert_eq!(lines[1], r#"<rect width="100%" height="100%" fill="rgb(0,0,0)"/>"#);
+        assert_eq!(lines[2], r#"<circle cx="10" cy="20.5" r="3" fill="rgb(255,0,0)"/>"#);
+        assert!(lines[3].contains(r#"x2="0.333" y2="2" stroke="rgb(0,255,0)""#));
+        assert!(lines[4].contains(r#"points="0 0 1 1 2 0""#));
+        assert!(lines[5].contains(r#"d="M 0 0 Q 5 0 5 5 C 1 1 1 1 0 0 Z""#));
+        assert!(lines[5].contains(r#"fill-rule="evenodd""#));
+        assert_eq!(lines[6], "</svg>");
+
+        let file = std::env::temp_dir().join("utils_svg_test.svg");
+        svg.save(&file).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), text);
+        std::fs::remove_file(&file).unwrap();
+
+        svg.clear();
+        assert!(svg.is_empty());
+    }
+}
+
+mod parallel_tests {
+    use std::sync::Mutex;
+
+    use glam::Vec3;
+    use utils::parallel::{render_tiles, tiles, TILE_SIZE};
+
+    #[test]
+    fn tiles_test() {
+        let tiles = tiles(40, 20);
+        assert_eq!(tiles.len(), 3 * 2);
+        assert_eq!(tiles[0].width, TILE_SIZE);
+        assert_eq!(tiles[2].x, 32);
+        assert_eq!(tiles[2].width, 8);
+        assert_eq!(tiles[5].height, 4);
+        let area: usize = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 40 * 20);
+    }
+
+    #[test]
+    fn render_tiles_test() {
+        let (width, height) = (53, 37);
+        let shade = |x: usize, y: usize| Vec3::new(x as f32, y as f32, (x * y) as f32);
+
+        let serial = render_tiles(width, height, 1, shade, |_| ());
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(serial[y * width + x], shade(x, y));
+            }
+        }
+
+        // the same image whatever the number of threads
+        for threads in [0, 2, 3, 8, 100] {
+            let last = Mutex::new(0.0);
+            let frame = render_tiles(width, height, threads, shade, |p| {
+                let mut last = last.lock().unwrap();
+                *last = p.max(*last);
+            });
+            assert_eq!(frame, serial);
+            assert_eq!(*last.lock().unwrap(), 1.0);
+        }
+
+        assert!(render_tiles(0, 0, 4, shade, |_| ()).is_empty());
+    }
+}
+
+mod progress_tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use utils::progress::{Progress, ProgressReporter};
+
+    #[test]
+    fn progress_display_test() {
+        let mut progress = Progress {
+            fraction: 0.5,
+            elapsed: Duration::from_secs(10),
+            eta: Some(Duration::from_secs(90)),
+            rays: 3_000_000,
+            rays_per_second: 300_000.0,
+        };
+        let text = progress.to_string();
+        assert!(text.starts_with(&format!("[{}>", "=".repeat(25))));
+        assert!(text.contains(" 50% 0.30 Mrays/s eta 1m30s"));
+
+        progress.fraction = 1.0;
+        assert!(progress.to_string().ends_with("100% 0.30 Mrays/s in 10.0s"));
+    }
+
+    #[test]
+    fn reporter_test() {
+        let observed = Arc::new(Mutex::new(vec![]));
+        let sink = observed.clone();
+        let reporter = ProgressReporter::new()
+            .quiet()
+            .with_callback(move |p| sink.lock().unwrap().push(*p));
+
+        reporter.begin();
+        reporter.update(0.0);
+        reporter.add_rays(100);
+        std::thread::sleep(Duration::from_millis(20));
+        reporter.update(0.25);
+        reporter.add_rays(300);
+        reporter.update(1.0);
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(observed.len(), 3);
+        assert_eq!(observed[0].eta, None);
+        assert_eq!(observed[1].rays, 100);
+        // three times as long again as it took to do a quarter
+        let eta = observed[1].eta.unwrap().as_secs_f64();
+        assert!((eta - 3.0 * observed[1].elapsed.as_secs_f64()).abs() < 1e-3);
+        assert!(observed[1].rays_per_second > 0.0);
+        assert_eq!(observed[2].rays, 400);
+        assert_eq!(observed[2].eta, Some(Duration::ZERO));
+
+        // counting again from the start
+        reporter.begin();
+        assert_eq!(reporter.progress(0.0).rays, 0);
+    }
+}